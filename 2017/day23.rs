@@ -6,232 +6,40 @@ use std::cmp::{min, max};
 use std::convert::TryFrom;
 use std::collections::*;
 use std::hash::Hash;
+use std::time::Instant;
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
-enum Reg {
-    A,B,C,D,E,F,G,H,
-}
-type Imm = i64;
-// Dst, Src
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
-enum Inst {
-    Set(Reg, Reg),
-    SetImm(Reg, Imm),
-    Sub(Reg, Reg),
-    SubImm(Reg, Imm),
-    Mul(Reg, Reg),
-    MulImm(Reg, Imm),
-    Jnz(Reg, Reg),
-    JnzImm(Reg, Imm),
-    JnzImmImm(Imm, Imm),
-    JnzImmReg(Imm, Reg),
-}
-
-fn parse_reg(input: &str) -> Reg {
-    let regc = input.chars().next().unwrap();
-    match regc {
-        'a' => Reg::A,
-        'b' => Reg::B,
-        'c' => Reg::C,
-        'd' => Reg::D,
-        'e' => Reg::E,
-        'f' => Reg::F,
-        'g' => Reg::G,
-        'h' => Reg::H,
-        _ => {
-            unreachable!("{}", regc);
-        }
-    }
-}
-
-enum RegOrImm {
-    Imm(Imm),
-    Reg(Reg),
-}
-
-fn parse_regimm(input: &str) -> RegOrImm {
-    input.parse::<Imm>()
-        .map(|i| RegOrImm::Imm(i))
-        .unwrap_or_else(|_| RegOrImm::Reg(parse_reg(input)))
-}
-
-fn parse(input: &str) -> Vec<Inst> {
-    input.lines().map(|line| {
-        let mut words = line.split(' ');
-        let verb = words.next().unwrap();
-        //dbg!(line);
-        let dst = parse_regimm(words.next().unwrap());
-        let src = parse_regimm(words.next().unwrap());
-
-        match (verb, dst, src) {
-            ("set", RegOrImm::Reg(dst), RegOrImm::Reg(r)) => Inst::Set(dst, r),
-            ("set", RegOrImm::Reg(dst), RegOrImm::Imm(i)) => Inst::SetImm(dst, i),
-            ("sub", RegOrImm::Reg(dst), RegOrImm::Reg(r)) => Inst::Sub(dst, r),
-            ("sub", RegOrImm::Reg(dst), RegOrImm::Imm(i)) => Inst::SubImm(dst, i),
-            ("mul", RegOrImm::Reg(dst), RegOrImm::Reg(r)) => Inst::Mul(dst, r),
-            ("mul", RegOrImm::Reg(dst), RegOrImm::Imm(i)) => Inst::MulImm(dst, i),
-            ("jnz", RegOrImm::Reg(dst), RegOrImm::Reg(r)) => Inst::Jnz(dst, r),
-            ("jnz", RegOrImm::Reg(dst), RegOrImm::Imm(i)) => Inst::JnzImm(dst, i),
-            ("jnz", RegOrImm::Imm(dsti), RegOrImm::Reg(r)) => Inst::JnzImmReg(dsti, r),
-            ("jnz", RegOrImm::Imm(dsti), RegOrImm::Imm(i)) => Inst::JnzImmImm(dsti, i),
-            _ => {
-                unreachable!();
-            }
-        }
-    }).collect()
-}
-
-struct Machine {
-    regs: [i64; 8],
-    pc: isize,
-    program: Vec<Inst>,
+use aoc::coproc::{Inst, Machine, Reg, parse};
 
-    muls: u64,
-    profile: Vec<u64>,
-    insn_lim: u64,
-}
-
-impl Machine {
-    fn new(program: &[Inst]) -> Self {
-        Self {
-            regs: [0; 8],
-            pc: 0,
-            program: program.to_vec(),
-            muls: 0,
-            profile: Vec::new(),
-            insn_lim: u64::MAX,
-        }
-    }
-
-    fn run(&mut self) {
-        let endpc = self.program.len() as isize;
-        while self.pc < endpc {
-            match self.program[self.pc as usize] {
-                Inst::Set(dst, src) => {
-                    self.regs[dst as usize] = self.regs[src as usize];
-                }
-                Inst::SetImm(dst, src) => {
-                    self.regs[dst as usize] = src;
-                }
-                Inst::Sub(dst, src) => {
-                    self.regs[dst as usize] -= self.regs[src as usize];
-                }
-                Inst::SubImm(dst, src) => {
-                    self.regs[dst as usize] -= src;
-                }
-                Inst::Mul(dst, src) => {
-                    self.regs[dst as usize] *= self.regs[src as usize];
-                    self.muls += 1;
-                }
-                Inst::MulImm(dst, src) => {
-                    self.regs[dst as usize] *= src;
-                    self.muls += 1;
-                }
-                Inst::Jnz(dst, src) => {
-                    if self.regs[dst as usize] != 0 {
-                        self.pc += (self.regs[src as usize] as isize) - 1;
-                        assert!(self.pc >= -1);
-                    }
-                }
-                Inst::JnzImm(dst, src) => {
-                    if self.regs[dst as usize] != 0 {
-                        self.pc += (src as isize) - 1;
-                        assert!(self.pc >= -1);
-                    }
-                }
-                Inst::JnzImmImm(dst, src) => {
-                    if dst != 0 {
-                        self.pc += (src as isize) - 1;
-                        assert!(self.pc >= -1);
-                    }
-                }
-                Inst::JnzImmReg(dst, src) => {
-                    if dst != 0 {
-                        self.pc += (self.regs[src as usize] as isize) - 1;
-                        assert!(self.pc >= -1);
-                    }
-                }
-            };
-            self.pc += 1;
-        }
+/// `Machine::run` plus a peephole optimization of the hot inner loop.
+fn run_part2(emu: &mut Machine) {
+    let endpc = emu.program.len() as isize;
+    while emu.profile.len() < endpc as usize {
+        emu.profile.push(0);
     }
 
-    fn run_part2(&mut self) {
-        let endpc = self.program.len() as isize;
-        while self.profile.len() < endpc as usize {
-            self.profile.push(0);
+    while emu.pc < endpc {
+        emu.profile[emu.pc as usize] += 1;
+        emu.insn_lim -= 1;
+        if emu.insn_lim == 0 {
+            return;
         }
 
-        while self.pc < endpc {
-            self.profile[self.pc as usize] += 1;
-            self.insn_lim -= 1;
-            if self.insn_lim == 0 {
-                return;
-            }
-
-            // Peephole optimizer
-            if self.pc == 12 - 1 {
-                self.regs[Reg::G as usize] = 0;
+        // Peephole optimizer
+        if emu.pc == 12 - 1 {
+            emu.regs[Reg::G as usize] = 0;
 
-                let b = self.regs[Reg::B as usize];
-                let d = self.regs[Reg::D as usize];
-                if b % d == 0 && b != d {
-                    self.regs[Reg::F as usize] = 0;
-                }
-
-                self.regs[Reg::E as usize] = b;
-                self.pc = 21 - 1;
-                continue;
+            let b = emu.regs[Reg::B as usize];
+            let d = emu.regs[Reg::D as usize];
+            if b % d == 0 && b != d {
+                emu.regs[Reg::F as usize] = 0;
             }
 
-            match self.program[self.pc as usize] {
-                Inst::Set(dst, src) => {
-                    self.regs[dst as usize] = self.regs[src as usize];
-                }
-                Inst::SetImm(dst, src) => {
-                    self.regs[dst as usize] = src;
-                }
-                Inst::Sub(dst, src) => {
-                    self.regs[dst as usize] -= self.regs[src as usize];
-                }
-                Inst::SubImm(dst, src) => {
-                    self.regs[dst as usize] -= src;
-                }
-                Inst::Mul(dst, src) => {
-                    self.regs[dst as usize] *= self.regs[src as usize];
-                    self.muls += 1;
-                }
-                Inst::MulImm(dst, src) => {
-                    self.regs[dst as usize] *= src;
-                    self.muls += 1;
-                }
-                Inst::Jnz(dst, src) => {
-                    if self.regs[dst as usize] != 0 {
-                        self.pc += (self.regs[src as usize] as isize) - 1;
-                        assert!(self.pc >= -1);
-                    }
-                }
-                Inst::JnzImm(dst, src) => {
-                    if self.regs[dst as usize] != 0 {
-                        self.pc += (src as isize) - 1;
-                        assert!(self.pc >= -1);
-                    }
-                }
-                Inst::JnzImmImm(dst, src) => {
-                    if dst != 0 {
-                        self.pc += (src as isize) - 1;
-                        assert!(self.pc >= -1);
-                    }
-                }
-                Inst::JnzImmReg(dst, src) => {
-                    if dst != 0 {
-                        self.pc += (self.regs[src as usize] as isize) - 1;
-                        assert!(self.pc >= -1);
-                    }
-                }
-            };
-            self.pc += 1;
+            emu.regs[Reg::E as usize] = b;
+            emu.pc = 21 - 1;
+            continue;
         }
+
+        emu.step();
     }
 }
 
@@ -274,18 +82,44 @@ fn part2(program: &[Inst]) -> i64 {
     // }
     //
     //emu.insn_lim = 1000_000_000;
-    emu.run_part2();
+    run_part2(&mut emu);
     //dbg!(emu.profile);
 
     emu.regs[Reg::H as usize]
 }
 
+/// Time part 1 on the interpreter and on the build-time compiled program (`aoc::aot`).
+fn bench_backends(program: &[Inst]) {
+    const RUNS: u32 = 100;
+    let time = |name: &str, f: &dyn Fn() -> u64| -> u64 {
+        let start = Instant::now();
+        let res = (0..RUNS).map(|_| f()).last().unwrap();
+        let elapsed = start.elapsed();
+        println!("{:>12}: {:?} ({:.1} us/run)", name, elapsed, elapsed.as_micros() as f64 / RUNS as f64);
+        res
+    };
+
+    let interp = time("interpreter", &|| part1(program));
+    match aoc::aot::DAY23_2017 {
+        Some(aot) => {
+            let compiled = time("aot", &|| aot([0; 8], &[]).1);
+            assert_eq!(compiled, interp);
+        }
+        None => println!("{:>12}: input wasn't cached at build time", "aot"),
+    }
+}
+
 fn main() -> Result<()> {
     let mut puzzle = aoc::Puzzle::new(2017, 23)?;
     let data = puzzle.get_data()?;
 
     let data = parse(data);
 
+    if std::env::args().any(|a| a == "--bench") {
+        bench_backends(&data);
+        return Ok(());
+    }
+
     let answ1 = part1(&data);
     dbg!(answ1);
     assert_eq!(answ1, 5929);
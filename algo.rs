@@ -394,3 +394,39 @@ mod tests4 {
         assert_eq!(pt, &vertices[2][2]);
     }
 }
+
+/// Xorshift64: a tiny deterministic PRNG for benchmarks, tests and randomized fallbacks.
+#[derive(Clone, Debug)]
+pub struct XorShift(u64);
+
+impl XorShift {
+    /// Xorshift is stuck at zero, so the low bit of `seed` is forced on.
+    pub fn new(seed: u64) -> Self {
+        Self(seed | 1)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Roughly uniform in `0..n`.
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+}
+
+#[cfg(test)]
+mod tests5 {
+    use super::*;
+
+    #[test]
+    fn xorshift() {
+        let mut rng = XorShift::new(0);
+        assert!((0..100).all(|_| rng.next_u64() != 0));
+        let (mut a, mut b) = (XorShift::new(42), XorShift::new(42));
+        assert!((0..100).all(|_| a.below(10) == b.below(10)));
+    }
+}
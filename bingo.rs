@@ -0,0 +1,234 @@
+//! Playing bingo against many boards at once (2021 day 4).
+//!
+//! Boards can be any size, and each may differ.  Every number is indexed to the cells it occupies
+//! across all boards, so a call marks in time proportional to its occurrences, and each board
+//! keeps a count of marked cells per line to notice a win as soon as it happens.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+pub type Num = u32;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// Line number (from 1) that doesn't parse.
+    Syntax(usize),
+    /// Line number (from 1) of a board row whose length differs from the board's first row.
+    Ragged(usize),
+    EmptyBoard,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Syntax(line) => write!(f, "line {}: expected numbers", line),
+            Error::Ragged(line) => write!(f, "line {}: row length differs from the rest of its board", line),
+            Error::EmptyBoard => write!(f, "empty board"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Board {
+    rows: usize,
+    cols: usize,
+    /// Row-major.
+    cells: Vec<Num>,
+}
+
+impl Board {
+    pub fn new(rows: Vec<Vec<Num>>) -> Result<Self, Error> {
+        let cols = rows.first().map_or(0, |r| r.len());
+        if cols == 0 {
+            return Err(Error::EmptyBoard);
+        }
+        if let Some(i) = rows.iter().position(|r| r.len() != cols) {
+            return Err(Error::Ragged(i + 1));
+        }
+        Ok(Self { rows: rows.len(), cols, cells: rows.concat() })
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> Num {
+        self.cells[row * self.cols + col]
+    }
+
+    /// Indices into a board's line counts of the lines through `cell`: its row, its column, then
+    /// any diagonals, which only square boards have.
+    fn lines(&self, cell: usize, diagonals: bool) -> impl Iterator<Item = usize> {
+        let (r, c, n) = (cell / self.cols, cell % self.cols, self.rows);
+        let square = diagonals && self.rows == self.cols;
+        let diag = if square && r == c { Some(self.rows + self.cols) } else { None };
+        let anti = if square && r + c == n - 1 { Some(self.rows + self.cols + 1) } else { None };
+        std::iter::once(r).chain(std::iter::once(self.rows + c)).chain(diag).chain(anti)
+    }
+
+    /// Cells needed to complete line `line`.
+    fn line_len(&self, line: usize) -> usize {
+        if line >= self.rows && line < self.rows + self.cols { self.rows } else { self.cols }
+    }
+}
+
+/// A board completing a line.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Win {
+    pub board: usize,
+    /// Index into the calls of the winning call.
+    pub turn: usize,
+    pub call: Num,
+    /// Sum of the board's unmarked numbers, times `call`.
+    pub score: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct Bingo {
+    calls: Vec<Num>,
+    boards: Vec<Board>,
+    diagonals: bool,
+}
+
+impl Bingo {
+    pub fn new(calls: Vec<Num>, boards: Vec<Board>) -> Self {
+        Self { calls, boards, diagonals: false }
+    }
+
+    /// Also win on a board's two diagonals, if it's square.
+    pub fn diagonals(mut self, diagonals: bool) -> Self {
+        self.diagonals = diagonals;
+        self
+    }
+
+    pub fn calls(&self) -> &[Num] {
+        &self.calls
+    }
+
+    pub fn boards(&self) -> &[Board] {
+        &self.boards
+    }
+
+    /// Every board that ever wins, in the order they win.  Boards completing on the same call are
+    /// in board order.  A board only wins once; repeated calls mark nothing new.
+    pub fn play(&self) -> Vec<Win> {
+        let mut index: HashMap<Num, Vec<(usize, usize)>> = HashMap::new();
+        for (b, board) in self.boards.iter().enumerate() {
+            for (cell, &n) in board.cells.iter().enumerate() {
+                index.entry(n).or_default().push((b, cell));
+            }
+        }
+
+        let mut marked = self.boards.iter().map(|b| vec![false; b.cells.len()]).collect::<Vec<_>>();
+        let mut line_counts = self.boards.iter().map(|b| vec![0; b.rows + b.cols + 2]).collect::<Vec<_>>();
+        let mut unmarked = self.boards.iter().map(|b| b.cells.iter().map(|&n| n as u64).sum::<u64>()).collect::<Vec<_>>();
+        let mut won = vec![false; self.boards.len()];
+        let mut wins = Vec::new();
+
+        for (turn, &call) in self.calls.iter().enumerate() {
+            let mut winners = Vec::new();
+            for &(b, cell) in index.get(&call).into_iter().flatten() {
+                if won[b] || marked[b][cell] {
+                    continue;
+                }
+                marked[b][cell] = true;
+                unmarked[b] -= call as u64;
+                let board = &self.boards[b];
+                for line in board.lines(cell, self.diagonals) {
+                    line_counts[b][line] += 1;
+                    if line_counts[b][line] == board.line_len(line) && !winners.contains(&b) {
+                        winners.push(b);
+                    }
+                }
+            }
+            // Scores count every cell the call marks, even past the completing one.
+            winners.sort_unstable();
+            for b in winners {
+                won[b] = true;
+                wins.push(Win { board: b, turn, call, score: unmarked[b] * call as u64 });
+            }
+            if wins.len() == self.boards.len() {
+                break;
+            }
+        }
+        wins
+    }
+}
+
+impl FromStr for Bingo {
+    type Err = Error;
+
+    /// The puzzle format: comma-separated calls, then boards of whitespace-separated numbers,
+    /// separated by blank lines.
+    fn from_str(s: &str) -> Result<Self, Error> {
+        fn numbers<'a>(i: usize, words: impl Iterator<Item = &'a str>) -> Result<Vec<Num>, Error> {
+            words.map(|w| w.trim().parse().map_err(|_| Error::Syntax(i + 1))).collect()
+        }
+
+        let mut lines = s.lines().enumerate();
+        let calls = match lines.next() {
+            Some((i, line)) => numbers(i, line.trim().split(','))?,
+            None => return Err(Error::Syntax(1)),
+        };
+
+        let mut boards = Vec::new();
+        let mut rows: Vec<Vec<Num>> = Vec::new();
+        let mut first = 0;
+        for (i, line) in lines.chain(std::iter::once((usize::MAX, ""))) {
+            if line.trim().is_empty() {
+                if !rows.is_empty() {
+                    boards.push(Board::new(std::mem::take(&mut rows)).map_err(|e| match e {
+                        Error::Ragged(r) => Error::Ragged(first + r),
+                        e => e,
+                    })?);
+                }
+                continue;
+            }
+            if rows.is_empty() {
+                first = i;
+            }
+            rows.push(numbers(i, line.split_ascii_whitespace())?);
+        }
+        Ok(Bingo::new(calls, boards))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shapes() {
+        let calls = vec![1, 5, 9, 2, 3, 4, 7];
+        let square = Board::new(vec![vec![1, 2, 3], vec![4, 5, 6], vec![7, 8, 9]]).unwrap();
+        let wide = Board::new(vec![vec![2, 3, 4, 5], vec![6, 7, 8, 10]]).unwrap();
+        let game = Bingo::new(calls, vec![square, wide]);
+
+        let plain = game.play();
+        assert_eq!(plain, vec![
+            Win { board: 0, turn: 4, call: 3, score: 3 * (4 + 6 + 7 + 8) },
+            Win { board: 1, turn: 5, call: 4, score: 4 * (6 + 7 + 8 + 10) },
+        ]);
+
+        // 1, 5, 9 is a diagonal; the 2x4 board has none.
+        let diag = game.clone().diagonals(true).play();
+        assert_eq!(diag[0], Win { board: 0, turn: 2, call: 9, score: 9 * (2 + 3 + 4 + 6 + 7 + 8) });
+        assert_eq!(diag[1], plain[1]);
+
+        let text = "3,1,2\n\n1 2\n3 4\n\n 5  6  7\n 3  1  2\n";
+        let game = text.parse::<Bingo>().unwrap();
+        assert_eq!(game.boards()[1].get(1, 2), 2);
+        assert_eq!(game.play().iter().map(|w| (w.board, w.turn)).collect::<Vec<_>>(), vec![(0, 1), (1, 2)]);
+
+        assert_eq!("1,2\n\n1 2\n3".parse::<Bingo>().unwrap_err(), Error::Ragged(4));
+        assert_eq!("1,x".parse::<Bingo>().unwrap_err(), Error::Syntax(1));
+        assert_eq!("1\n\n1 2\n\n3 y".parse::<Bingo>().unwrap_err(), Error::Syntax(5));
+    }
+}
@@ -0,0 +1,398 @@
+//! The 2021 day 16 BITS transmission format: packet AST, decoder, encoder and evaluator.
+
+use anyhow::{anyhow, Result};
+use bitvec::mem::BitMemory;
+use bitvec::prelude::*;
+use std::fmt;
+
+use crate::{BitCursor, BitError, BitWriter, OutOfBits};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum OpType {
+    Sum,
+    Product,
+    Min,
+    Max,
+    Gt,
+    Lt,
+    Eq,
+}
+
+impl OpType {
+    fn from_type_id(id: u8) -> Option<Self> {
+        Some(match id {
+            0 => OpType::Sum,
+            1 => OpType::Product,
+            2 => OpType::Min,
+            3 => OpType::Max,
+            5 => OpType::Gt,
+            6 => OpType::Lt,
+            7 => OpType::Eq,
+            _ => return None,
+        })
+    }
+
+    fn type_id(self) -> u8 {
+        match self {
+            OpType::Sum => 0,
+            OpType::Product => 1,
+            OpType::Min => 2,
+            OpType::Max => 3,
+            OpType::Gt => 5,
+            OpType::Lt => 6,
+            OpType::Eq => 7,
+        }
+    }
+
+    fn sym(self) -> &'static str {
+        match self {
+            OpType::Sum => "+",
+            OpType::Product => "*",
+            OpType::Min => "min",
+            OpType::Max => "max",
+            OpType::Gt => ">",
+            OpType::Lt => "<",
+            OpType::Eq => "=",
+        }
+    }
+}
+
+/// How an operator packet declares the extent of its sub-packets.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum LengthType {
+    /// Total length in bits (15-bit field).
+    Bits,
+    /// Number of sub-packets (11-bit field).
+    Count,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Body {
+    Literal(u64),
+    Operator {
+        op: OpType,
+        length_type: LengthType,
+        subpackets: Vec<Packet>,
+    },
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Packet {
+    pub version: u8,
+    pub body: Body,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DecodeErrorKind {
+    /// Needed this many more bits than were left.
+    Truncated(usize),
+    /// Sub-packets ran past the declared bit length.
+    Overrun,
+    /// Literal doesn't fit in a u64.
+    LiteralOverflow,
+    /// Comparisons need exactly two operands, min and max at least one.
+    Arity(OpType, usize),
+    /// Non-zero bits after the outermost packet.
+    TrailingData,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DecodeError {
+    /// Bit offset into the transmission.
+    pub pos: usize,
+    pub kind: DecodeErrorKind,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            DecodeErrorKind::Truncated(n) => write!(f, "bit {}: truncated, needed {} more bits", self.pos, n),
+            DecodeErrorKind::Overrun => write!(f, "bit {}: sub-packets overrun declared length", self.pos),
+            DecodeErrorKind::LiteralOverflow => write!(f, "bit {}: literal overflows u64", self.pos),
+            DecodeErrorKind::Arity(op, n) => write!(f, "bit {}: {:?} packet with {} sub-packets", self.pos, op, n),
+            DecodeErrorKind::TrailingData => write!(f, "bit {}: trailing data after packet", self.pos),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl From<OutOfBits> for DecodeError {
+    fn from(e: OutOfBits) -> Self {
+        DecodeError { pos: e.pos, kind: DecodeErrorKind::Truncated(e.wanted - e.remaining) }
+    }
+}
+
+struct Decoder<'a> {
+    curs: BitCursor<'a, Msb0>,
+}
+
+impl<'a> Decoder<'a> {
+    fn pos(&self) -> usize {
+        self.curs.position()
+    }
+
+    fn error(&self, pos: usize, kind: DecodeErrorKind) -> DecodeError {
+        DecodeError { pos, kind }
+    }
+
+    fn read<T: BitMemory>(&mut self, bits: usize) -> Result<T, DecodeError> {
+        match self.curs.try_parse_be::<T>(bits) {
+            Ok(v) => Ok(v),
+            Err(BitError::OutOfBits(e)) => Err(e.into()),
+            Err(e @ BitError::Width { .. }) => unreachable!("field widths are fixed: {}", e),
+        }
+    }
+
+    /// Decode packets until `sub` is used up.  Running off its end means a sub-packet overran
+    /// the declared length.
+    fn packets_in(&mut self, sub: BitCursor<'a, Msb0>, out: &mut Vec<Packet>) -> Result<(), DecodeError> {
+        let outer = std::mem::replace(&mut self.curs, sub);
+        let mut res = Ok(());
+        while self.curs.remaining() > 0 {
+            let start = self.pos();
+            match self.packet() {
+                Ok(p) => out.push(p),
+                Err(DecodeError { kind: DecodeErrorKind::Truncated(_), .. }) => {
+                    res = Err(self.error(start, DecodeErrorKind::Overrun));
+                    break;
+                }
+                Err(e) => {
+                    res = Err(e);
+                    break;
+                }
+            }
+        }
+        self.curs = outer;
+        res
+    }
+
+    /// Decode one packet, recursing into nested packets.
+    fn packet(&mut self) -> Result<Packet, DecodeError> {
+        let start = self.pos();
+        // The first three bits encode the packet _version_.
+        let version = self.read::<u8>(3)?;
+        // The next three bits encode the packet _type ID_.
+        let type_ = self.read::<u8>(3)?;
+
+        let op = match OpType::from_type_id(type_) {
+            Some(op) => op,
+            // Packets with type ID 4 represent a _literal value_.  Literals are represented
+            // using a UTF8-like encoding; each nibble (MSB first) is prefixed with a continue bit.
+            None => {
+                let mut res = 0u64;
+                loop {
+                    let group_pos = self.pos();
+                    let next = self.read::<u8>(5)?;
+                    if res >> 60 != 0 {
+                        return Err(self.error(group_pos, DecodeErrorKind::LiteralOverflow));
+                    }
+                    res = (res << 4) | (next & 0xf) as u64;
+                    if (next & 0x10) == 0 {
+                        break;
+                    }
+                }
+                return Ok(Packet { version, body: Body::Literal(res) });
+            }
+        };
+
+        // Every other type of packet represents an _operator_; the first bit determines the
+        // _length type ID_.
+        let mut subpackets = Vec::new();
+        let length_type = if self.read::<u8>(1)? == 0 {
+            // The next 15 bits represent the _total length in bits_ of the sub-packets.
+            let tlen = self.read::<u16>(15)? as usize;
+            let sub = self.curs.sub_cursor(tlen)?;
+            self.packets_in(sub, &mut subpackets)?;
+            LengthType::Bits
+        } else {
+            // The next 11 bits represent the _number of sub-packets immediately contained_.
+            let nsubpackets = self.read::<u16>(11)?;
+            for _ in 0..nsubpackets {
+                subpackets.push(self.packet()?);
+            }
+            LengthType::Count
+        };
+
+        let arity_ok = match op {
+            OpType::Gt | OpType::Lt | OpType::Eq => subpackets.len() == 2,
+            OpType::Min | OpType::Max => !subpackets.is_empty(),
+            OpType::Sum | OpType::Product => true,
+        };
+        if !arity_ok {
+            return Err(self.error(start, DecodeErrorKind::Arity(op, subpackets.len())));
+        }
+
+        Ok(Packet { version, body: Body::Operator { op, length_type, subpackets } })
+    }
+}
+
+impl Packet {
+    /// Decode a whole transmission: one packet, followed only by zero padding.
+    pub fn decode(bits: &BitSlice<Msb0, u8>) -> Result<Packet, DecodeError> {
+        let mut dec = Decoder { curs: BitCursor::new(bits) };
+        let packet = dec.packet()?;
+        if let Some(i) = dec.curs.as_slice().first_one() {
+            return Err(dec.error(dec.pos() + i, DecodeErrorKind::TrailingData));
+        }
+        Ok(packet)
+    }
+
+    pub fn from_hex(hex: &str) -> Result<Packet> {
+        let bytes = hex::decode(hex.trim_end())?;
+        Ok(Packet::decode(bytes.view_bits::<Msb0>())?)
+    }
+
+    pub fn encode(&self, out: &mut BitWriter<Msb0>) -> Result<()> {
+        if self.version > 7 {
+            return Err(anyhow!("Version {} doesn't fit in 3 bits", self.version));
+        }
+        out.write_be(self.version, 3)?;
+        match &self.body {
+            Body::Literal(val) => {
+                out.write_be(4u8, 3)?;
+                let groups = 16 - val.leading_zeros() as usize / 4;
+                for g in (0..groups.max(1)).rev() {
+                    out.write_be((g != 0) as u8, 1)?;
+                    out.write_be((val >> (4 * g)) as u8 & 0xf, 4)?;
+                }
+            }
+            Body::Operator { op, length_type, subpackets } => {
+                out.write_be(op.type_id(), 3)?;
+                let mut sub = BitWriter::new();
+                for p in subpackets.iter() {
+                    p.encode(&mut sub)?;
+                }
+                match length_type {
+                    LengthType::Bits => {
+                        if sub.position() >= 1 << 15 {
+                            return Err(anyhow!("{} bits of sub-packets don't fit in 15 bits", sub.position()));
+                        }
+                        out.write_be(0u8, 1)?;
+                        out.write_be(sub.position() as u16, 15)?;
+                    }
+                    LengthType::Count => {
+                        if subpackets.len() >= 1 << 11 {
+                            return Err(anyhow!("{} sub-packets don't fit in 11 bits", subpackets.len()));
+                        }
+                        out.write_be(1u8, 1)?;
+                        out.write_be(subpackets.len() as u16, 11)?;
+                    }
+                }
+                out.write_bits(sub.as_slice());
+            }
+        }
+        Ok(())
+    }
+
+    /// Upper-case hex, zero-padded to a whole number of bytes.
+    pub fn to_hex(&self) -> Result<String> {
+        let mut out = BitWriter::new();
+        self.encode(&mut out)?;
+        Ok(hex::encode_upper(out.into_bytes()))
+    }
+
+    pub fn version_sum(&self) -> u64 {
+        let subs = match &self.body {
+            Body::Literal(_) => 0,
+            Body::Operator { subpackets, .. } => subpackets.iter().map(|p| p.version_sum()).sum(),
+        };
+        self.version as u64 + subs
+    }
+
+    /// Evaluate the expression, or `None` if it overflows a u64.
+    pub fn eval(&self) -> Option<u64> {
+        let (op, subpackets) = match &self.body {
+            Body::Literal(val) => return Some(*val),
+            Body::Operator { op, subpackets, .. } => (*op, subpackets),
+        };
+        let vals = subpackets.iter().map(|p| p.eval()).collect::<Option<Vec<_>>>()?;
+        match op {
+            OpType::Sum => vals.iter().try_fold(0u64, |acc, &v| acc.checked_add(v)),
+            OpType::Product => vals.iter().try_fold(1u64, |acc, &v| acc.checked_mul(v)),
+            OpType::Min => vals.iter().copied().min(),
+            OpType::Max => vals.iter().copied().max(),
+            OpType::Gt => Some((vals[0] > vals[1]) as u64),
+            OpType::Lt => Some((vals[0] < vals[1]) as u64),
+            OpType::Eq => Some((vals[0] == vals[1]) as u64),
+        }
+    }
+
+    fn fmt_indented(&self, f: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+        write!(f, "{:1$}v{2}:", "", 2 * depth, self.version)?;
+        match &self.body {
+            Body::Literal(val) => write!(f, "{}", val),
+            Body::Operator { op, subpackets, .. } => {
+                write!(f, "({}", op.sym())?;
+                for p in subpackets.iter() {
+                    writeln!(f)?;
+                    p.fmt_indented(f, depth + 1)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+/// S-expression, e.g. `(+ 1 (* 2 3))`.  The alternate form `{:#}` puts each packet on its own
+/// indented line, prefixed with its version.
+impl fmt::Display for Packet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if f.alternate() {
+            return self.fmt_indented(f, 0);
+        }
+        match &self.body {
+            Body::Literal(val) => write!(f, "{}", val),
+            Body::Operator { op, subpackets, .. } => {
+                write!(f, "({}", op.sym())?;
+                for p in subpackets.iter() {
+                    write!(f, " {}", p)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        for hex in ["D2FE28", "38006F45291200", "EE00D40C823060", "9C0141080250320F1802104A08"].iter() {
+            let packet = Packet::from_hex(hex).unwrap();
+            assert_eq!(&packet.to_hex().unwrap(), hex);
+        }
+        let packet = Packet::from_hex("9C0141080250320F1802104A08").unwrap();
+        assert_eq!(packet.to_string(), "(= (+ 1 3) (* 2 2))");
+        assert_eq!(format!("{:#}", packet), "\
+v4:(=
+  v2:(+
+    v2:1
+    v4:3)
+  v6:(*
+    v0:2
+    v2:2))");
+    }
+
+    #[test]
+    fn errors() {
+        let err = |hex| Packet::from_hex(hex).unwrap_err().downcast::<DecodeError>().unwrap();
+        // Literal cut off before its third group.
+        assert_eq!(err("D2FE"), DecodeError { pos: 16, kind: DecodeErrorKind::Truncated(5) });
+        assert_eq!(err("D2FE2801"), DecodeError { pos: 31, kind: DecodeErrorKind::TrailingData });
+        // Sum declaring 11 bits of sub-packets, holding a 16-bit literal.
+        assert_eq!(err("00002C4888"), DecodeError { pos: 22, kind: DecodeErrorKind::Overrun });
+        // Less-than with a single operand.
+        assert_eq!(err("3A004428"), DecodeError { pos: 0, kind: DecodeErrorKind::Arity(OpType::Lt, 1) });
+
+        let big = Packet { version: 0, body: Body::Literal(u64::MAX) };
+        let product = Packet {
+            version: 0,
+            body: Body::Operator { op: OpType::Product, length_type: LengthType::Count, subpackets: vec![big.clone(), big] },
+        };
+        assert_eq!(product.eval(), None);
+        assert_eq!(Packet::from_hex(&product.to_hex().unwrap()).unwrap(), product);
+    }
+}
@@ -0,0 +1,164 @@
+//! Checking nested delimiters, like 2021 day 10's navigation subsystem.
+
+use std::fmt;
+
+/// Result of checking one line.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Check {
+    /// Every delimiter closed, in order.
+    Valid,
+    /// A closer at character `pos` doesn't match the innermost open delimiter, whose closer was
+    /// `expected` (`None` if nothing was open).
+    Corrupted { pos: usize, expected: Option<char>, found: char },
+    /// Fine so far, but still open; `completion` closes everything.
+    Incomplete { completion: String },
+    /// Character `pos` isn't a delimiter, and those aren't being ignored.
+    Unexpected { pos: usize, found: char },
+}
+
+#[derive(Clone, Debug)]
+pub struct Delimiters {
+    /// (open, close)
+    pairs: Vec<(char, char)>,
+    ignore_others: bool,
+}
+
+impl Delimiters {
+    pub fn new(pairs: &[(char, char)]) -> Self {
+        Self { pairs: pairs.to_vec(), ignore_others: false }
+    }
+
+    /// `()`, `[]`, `{}` and `<>`.
+    pub fn standard() -> Self {
+        Self::new(&[('(', ')'), ('[', ']'), ('{', '}'), ('<', '>')])
+    }
+
+    /// Skip characters that aren't delimiters instead of reporting them.
+    pub fn ignore_others(mut self, ignore: bool) -> Self {
+        self.ignore_others = ignore;
+        self
+    }
+
+    pub fn check(&self, line: &str) -> Check {
+        // Closers expected, innermost last.
+        let mut stack = Vec::new();
+        for (pos, c) in line.chars().enumerate() {
+            // A delimiter that's its own closer, like `|`, closes if it's the one expected.
+            if stack.last() == Some(&c) && self.pairs.iter().any(|&(open, close)| open == c && close == c) {
+                stack.pop();
+            } else if let Some(&(_, close)) = self.pairs.iter().find(|(open, _)| *open == c) {
+                stack.push(close);
+            } else if self.pairs.iter().any(|(_, close)| *close == c) {
+                let expected = stack.pop();
+                if expected != Some(c) {
+                    return Check::Corrupted { pos, expected, found: c };
+                }
+            } else if !self.ignore_others {
+                return Check::Unexpected { pos, found: c };
+            }
+        }
+        if stack.is_empty() {
+            Check::Valid
+        } else {
+            Check::Incomplete { completion: stack.iter().rev().collect() }
+        }
+    }
+}
+
+/// A line's score doesn't fit in a `u64`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Overflow;
+
+impl fmt::Display for Overflow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "score overflows u64")
+    }
+}
+
+impl std::error::Error for Overflow {}
+
+/// Points for a checked line, if it scores at all.
+pub trait Scoring {
+    fn score(&self, check: &Check) -> Result<Option<u64>, Overflow>;
+}
+
+/// Scores corrupted lines by the closer found.
+pub struct SyntaxErrorScore {
+    pub points: Vec<(char, u64)>,
+}
+
+impl SyntaxErrorScore {
+    pub fn standard() -> Self {
+        Self { points: vec![(')', 3), (']', 57), ('}', 1197), ('>', 25137)] }
+    }
+}
+
+impl Scoring for SyntaxErrorScore {
+    fn score(&self, check: &Check) -> Result<Option<u64>, Overflow> {
+        Ok(match check {
+            Check::Corrupted { found, .. } => self.points.iter().find(|(c, _)| c == found).map(|&(_, p)| p),
+            _ => None,
+        })
+    }
+}
+
+/// Scores incomplete lines by their completion, as digits in base `base`.  `None` if the completion
+/// has a closer without points.
+pub struct AutocompleteScore {
+    pub base: u64,
+    pub points: Vec<(char, u64)>,
+}
+
+impl AutocompleteScore {
+    pub fn standard() -> Self {
+        Self { base: 5, points: vec![(')', 1), (']', 2), ('}', 3), ('>', 4)] }
+    }
+}
+
+impl Scoring for AutocompleteScore {
+    fn score(&self, check: &Check) -> Result<Option<u64>, Overflow> {
+        let completion = match check {
+            Check::Incomplete { completion } => completion,
+            _ => return Ok(None),
+        };
+        let mut acc = 0u64;
+        for c in completion.chars() {
+            let p = match self.points.iter().find(|(d, _)| *d == c) {
+                Some(&(_, p)) => p,
+                None => return Ok(None),
+            };
+            acc = acc.checked_mul(self.base).and_then(|a| a.checked_add(p)).ok_or(Overflow)?;
+        }
+        Ok(Some(acc))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks() {
+        let d = Delimiters::standard();
+        assert_eq!(d.check("([]{<>})"), Check::Valid);
+        assert_eq!(d.check("[({(<(())[]>[[{[]{<()<>>"), Check::Incomplete { completion: "}}]])})]".to_string() });
+        assert_eq!(d.check("{([(<{}[<>[]}>{[]{[(<()>"), Check::Corrupted { pos: 12, expected: Some(']'), found: '}' });
+        assert_eq!(d.check(")"), Check::Corrupted { pos: 0, expected: None, found: ')' });
+        assert_eq!(d.check("(a)"), Check::Unexpected { pos: 1, found: 'a' });
+        assert_eq!(d.clone().ignore_others(true).check("f(a[0]"), Check::Incomplete { completion: ")".to_string() });
+
+        let quotes = Delimiters::new(&[('«', '»'), ('(', ')')]);
+        assert_eq!(quotes.check("«(»"), Check::Corrupted { pos: 2, expected: Some(')'), found: '»' });
+
+        let bars = Delimiters::new(&[('|', '|'), ('(', ')')]);
+        assert_eq!(bars.check("|(||)|"), Check::Valid);
+        assert_eq!(bars.check("(|)"), Check::Corrupted { pos: 2, expected: Some('|'), found: ')' });
+        assert_eq!(bars.check("|(|"), Check::Incomplete { completion: "|)|".to_string() });
+
+        assert_eq!(AutocompleteScore::standard().score(&d.check("<{([{{}}[<[[[<>{}]]]>[]]")), Ok(Some(294)));
+        assert_eq!(SyntaxErrorScore::standard().score(&d.check("<{([([[(<>()){}]>(<<{{")), Ok(Some(25137)));
+        assert_eq!(SyntaxErrorScore::standard().score(&Check::Valid), Ok(None));
+        assert_eq!(AutocompleteScore::standard().score(&d.check(&"(".repeat(28))), Ok(Some(9313225746154785156)));
+        assert_eq!(AutocompleteScore::standard().score(&d.check(&"(".repeat(29))), Err(Overflow));
+    }
+}
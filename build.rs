@@ -0,0 +1,43 @@
+//! Compile the cached VM puzzle inputs to Rust ahead of time; see `vm/codegen.rs`.  Inputs that
+//! haven't been downloaded yet come out as `None`.
+
+use std::path::{Path, PathBuf};
+
+use aoc_vm::decompile::Lifted;
+use aoc_vm::{alu, codegen, coproc};
+
+fn emit(out: &mut String, name: &str, path: &str, lift: fn(&str) -> Lifted, count_muls: bool) {
+    // Cargo counts a missing path as always changed, so watch the input's directory until the
+    // input shows up.  Inputs can only be cached into a directory that exists (see
+    // `write_input()`), so without one there's nothing to watch.
+    let path = Path::new(path);
+    if path.exists() {
+        println!("cargo:rerun-if-changed={}", path.display());
+    } else if let Some(dir) = path.parent().filter(|dir| dir.is_dir()) {
+        println!("cargo:rerun-if-changed={}", dir.display());
+    }
+    let text = std::fs::read_to_string(path);
+    let nregs = match &text {
+        Ok(text) => {
+            let lifted = lift(text);
+            let opts = codegen::Options { name: format!("{}_impl", name), const_fn: true, count_muls };
+            out.push_str(&codegen::generate(&lifted, &opts));
+            lifted.reg_names.len()
+        }
+        Err(_) => if count_muls { 8 } else { 4 },
+    };
+    let value = if text.is_ok() { format!("Some({}_impl)", name) } else { "None".to_owned() };
+    out.push_str(&format!("pub const {}: Option<{}> = {};\n\n",
+                          name.to_uppercase(), codegen::fn_type(nregs), value));
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    let mut out = String::new();
+    emit(&mut out, "day24", "2021/day24.in", |text| alu::lift(&alu::parse(text)), false);
+    emit(&mut out, "day23_2017", "2017/day23.in", |text| coproc::lift(&coproc::parse(text)), true);
+
+    let path = PathBuf::from(std::env::var("OUT_DIR").unwrap()).join("aot.rs");
+    std::fs::write(path, out).unwrap();
+}
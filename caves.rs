@@ -0,0 +1,233 @@
+//! Counting and listing paths through a cave system (2021 day 12).
+//!
+//! Caves are interned to indices, and the small caves on a path so far are a `u64` bitmask, so
+//! counting can be memoized on (cave, visited, policy state).  Big caves don't need tracking: no
+//! two are connected, so a path can't loop through big caves alone.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::str::FromStr;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// Line number (from 1) that isn't `a-b`.
+    Syntax(usize),
+    MissingCave(&'static str),
+    TooManyCaves(usize),
+    /// Two connected big caves, which allow infinitely many paths.
+    BigBigEdge(String, String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Syntax(line) => write!(f, "line {}: expected 'a-b'", line),
+            Error::MissingCave(name) => write!(f, "no cave named {:?}", name),
+            Error::TooManyCaves(n) => write!(f, "{} caves, at most 64 supported", n),
+            Error::BigBigEdge(a, b) => write!(f, "big caves {} and {} are connected, so there are infinitely many paths", a, b),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Which small caves a path may enter.  Big caves are always allowed; `start` is never re-entered.
+pub trait VisitPolicy {
+    /// Anything besides the visited set that decides what's allowed next.
+    type State: Copy + Eq + Hash;
+
+    fn start(&self) -> Self::State;
+
+    /// State after entering small cave `cave`, which is already on the path if `seen`, or `None` if
+    /// the path may not go there.
+    fn enter_small(&self, state: Self::State, cave: usize, seen: bool) -> Option<Self::State>;
+}
+
+/// Small caves at most once each (part 1).
+pub struct Once;
+
+impl VisitPolicy for Once {
+    type State = ();
+
+    fn start(&self) {}
+
+    fn enter_small(&self, _: (), _: usize, seen: bool) -> Option<()> {
+        if seen { None } else { Some(()) }
+    }
+}
+
+/// Up to `k` re-entries of small caves in total.  `Revisits(1)` is part 2's "one small cave
+/// twice".
+pub struct Revisits(pub u32);
+
+impl VisitPolicy for Revisits {
+    /// Re-entries left.
+    type State = u32;
+
+    fn start(&self) -> u32 {
+        self.0
+    }
+
+    fn enter_small(&self, left: u32, _: usize, seen: bool) -> Option<u32> {
+        if !seen {
+            Some(left)
+        } else {
+            left.checked_sub(1)
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CaveGraph {
+    names: Vec<String>,
+    big: Vec<bool>,
+    adj: Vec<Vec<usize>>,
+    start: usize,
+    end: usize,
+}
+
+impl CaveGraph {
+    pub fn new<'a, I>(edges: I) -> Result<Self, Error>
+        where I: IntoIterator<Item = (&'a str, &'a str)>
+    {
+        let mut ids = HashMap::new();
+        let mut names = Vec::new();
+        let mut adj: Vec<Vec<usize>> = Vec::new();
+        let mut intern = |name: &str, adj: &mut Vec<Vec<usize>>| {
+            *ids.entry(name.to_string()).or_insert_with(|| {
+                names.push(name.to_string());
+                adj.push(Vec::new());
+                names.len() - 1
+            })
+        };
+        let mut pairs = Vec::new();
+        for (a, b) in edges {
+            let (a, b) = (intern(a, &mut adj), intern(b, &mut adj));
+            pairs.push((a, b));
+        }
+        if names.len() > 64 {
+            return Err(Error::TooManyCaves(names.len()));
+        }
+        let big = names.iter().map(|n| n.starts_with(|c: char| c.is_ascii_uppercase())).collect::<Vec<_>>();
+        for &(a, b) in pairs.iter() {
+            if big[a] && big[b] {
+                return Err(Error::BigBigEdge(names[a].clone(), names[b].clone()));
+            }
+            if !adj[a].contains(&b) {
+                adj[a].push(b);
+            }
+            if !adj[b].contains(&a) {
+                adj[b].push(a);
+            }
+        }
+        let find = |name: &'static str| names.iter().position(|n| n == name).ok_or(Error::MissingCave(name));
+        Ok(Self { start: find("start")?, end: find("end")?, names, big, adj })
+    }
+
+    pub fn name(&self, cave: usize) -> &str {
+        &self.names[cave]
+    }
+
+    pub fn format_path(&self, path: &[usize]) -> String {
+        path.iter().map(|&c| self.name(c)).collect::<Vec<_>>().join(",")
+    }
+
+    /// Where a path at `cave` may go next, with the visited set and policy state after.
+    fn next<'a, P: VisitPolicy>(&'a self, policy: &'a P, cave: usize, visited: u64, state: P::State) -> impl Iterator<Item = (usize, u64, P::State)> + 'a {
+        self.adj[cave].iter().filter_map(move |&n| {
+            if n == self.start {
+                None
+            } else if self.big[n] {
+                Some((n, visited, state))
+            } else {
+                let seen = visited & (1 << n) != 0;
+                policy.enter_small(state, n, seen).map(|s| (n, visited | (1 << n), s))
+            }
+        })
+    }
+
+    /// Number of paths from `start` to `end`.
+    pub fn count_paths<P: VisitPolicy>(&self, policy: &P) -> u128 {
+        type Memo<S> = HashMap<(usize, u64, S), u128>;
+        fn count<P: VisitPolicy>(g: &CaveGraph, policy: &P, cave: usize, visited: u64, state: P::State, memo: &mut Memo<P::State>) -> u128 {
+            if cave == g.end {
+                return 1;
+            }
+            if let Some(&n) = memo.get(&(cave, visited, state)) {
+                return n;
+            }
+            let n = g.next(policy, cave, visited, state)
+                .map(|(next, v, s)| count(g, policy, next, v, s, memo))
+                .sum();
+            memo.insert((cave, visited, state), n);
+            n
+        }
+        count(self, policy, self.start, 1 << self.start, policy.start(), &mut HashMap::new())
+    }
+
+    /// Call `f` with every path from `start` to `end`, as cave indices.
+    pub fn for_each_path<P: VisitPolicy, F: FnMut(&[usize])>(&self, policy: &P, mut f: F) {
+        fn walk<P: VisitPolicy, F: FnMut(&[usize])>(g: &CaveGraph, policy: &P, path: &mut Vec<usize>, visited: u64, state: P::State, f: &mut F) {
+            let cave = *path.last().unwrap();
+            if cave == g.end {
+                f(path);
+                return;
+            }
+            for (next, v, s) in g.next(policy, cave, visited, state).collect::<Vec<_>>() {
+                path.push(next);
+                walk(g, policy, path, v, s, f);
+                path.pop();
+            }
+        }
+        walk(self, policy, &mut vec![self.start], 1 << self.start, policy.start(), &mut f);
+    }
+
+    pub fn paths<P: VisitPolicy>(&self, policy: &P) -> Vec<Vec<usize>> {
+        let mut paths = Vec::new();
+        self.for_each_path(policy, |p| paths.push(p.to_vec()));
+        paths
+    }
+}
+
+impl FromStr for CaveGraph {
+    type Err = Error;
+
+    /// One `a-b` connection per line.
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let edges = s.lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| line.trim().split_once('-').ok_or(Error::Syntax(i + 1)))
+            .collect::<Result<Vec<_>, Error>>()?;
+        CaveGraph::new(edges)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SMALL: &str = "start-A\nstart-b\nA-c\nA-b\nb-d\nA-end\nb-end";
+
+    #[test]
+    fn policies() {
+        let g = SMALL.parse::<CaveGraph>().unwrap();
+        assert_eq!(g.count_paths(&Once), 10);
+        assert_eq!(g.count_paths(&Revisits(1)), 36);
+        assert_eq!(g.count_paths(&Revisits(0)), 10);
+        for k in 0..4 {
+            let paths = g.paths(&Revisits(k));
+            assert_eq!(paths.len() as u128, g.count_paths(&Revisits(k)));
+            let mut names = paths.iter().map(|p| g.format_path(p)).collect::<Vec<_>>();
+            names.sort();
+            names.dedup();
+            assert_eq!(names.len(), paths.len());
+        }
+        assert!(g.paths(&Once).iter().any(|p| g.format_path(p) == "start,A,c,A,b,A,end"));
+
+        assert_eq!("start-A\nA-B\nB-end".parse::<CaveGraph>().unwrap_err(), Error::BigBigEdge("A".to_string(), "B".to_string()));
+        assert_eq!("start-a".parse::<CaveGraph>().unwrap_err(), Error::MissingCave("end"));
+        assert_eq!("start-a\na end".parse::<CaveGraph>().unwrap_err(), Error::Syntax(2));
+    }
+}
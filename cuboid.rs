@@ -0,0 +1,251 @@
+//! Sets of integer points built from axis-aligned boxes, in any number of dimensions (2021 day 22).
+
+use std::cmp::{max, min};
+
+/// Closed box `lo[i]..=hi[i]`; never empty.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Cuboid<const N: usize> {
+    pub lo: [i64; N],
+    pub hi: [i64; N],
+}
+
+impl<const N: usize> Cuboid<N> {
+    /// `None` if empty along some axis.
+    pub fn new(lo: [i64; N], hi: [i64; N]) -> Option<Self> {
+        if (0..N).all(|i| lo[i] <= hi[i]) {
+            Some(Self { lo, hi })
+        } else {
+            None
+        }
+    }
+
+    /// Number of points, or `None` on overflow.
+    pub fn volume(&self) -> Option<i128> {
+        (0..N).try_fold(1i128, |acc, i| acc.checked_mul(self.hi[i] as i128 - self.lo[i] as i128 + 1))
+    }
+
+    pub fn contains(&self, p: &[i64; N]) -> bool {
+        (0..N).all(|i| self.lo[i] <= p[i] && p[i] <= self.hi[i])
+    }
+
+    pub fn intersect(&self, other: &Self) -> Option<Self> {
+        let mut lo = self.lo;
+        let mut hi = self.hi;
+        for i in 0..N {
+            lo[i] = max(lo[i], other.lo[i]);
+            hi[i] = min(hi[i], other.hi[i]);
+        }
+        Self::new(lo, hi)
+    }
+
+    /// `self` minus `other`, as at most `2 * N` disjoint boxes.
+    pub fn subtract(&self, other: &Self) -> Vec<Self> {
+        let cut = match self.intersect(other) {
+            Some(cut) => cut,
+            None => return vec![*self],
+        };
+        // Peel off the slabs either side of `cut` one axis at a time, shrinking what's left
+        // towards `cut`.
+        let mut res = Vec::new();
+        let mut rest = *self;
+        for i in 0..N {
+            if cut.lo[i] > rest.lo[i] {
+                let mut slab = rest;
+                slab.hi[i] = cut.lo[i] - 1;
+                res.push(slab);
+            }
+            if cut.hi[i] < rest.hi[i] {
+                let mut slab = rest;
+                slab.lo[i] = cut.hi[i] + 1;
+                res.push(slab);
+            }
+            rest.lo[i] = cut.lo[i];
+            rest.hi[i] = cut.hi[i];
+        }
+        res
+    }
+}
+
+/// A set of points, stored as disjoint boxes.
+///
+/// Boxes removed by an update are left as dead entries and only swept out once they're the
+/// majority, or on `compact`.
+#[derive(Clone, Debug)]
+pub struct CuboidSet<const N: usize> {
+    boxes: Vec<Option<Cuboid<N>>>,
+    live: usize,
+}
+
+impl<const N: usize> Default for CuboidSet<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> CuboidSet<N> {
+    pub fn new() -> Self {
+        Self {
+            boxes: Vec::new(),
+            live: 0,
+        }
+    }
+
+    /// Number of disjoint boxes.
+    pub fn len(&self) -> usize {
+        self.live
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.live == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Cuboid<N>> {
+        self.boxes.iter().flatten()
+    }
+
+    pub fn compact(&mut self) {
+        self.boxes.retain(|b| b.is_some());
+    }
+
+    fn maybe_compact(&mut self) {
+        if self.boxes.len() > 2 * self.live + 16 {
+            self.compact();
+        }
+    }
+
+    /// Cut `c` out of every box.
+    fn carve(&mut self, c: &Cuboid<N>) {
+        let mut pieces = Vec::new();
+        for slot in self.boxes.iter_mut() {
+            if let Some(b) = slot {
+                if b.intersect(c).is_some() {
+                    pieces.extend(b.subtract(c));
+                    *slot = None;
+                    self.live -= 1;
+                }
+            }
+        }
+        self.live += pieces.len();
+        self.boxes.extend(pieces.into_iter().map(Some));
+    }
+
+    /// Add every point of `c`.
+    pub fn insert(&mut self, c: Cuboid<N>) {
+        self.carve(&c);
+        self.boxes.push(Some(c));
+        self.live += 1;
+        self.maybe_compact();
+    }
+
+    /// Remove every point of `c`.
+    pub fn remove(&mut self, c: &Cuboid<N>) {
+        self.carve(c);
+        self.maybe_compact();
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        let mut res = self.clone();
+        for c in other.iter() {
+            res.insert(*c);
+        }
+        res
+    }
+
+    pub fn difference(&self, other: &Self) -> Self {
+        let mut res = self.clone();
+        for c in other.iter() {
+            res.remove(c);
+        }
+        res
+    }
+
+    pub fn intersection(&self, other: &Self) -> Self {
+        // Both sides are disjoint, so the pairwise intersections are too.
+        let boxes = self.iter()
+            .flat_map(|a| other.iter().filter_map(move |b| a.intersect(b)))
+            .map(Some)
+            .collect::<Vec<_>>();
+        Self {
+            live: boxes.len(),
+            boxes,
+        }
+    }
+
+    /// Keep only the points inside `region`.
+    pub fn restrict(&mut self, region: &Cuboid<N>) {
+        for slot in self.boxes.iter_mut() {
+            if let Some(b) = slot {
+                *slot = b.intersect(region);
+                if slot.is_none() {
+                    self.live -= 1;
+                }
+            }
+        }
+        self.maybe_compact();
+    }
+
+    /// Number of points, or `None` on overflow.
+    pub fn volume(&self) -> Option<i128> {
+        self.iter().try_fold(0i128, |acc, c| acc.checked_add(c.volume()?))
+    }
+
+    pub fn contains(&self, p: &[i64; N]) -> bool {
+        self.iter().any(|c| c.contains(p))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(lo: [i64; 2], hi: [i64; 2]) -> Cuboid<2> {
+        Cuboid::new(lo, hi).unwrap()
+    }
+
+    #[test]
+    fn rectangles() {
+        let mut a = CuboidSet::new();
+        a.insert(rect([0, 0], [9, 9]));
+        a.insert(rect([5, 5], [14, 14]));
+        let mut b = CuboidSet::new();
+        b.insert(rect([-3, 2], [6, 3]));
+
+        let brute = |s: &CuboidSet<2>| {
+            let mut n = 0;
+            for x in -5..20 {
+                for y in -5..20 {
+                    n += s.contains(&[x, y]) as i128;
+                }
+            }
+            n
+        };
+        assert_eq!(a.volume(), Some(175));
+        assert_eq!(brute(&a), 175);
+        assert_eq!(a.union(&b).volume(), Some(175 + 6));
+        assert_eq!(a.difference(&b).volume(), Some(175 - 14));
+        assert_eq!(a.intersection(&b).volume(), Some(14));
+        for s in [a.union(&b), a.difference(&b), a.intersection(&b)].iter() {
+            assert_eq!(brute(s), s.volume().unwrap());
+        }
+
+        a.remove(&rect([-100, -100], [100, 100]));
+        assert!(a.is_empty());
+        assert_eq!(a.volume(), Some(0));
+        a.compact();
+        assert_eq!(a.boxes.len(), 0);
+    }
+
+    #[test]
+    fn hypercubes() {
+        let mut s = CuboidSet::<4>::new();
+        s.insert(Cuboid::new([0; 4], [2; 4]).unwrap());
+        s.remove(&Cuboid::new([1; 4], [1; 4]).unwrap());
+        assert_eq!(s.volume(), Some(80));
+        assert!(!s.contains(&[1, 1, 1, 1]));
+        assert!(s.contains(&[1, 1, 1, 2]));
+
+        let huge = Cuboid::new([i64::MIN; 4], [i64::MAX; 4]).unwrap();
+        assert_eq!(huge.volume(), None);
+        assert_eq!(Cuboid::new([i64::MIN; 1], [i64::MAX; 1]).unwrap().volume(), Some(1 << 64));
+    }
+}
@@ -10,101 +10,75 @@ use std::collections::*;
 use std::hash::Hash;
 use std::iter::FromIterator;
 
-fn parse(line: &str) -> Result<Vec<char>, (usize, char)> {
-    let mut stack = Vec::new();
+use aoc::brackets::{AutocompleteScore, Check, Delimiters, Scoring, SyntaxErrorScore};
 
-    for (i, chr) in line.chars().enumerate() {
-        match chr {
-            '(' => { stack.push('('); }
-            '{' => { stack.push('{'); }
-            '[' => { stack.push('['); }
-            '<' => { stack.push('<'); }
-
-
-            ')' => {
-                if stack.pop() != Some('(') {
-                    return Err((i, chr));
-                }
-            }
-            ']' => {
-                if stack.pop() != Some('[') {
-                    return Err((i, chr));
-                }
-            }
-            '}' => {
-                if stack.pop() != Some('{') {
-                    return Err((i, chr));
-                }
-            }
-            '>' => {
-                if stack.pop() != Some('<') {
-                    return Err((i, chr));
-                }
-            }
-
-            _ => { unreachable!(); }
+fn scores(lines: &Vec<&str>, scoring: &impl Scoring) -> Result<Vec<u64>> {
+    let delims = Delimiters::standard();
+    let mut res = Vec::new();
+    for line in lines {
+        let check = delims.check(line);
+        if let Check::Unexpected { pos, found } = check {
+            return Err(anyhow!("{:?}: unexpected {:?} at {}", line, found, pos));
+        }
+        if let Some(score) = scoring.score(&check).map_err(|e| anyhow!("{:?}: {}", line, e))? {
+            res.push(score);
         }
     }
-
-    Ok(stack)
+    Ok(res)
 }
 
-fn part1(lines: &Vec<&str>) -> i64 {
-    let scores = {
-        let mut scores = HashMap::new();
-        scores.insert(')', 3);
-        scores.insert(']', 57);
-        scores.insert('}', 1197);
-        scores.insert('>', 25137);
-        scores
-    };
-    let mut total = 0;
-    for line in lines.iter() {
-        if let Err((_pos, chr)) = parse(line) {
-            total += scores.get(&chr).unwrap();
-        }
-    }
-    total
+fn part1(lines: &Vec<&str>) -> Result<u64> {
+    Ok(scores(lines, &SyntaxErrorScore::standard())?.iter().sum())
 }
 
-fn part2(lines: &Vec<&str>) -> u64 {
-    let scores = {
-        let mut scores = HashMap::new();
-        scores.insert('(', 1);
-        scores.insert('[', 2);
-        scores.insert('{', 3);
-        scores.insert('<', 4);
-        scores
-    };
-    let mut res = Vec::new();
-    for line in lines.iter() {
-        if let Ok(stk) = parse(line) {
-            let mut sco = 0u64;
-            for chr in stk.iter().rev() {
-                sco = (5 * sco) + scores[chr];
-            }
-            res.push(sco);
-        }
+fn part2(lines: &Vec<&str>) -> Result<u64> {
+    let mut res = scores(lines, &AutocompleteScore::standard())?;
+    if res.is_empty() {
+        return Err(anyhow!("no incomplete lines"));
     }
-    res.sort();
-    dbg!(&res);
-    let i = res.len() / 2;
-    dbg!(i, res.len());
-    res[i]
+    res.sort_unstable();
+    Ok(res[res.len() / 2])
 }
 
 fn main() -> Result<()> {
     let mut puzzle = aoc::Puzzle::new(2021, 10)?;
     let data = puzzle.get_data()?;
+    //let data = SAMPLE_DATA;
     let lines = data.lines().collect::<Vec<_>>();
 
-    //let answ1 = part1(&lines);
-    //dbg!(&answ1);
+    let answ1 = part1(&lines)?;
+    dbg!(&answ1);
     //puzzle.submit_answer(aoc::Part::One, &format!("{}", answ1))?;
 
-    let answ2 = part2(&lines);
+    let answ2 = part2(&lines)?;
     dbg!(&answ2);
     //puzzle.submit_answer(aoc::Part::Two, &format!("{}", answ2))?;
 
     Ok(())
 }
+
+const SAMPLE_DATA: &str =
+"[({(<(())[]>[[{[]{<()<>>
+[(()[<>])]({[<{<<[]>>(
+{([(<{}[<>[]}>{[]{[(<()>
+(((({<>}<{<{<>}{[]{[]{}
+[[<[([]))<([[{}[[()]]]
+[{[{({}]{}}([{[{{{}}([]
+{<[[]]>}<{[{[{[]{()[[[]
+[<(<(<(<{}))><([]([]()
+<{([([[(<>()){}]>(<<{{
+<{([{{}}[<[[[<>{}]]]>[]]";
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let lines = SAMPLE_DATA.lines().collect::<Vec<_>>();
+        assert_eq!(part1(&lines).unwrap(), 26397);
+        assert_eq!(part2(&lines).unwrap(), 288957);
+        assert!(part2(&vec!["()"]).is_err());
+        assert!(part1(&vec!["()\r"]).is_err());
+    }
+}
@@ -12,6 +12,8 @@ use std::hash::Hash;
 use std::iter::FromIterator;
 
 use aoc::{dbg2, byte, ByteString};
+use aoc::matrix::{Matrix, Mod, Scalar};
+use aoc::polymer::Polymer;
 
 fn do_step(inp: &[u8], rules: &HashMap<ByteString, u8>) -> ByteString {
     let mut res = Vec::new();
@@ -27,142 +29,53 @@ fn do_step(inp: &[u8], rules: &HashMap<ByteString, u8>) -> ByteString {
     res
 }
 
-fn part1(input: &ParseResult) -> i64 {
-    let (state, rules) = input;
-    let mut state = state.clone();
-    for _ in 0..10 {
-        state = do_step(&state, rules);
-        //println!("{}", std::str::from_utf8(&state).unwrap());
-    }
-    let mut mct = b'\0';
-    let mut mct_c = -1;
-    let mut lct = b'\0';
-    let mut lct_c = i64::MAX;
-    let mut freq = HashMap::new();
-    for e in state {
-        *freq.entry(e).or_default() += 1;
-    }
-    for (e, cnt) in freq.iter() {
-        if *cnt < lct_c {
-            lct_c = *cnt;
-            lct = *e;
-        }
-        if *cnt > mct_c {
-            mct_c = *cnt;
-            mct = *e;
-        }
-    }
-    mct_c - lct_c
+fn most_minus_least(counts: &[u128]) -> u128 {
+    let present = counts.iter().copied().filter(|&c| c > 0);
+    present.clone().max().unwrap() - present.min().unwrap()
 }
 
-// Revised step function using a histogram of pairs.
-fn do_step2(inp: &HashMap<ByteString, u64>, rules: &HashMap<ByteString, u8>) -> HashMap<ByteString, u64> {
-    let mut res = HashMap::new();
-
-    for (pair, cnt) in inp.iter() {
-        if let Some(blah) = rules.get(pair) {
-            let v1 = vec![pair[0], *blah];
-            let v2 = vec![*blah, pair[1]];
-            let incr = res.get(&v1).unwrap_or(&0) + cnt;
-            res.insert(v1, incr);
-            let incr = res.get(&v2).unwrap_or(&0) + cnt;
-            res.insert(v2, incr);
-        } else {
-            let incr = res.get(pair).unwrap_or(&0) + cnt;
-            res.insert(pair.clone(), incr);
-        }
+fn part1(input: &ParseResult) -> u128 {
+    let mut polymer = input.clone();
+    for _ in 0..10 {
+        polymer.step().unwrap();
     }
-
-    res
+    most_minus_least(&polymer.element_counts())
 }
 
-fn part2(input: &ParseResult) -> u64 {
-    let (state, rules) = input;
-    dbg2!(state);
-
-    // Transform parsed state into histogram of pairs.
-    let mut hashstate = HashMap::new();
-    for pair in state.windows(2) {
-        let v = pair.to_vec();
-        *hashstate.entry(v).or_default() += 1;
-    }
-
-    // Apply 40 steps.
-    for i in 0..40 {
-        hashstate = do_step2(&hashstate, rules);
-    }
-
-    // Transform pair histogram into frequency of individual elements.
-    let mut freq = HashMap::new();
-    for (e, cnt) in hashstate.iter() {
-        *freq.entry(e[0]).or_default() += cnt;
-        *freq.entry(e[1]).or_default() += cnt;
+fn part2(input: &ParseResult) -> u128 {
+    let mut polymer = input.clone();
+    for _ in 0..40 {
+        polymer.step().unwrap();
     }
+    most_minus_least(&polymer.element_counts())
+}
 
-    // Every element in the sequence is double-counted, except first/last.  First/last remain the
-    // same from the beginning to the end, so just add them in here.  Now every element is
-    // double-counted.
-    *freq.entry(state[0]).or_default() += 1;
-    *freq.entry(state[state.len()-1]).or_default() += 1;
-
-    // Finally, find the most and least frequent elements in the histogram.
-    let mut mct = b'\0';
-    let mut mct_c = 0;
-    let mut lct = b'\0';
-    let mut lct_c = u64::MAX;
-    for (e, cnt) in freq.iter() {
-        if *cnt < lct_c {
-            lct_c = *cnt;
-            lct = *e;
-        }
-        if *cnt > mct_c {
-            mct_c = *cnt;
-            mct = *e;
-        }
-    }
+/// Count of each element after `steps` steps, by matrix powers.  `None` if `T` overflows.
+fn element_counts<T: Scalar + From<u64>>(input: &ParseResult, steps: u64) -> Option<Vec<(u8, T)>> {
+    let pairs = input.pair_counts().iter()
+        .map(|&c| u64::try_from(c).ok().map(T::from))
+        .collect::<Option<Vec<_>>>()?;
+    let pairs = input.matrix::<T>().advance(&pairs, steps)?;
+    let counts = input.counts_from_pairs(&pairs)?;
+    Some(input.elements().iter().copied().zip(counts).collect())
+}
 
-    // Adjust for double-counting.
-    let mct_c_cor = mct_c/2;
-    let lct_c_cor = lct_c/2;
-    dbg!(mct as char, mct_c_cor, lct as char, lct_c_cor);
-    mct_c_cor - lct_c_cor
+fn part2_matrix(input: &ParseResult, steps: u64) -> Option<u128> {
+    let counts = element_counts::<u128>(input, steps)?;
+    Some(most_minus_least(&counts.iter().map(|&(_, c)| c).collect::<Vec<_>>()))
 }
 
-type ParseResult = (ByteString, HashMap<ByteString, u8>);
+type ParseResult = Polymer;
 
-fn parse(data: &str) -> ParseResult {
-    let (template, insertion) = data.split_once("\n\n").unwrap();
-    let mut rules = HashMap::new();
-    for line in insertion.lines() {
-        let rule = line.split_once(" -> ").unwrap();
-        rules.insert(rule.0.as_bytes().to_vec(), rule.1.as_bytes()[0]);
-    }
-    (template.as_bytes().to_vec(), rules)
+fn parse(data: &str) -> Result<ParseResult> {
+    Ok(data.parse()?)
 }
 
 fn main() -> Result<()> {
     let mut puzzle = aoc::Puzzle::new(2021, 14)?;
     let data = puzzle.get_data()?;
-//    let data =
-//"NNCB
-//
-//CH -> B
-//HH -> N
-//CB -> H
-//NH -> C
-//HB -> C
-//HC -> B
-//HN -> C
-//NN -> C
-//BH -> H
-//NC -> B
-//NB -> B
-//BN -> B
-//BB -> N
-//BC -> B
-//CC -> N
-//CN -> C";
-    let parsed = parse(data);
+    //let data = SAMPLE_DATA;
+    let parsed = parse(data)?;
 
     let answ1 = part1(&parsed);
     dbg!(&answ1);
@@ -170,6 +83,62 @@ fn main() -> Result<()> {
     let answ2 = part2(&parsed);
     dbg!(&answ2);
     assert_eq!(answ2, 3459174981021);
+    assert_eq!(part2_matrix(&parsed, 40), Some(answ2));
+    for (e, count) in element_counts::<Mod<1_000_000_007>>(&parsed, 1_000_000_000).unwrap() {
+        println!("{} after 10^9 steps: {}", e as char, count);
+    }
 
     Ok(())
 }
+
+const SAMPLE_DATA: &str =
+"NNCB
+
+CH -> B
+HH -> N
+CB -> H
+NH -> C
+HB -> C
+HC -> B
+HN -> C
+NN -> C
+BH -> H
+NC -> B
+NB -> B
+BN -> B
+BB -> N
+BC -> B
+CC -> N
+CN -> C";
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let parsed = parse(SAMPLE_DATA).unwrap();
+        assert_eq!(part1(&parsed), 1588);
+
+        // Against the literal polymer.
+        let rules = SAMPLE_DATA.lines()
+            .filter_map(|line| line.split_once(" -> "))
+            .map(|(pair, ins)| (pair.as_bytes().to_vec(), ins.as_bytes()[0]))
+            .collect::<HashMap<_, _>>();
+        let mut literal = b"NNCB".to_vec();
+        let mut polymer = parsed.clone();
+        for _ in 0..5 {
+            literal = do_step(&literal, &rules);
+            polymer.step().unwrap();
+        }
+        assert_eq!(polymer.length(), 97);
+        for (&e, count) in polymer.elements().iter().zip(polymer.element_counts()) {
+            assert_eq!(literal.iter().filter(|&&c| c == e).count() as u128, count);
+        }
+        assert_eq!(part2(&parsed), 2188189693529);
+        assert_eq!(part2_matrix(&parsed, 10), Some(1588));
+        assert_eq!(part2_matrix(&parsed, 40), Some(2188189693529));
+        let small = element_counts::<Mod<1_000_000_007>>(&parsed, 10).unwrap();
+        assert_eq!(small, element_counts::<u128>(&parsed, 10).unwrap().into_iter().map(|(e, c)| (e, Mod(c as u64))).collect::<Vec<_>>());
+    }
+}
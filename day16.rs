@@ -13,145 +13,39 @@ use std::hash::Hash;
 use std::iter::FromIterator;
 
 use aoc::{dbg2, byte, BitCursor, ByteString};
-
-#[derive(Eq,PartialEq,Clone,Debug,Hash)]
-struct EvalResult {
-    value: u64,
-    sum_versions: u64,
-}
-
-/// Evaluate the Packet tree represented by this sequence of bits, recursing to evaluate nested
-/// packets.
-fn eval(input: &mut BitCursor<Msb0>) -> EvalResult {
-    // The first three bits encode the packet _version_.
-    let version = input.parse_be::<u8>(3);
-    // The next three bits encode the packet _type ID_.
-    let type_ = input.parse_be::<u8>(3);
-
-    let mut sumversions = version as u64;
-
-    let pktval = match type_ {
-        // Packets with type ID 4 represent a _literal value_.
-        0x4 => {
-            let mut res = 0u64;
-            // Literals are represented using a UTF8-like encoding; each nibble is prefixed with a
-            // continue bit.
-            loop {
-                let next = input.parse_be::<u8>(5);
-
-                // The nibbles are MSB.
-                res = res << 4;
-                res |= (next & 0xf) as u64;
-
-                // Check the continue bit.
-                if (next & 0x10) == 0 {
-                    break;
-                }
-            }
-
-            // Evaluated result of a literal value is just the value.
-            res
-        }
-        // Every other type of packet represents an _operator_.
-        _ => {
-            // Shared parsing logic for operator packets: first bit determines _length type ID_.
-            let lentype = input.parse_be::<u8>(1);
-
-            // Store the evaluated value of each sub-Packet (sub-expression).
-            let mut subpackets = Vec::new();
-
-            match lentype {
-                // If the length type ID is 0, then the next 15 bits represent the _total length in
-                // bits_ of the contained sub-packets.
-                0 => {
-                    let tlen = input.parse_be::<u16>(15);
-                    let future_pos = input.as_slice()[(tlen as usize)..].as_bitptr();
-
-                    // Evaluate enough packets, recursively, to consume the appropriate number of
-                    // bits.
-                    while input.as_slice().as_bitptr() != future_pos {
-                        let eresult = eval(input);
-                        subpackets.push(eresult.value);
-                        sumversions += eresult.sum_versions;
-                    }
-                }
-                // If the length type ID is 1, then the next 11 bits represent the _number of
-                // sub-packets immediately contained_ by this packet.
-                1 => {
-                    let nsubpackets = input.parse_be::<u16>(11);
-
-                    // Evaluate the appropriate number of subpackets.
-                    for _i in 0..nsubpackets {
-                        let eresult = eval(input);
-                        subpackets.push(eresult.value);
-                        sumversions += eresult.sum_versions;
-                    }
-                }
-                _ => { unreachable!(); }
-            }
-
-            // Re-dispatch on type ID, for the operator subset of types (part 2).
-            match type_ {
-                // Packets with type ID 0 are _sum_ packets.
-                0 => subpackets.iter().sum::<u64>(),
-                // Packets with type ID 1 are _product_ packets.
-                1 => subpackets.iter().product(),
-                // Packets with type ID 2 are _min_ packets.
-                2 => *subpackets.iter().min().unwrap(),
-                // Packets with type ID 3 are _max_ packets.
-                3 => *subpackets.iter().max().unwrap(),
-                // Packets with type ID 5 are _greater than_ packets.
-                5 => if subpackets[0] > subpackets[1] { 1 } else { 0 },
-                // Packets with type ID 6 are _less than_ packets.
-                6 => if subpackets[0] < subpackets[1] { 1 } else { 0 },
-                // Packets with type ID 7 are _equal to_ packets.
-                7 => if subpackets[0] == subpackets[1] { 1 } else { 0 },
-                _ => { unreachable!(); }
-            }
-
-        }
-    };
-
-    EvalResult {
-        value: pktval,
-        sum_versions: sumversions,
-    }
-}
+use aoc::bits::Packet;
 
 // What do you get if you evaluate the expression represented by your hexadecimal-encoded BITS
 // transmission?
-fn part2(input: &ParseResult) -> u64 {
-    let bits = input.view_bits::<Msb0>();
-    let mut curs = BitCursor::new(&bits);
-    let EvalResult { value, .. } = eval(&mut curs);
-    value
+fn part2(input: &ParseResult) -> Result<u64> {
+    input.eval().ok_or_else(|| anyhow!("Expression overflows u64"))
 }
 
 // Parse the hierarchy of the packets throughout the transmission and _add up all of the version
 // numbers_.
 fn part1(input: &ParseResult) -> u64 {
-    let bits = input.view_bits::<Msb0>();
-    let mut curs = BitCursor::new(&bits);
-    let EvalResult { sum_versions, .. } = eval(&mut curs);
-    sum_versions
+    input.version_sum()
 }
 
-type ParseResult = Vec<u8>;
+type ParseResult = Packet;
 
-// Yeah, this isn't really the parser, but usually input parsing is a smaller part of the puzzle.
-fn parse(data: &str) -> ParseResult {
-    hex::decode(data.trim_end()).unwrap()
+fn parse(data: &str) -> Result<ParseResult> {
+    Packet::from_hex(data)
 }
 
 fn main() -> Result<()> {
     let mut puzzle = aoc::Puzzle::new(2021, 16)?;
     let data = puzzle.get_data()?;
-    let parsed = parse(data);
+    let parsed = parse(data)?;
+
+    if std::env::args().any(|a| a == "--print") {
+        println!("{:#}", parsed);
+    }
 
     let answ1 = part1(&parsed);
     dbg!(&answ1);
     assert_eq!(answ1, 984);
-    let answ2 = part2(&parsed);
+    let answ2 = part2(&parsed)?;
     dbg!(&answ2);
     assert_eq!(answ2, 1015320896946);
 
@@ -162,11 +56,17 @@ fn main() -> Result<()> {
 mod test {
     use super::*;
 
+    struct EvalResult {
+        value: u64,
+        sum_versions: u64,
+    }
+
     fn test_eval(inp: &str) -> EvalResult {
-        let parsed = parse(inp);
-        let bits = parsed.view_bits::<Msb0>();
-        let mut curs = BitCursor::new(&bits);
-        eval(&mut curs)
+        let parsed = parse(inp).unwrap();
+        EvalResult {
+            value: parsed.eval().unwrap(),
+            sum_versions: part1(&parsed),
+        }
     }
 
     #[test]
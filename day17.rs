@@ -46,120 +46,216 @@ fn simulate(idx: i64, idy: i64, tzone: &ParseResult) -> Option<()> {
     reached
 }
 
-fn simulate_y(idy: i64, tzone: &ParseResult) -> Option<i64> {
-    let (_, zoney) = tzone;
-    let zoney = zoney.0..=zoney.1;
-
-    let mut y = 0;
-    let mut dy = idy;
-    let mut highy = i64::MIN;
-    let mut reached = false;
-
-    loop {
-        y = y + dy;
-
-        if y > highy { highy = y; }
-
-        if zoney.contains(&y) {
-            reached = true;
-            break;
-        }
+/// Position after `n` steps starting at speed `v` and slowing by 1 each step, ignoring drag
+/// stopping at zero: `n * v - T(n - 1)`.  Rises until step `v`, equal at `v + 1`, then falls.
+fn pos(v: i64, n: i64) -> i64 {
+    n * v - n * (n - 1) / 2
+}
 
-        if dy < 0 && y < *zoney.start() { break; }
-        dy -= 1;
+/// Real steps at which `pos(v, n) == t`: the roots of `n^2 - (2v + 1) n + 2t = 0`.
+fn crossings(v: i64, t: i64) -> Option<(f64, f64)> {
+    let b = (2 * v + 1) as f64;
+    let disc = b * b - 8.0 * t as f64;
+    if disc < 0.0 {
+        return None;
     }
-
-    if reached { Some(highy) } else { None }
+    Some(((b - disc.sqrt()) / 2.0, (b + disc.sqrt()) / 2.0))
 }
 
-fn simulate_x(idx: i64, tzone: &ParseResult) -> Option<()> {
-    let (zonex, _) = tzone;
-    let zonex = zonex.0..=zonex.1;
-
-    let mut x = 0;
-    let mut dx = idx;
-    let mut reached = None;
-
-    loop {
-        x = x + dx;
-
-        if zonex.contains(&x) {
-            reached = Some(());
-            break;
-        }
-
-        if dx > 0 { dx -= 1; }
-        if dx < 0 { dx += 1; }
+/// Inclusive range of steps `n >= 1` spent inside a target; `i64::MAX` for "forever".
+type Window = (i64, i64);
+
+/// Steps in `a..=b` where `lo <= pos(v, n) <= hi`, given `pos` is monotonic there.
+///
+/// The ends come from the roots of the quadratic, then get nudged to the exact integer boundary
+/// to undo any floating-point error.
+fn window(v: i64, (lo, hi): (i64, i64), (a, b): Window, rising: bool) -> Option<Window> {
+    let inside = |n: i64| (lo..=hi).contains(&pos(v, n));
+    // First step on the near side of the target, and last before leaving it.
+    let (enter, leave) = if rising {
+        (crossings(v, lo).map(|r| r.0.ceil()), crossings(v, hi).map(|r| r.0.floor()))
+    } else {
+        (crossings(v, hi).map(|r| r.1.ceil()), crossings(v, lo).map(|r| r.1.floor()))
+    };
+    let clamp = |n: f64| n.max(a as f64).min(b as f64) as i64;
+    let mut first = enter.map_or(a, clamp);
+    let mut last = leave.map_or(b, clamp);
+    let past = |n: i64| if rising { pos(v, n) > hi } else { pos(v, n) < lo };
+    while first > a && inside(first - 1) {
+        first -= 1;
+    }
+    while first <= last && !inside(first) && !past(first) {
+        first += 1;
+    }
+    while last < b && inside(last + 1) {
+        last += 1;
+    }
+    while last >= first && !inside(last) {
+        last -= 1;
+    }
+    if first <= last && inside(first) { Some((first, last)) } else { None }
+}
 
-        if dx == 0 && !zonex.contains(&x) { break; }
-        if dx > 0 && x > *zonex.end() { break; }
-        if dx < 0 && x < *zonex.start() { break; }
+/// Steps during which the probe is within `x` horizontally when launched at `vx`.
+fn x_window(vx: i64, x: (i64, i64)) -> Option<Window> {
+    if vx < 0 {
+        return x_window(-vx, (-x.1, -x.0));
+    }
+    if vx == 0 {
+        return if x.0 <= 0 && 0 <= x.1 { Some((1, i64::MAX)) } else { None };
     }
+    // Moving until step `vx`, then parked at `T(vx)`.
+    let w = window(vx, x, (1, vx), true)?;
+    Some(if w.1 == vx { (w.0, i64::MAX) } else { w })
+}
 
-    reached
+/// Steps during which the probe is within `y` vertically when launched at `vy`.
+fn y_windows(vy: i64, y: (i64, i64)) -> Vec<Window> {
+    let mut res = Vec::new();
+    if vy >= 1 {
+        res.extend(window(vy, y, (1, vy), true));
+    }
+    res.extend(window(vy, y, (max(vy + 1, 1), i64::MAX), false));
+    res
 }
 
-fn part1(input: &ParseResult) -> i64 {
-    let mut besty = i64::MIN;
-    for dy in -125..125 {
-        let y = simulate_y(dy, &input).unwrap_or(i64::MIN);
-        if y > besty {
-            besty = y;
-        }
+/// Smallest `k >= 0` with `T(k) >= t`.
+fn min_triangular(t: i64) -> i64 {
+    let mut k = max(0, crossings(0, -t).map_or(0.0, |r| r.1.floor()) as i64 - 1);
+    while k * (k + 1) / 2 < t {
+        k += 1;
     }
-    besty
+    k
 }
 
-fn part2(input: &ParseResult) -> i64 {
-    let mut res = 0;
-    let mut x_cand = Vec::new();
-    let mut y_cand = Vec::new();
-
-    // You can simulate x and y separately to reduce the search space considerably (~184 -> 116 in
-    // x for my input, ~5000 -> 234 in y for my input).  You still need to simulate (x, y)
-    // candidates to determine actual matches.  This eliminates ~97% of simulations for my initial,
-    // large y range guess (-200..5000) or ~30% of simulations with an ideal y range (-125..125) --
-    // including the cost of the additional single-dimension simulations.
-    for dx in 1..=(input.0.1) {
-        if simulate_x(dx, &input).is_some() {
-            x_cand.push(dx);
-        }
+/// A launch velocity that ends up in the target, and the highest point reached (counting the
+/// launch point).
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
+struct Shot {
+    vx: i64,
+    vy: i64,
+    apex: i64,
+}
+
+/// Every velocity that puts the probe in the target at some step.
+fn solve(target: &ParseResult) -> Result<Vec<Shot>> {
+    let &((x0, x1), (y0, y1)) = target;
+
+    // Too slow never gets there; too fast skips past on the first step.
+    let mut vxs = Vec::new();
+    if x1 > 0 {
+        vxs.extend(min_triangular(max(x0, 1))..=x1);
     }
-    //for dy in -200..5000 {
-    for dy in -125..125 {
-        if simulate_y(dy, &input).is_some() {
-            y_cand.push(dy);
+    if x0 <= 0 && 0 <= x1 {
+        vxs.push(0);
+    }
+    if x0 < 0 {
+        vxs.extend(x0..=-min_triangular(max(-x1, 1)));
+    }
+    let xs = vxs.into_iter()
+        .filter_map(|vx| x_window(vx, (x0, x1)).map(|w| (vx, w)))
+        .collect::<Vec<_>>();
+
+    // Below the target, `vy` must not overshoot it on the way back down through `y = 0`.  Above,
+    // the first step must not overshoot it.  If it contains the launch height, a probe only
+    // reaches it on the way down after step `2 vy + 1`, which must be before leaving in `x`.
+    let vy_lo = if y0 <= 0 { y0 } else { min_triangular(y0) };
+    let vy_hi = if y1 < 0 {
+        -y0 - 1
+    } else if y0 > 0 {
+        y1
+    } else {
+        let last = xs.iter().map(|(_, w)| w.1).max().unwrap_or(0);
+        if last == i64::MAX {
+            return Err(anyhow!("infinitely many velocities: the probe can fall straight down through the target"));
+        }
+        max(y1, (last - 1) / 2)
+    };
+
+    let mut shots = Vec::new();
+    for vy in vy_lo..=vy_hi {
+        let yw = y_windows(vy, (y0, y1));
+        for &(vx, xw) in xs.iter() {
+            if yw.iter().any(|w| max(w.0, xw.0) <= min(w.1, xw.1)) {
+                shots.push(Shot { vx, vy, apex: pos(max(vy, 0), max(vy, 0)) });
+            }
         }
     }
+    shots.sort();
+    Ok(shots)
+}
 
-    for dx in x_cand.iter().copied() {
-        for dy in y_cand.iter().copied() {
-            if simulate(dx, dy, &input).is_some() {
-                res += 1;
-            }
+fn part1(input: &ParseResult) -> Result<i64> {
+    let shots = solve(input)?;
+    shots.iter().map(|s| s.apex).max().ok_or_else(|| anyhow!("target can't be hit"))
+}
+
+fn part2(input: &ParseResult) -> Result<usize> {
+    let shots = solve(input)?;
+    if std::env::args().any(|a| a == "--print") {
+        for s in shots.iter() {
+            println!("{:5},{:5}  apex {}", s.vx, s.vy, s.apex);
         }
     }
-    res
+    Ok(shots.len())
 }
 
 type ParseResult = ((i64, i64), (i64, i64));
 
-fn parse(_data: &str) -> ParseResult {
-    // Eyeball parsing.
-    ((138, 184), (-125, -71))
-    //((20, 30), (-10, -5))
+fn parse(data: &str) -> Result<ParseResult> {
+    let ranges = data.trim().strip_prefix("target area: ").ok_or_else(|| anyhow!("expected 'target area: '"))?;
+    let mut axes = ranges.split(", ").map(|r| {
+        let (_, r) = r.split_once('=').ok_or_else(|| anyhow!("bad range {:?}", r))?;
+        let (lo, hi) = r.split_once("..").ok_or_else(|| anyhow!("bad range {:?}", r))?;
+        let (lo, hi) = (lo.parse::<i64>()?, hi.parse::<i64>()?);
+        Ok((min(lo, hi), max(lo, hi)))
+    });
+    let mut next = || axes.next().unwrap_or_else(|| Err(anyhow!("missing range")));
+    Ok((next()?, next()?))
 }
 
 fn main() -> Result<()> {
     let mut puzzle = aoc::Puzzle::new(2021, 17)?;
     let data = puzzle.get_data()?;
-    let parsed = parse(data);
+    //let data = SAMPLE_DATA;
+    let parsed = parse(data)?;
 
-    let answ1 = part1(&parsed);
+    let answ1 = part1(&parsed)?;
     dbg!(&answ1);
     assert_eq!(answ1, 7750);
-    let answ2 = part2(&parsed);
+    let answ2 = part2(&parsed)?;
     dbg!(&answ2);
     assert_eq!(answ2, 4120);
     Ok(())
 }
+
+const SAMPLE_DATA: &str = "target area: x=20..30, y=-10..-5";
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let parsed = parse(SAMPLE_DATA).unwrap();
+        assert_eq!(parsed, ((20, 30), (-10, -5)));
+        assert_eq!(part1(&parsed).unwrap(), 45);
+        assert_eq!(part2(&parsed).unwrap(), 112);
+
+        // Mirrored, above the launch point, and straddling it in either direction.
+        let targets = [((-30, -20), (-10, -5)), ((5, 12), (3, 15)), ((-4, 6), (-8, -2)), ((11, 14), (-3, 4))];
+        for target in targets.iter() {
+            let shots = solve(target).unwrap();
+            let mut brute = Vec::new();
+            for vx in -40..=40 {
+                for vy in -40..=40 {
+                    if simulate(vx, vy, target).is_some() {
+                        brute.push((vx, vy));
+                    }
+                }
+            }
+            assert_eq!(shots.iter().map(|s| (s.vx, s.vy)).collect::<Vec<_>>(), brute, "{:?}", target);
+        }
+        assert!(solve(&((-3, 3), (-1, 1))).is_err());
+    }
+}
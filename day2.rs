@@ -2,69 +2,60 @@
 
 use anyhow::{anyhow, Result};
 
-fn part1(input: &str) -> String {
-    let mut hor = 0;
-    let mut depth = 0;
-    for line in input.lines() {
-        let mut vals = line.split(' ');
-        let cmd = vals.next().unwrap();
-        let n = vals.next().unwrap().parse::<i64>().unwrap();
-        match cmd {
-            "forward" => {
-                hor += n;
-            }
-            "down" => {
-                depth += n;
-            }
-            "up" => {
-                depth -= n;
-            }
-            _ => {
-                panic!();
-            }
-        }
-    }
-    dbg!(hor, depth);
+use aoc::navigation::{self, Aimed, Command, Navigator, Plain, Trajectory};
 
-    format!("{}", hor*depth)
+fn answer<N: Navigator>(nav: &N, commands: &[Command]) -> Result<i64> {
+    let end = Trajectory::new(nav, commands)?.end();
+    end.product().ok_or_else(|| anyhow!("{:?} overflows", end))
 }
 
-fn part2(input: &str) -> String {
-    let mut hor = 0;
-    let mut depth = 0;
-    let mut aim = 0;
-
-    for line in input.lines() {
-        let mut vals = line.split(' ');
-        let cmd = vals.next().unwrap();
-        let n = vals.next().unwrap().parse::<i64>().unwrap();
-        match cmd {
-            "forward" => {
-                hor += n;
-                depth += aim * n;
-            }
-            "down" => {
-                aim += n;
-            }
-            "up" => {
-                aim -= n;
-            }
-            _ => {
-                panic!();
-            }
-        }
-    }
-    dbg!(hor, depth);
+fn part1(commands: &[Command]) -> Result<i64> {
+    answer(&Plain, commands)
+}
 
-    format!("{}", hor*depth)
+fn part2(commands: &[Command]) -> Result<i64> {
+    answer(&Aimed, commands)
 }
 
 fn main() -> Result<()> {
     let mut puzzle = aoc::Puzzle::new2021(2)?;
     let data = puzzle.get_data()?;
+    //let data = SAMPLE_DATA;
+    let commands = navigation::parse(data)?;
+
+    let answ1 = part1(&commands)?;
+    dbg!(&answ1);
+    //puzzle.submit_answer(aoc::Part::One, &format!("{}", answ1))?;
 
-    //println!("{}", part1(data));
-    println!("{}", part2(data));
+    let answ2 = part2(&commands)?;
+    dbg!(&answ2);
+    //puzzle.submit_answer(aoc::Part::Two, &format!("{}", answ2))?;
+
+    if std::env::args().any(|a| a == "--print") {
+        let path = Trajectory::new(&Aimed, &commands)?;
+        println!("{}", path.plot(100, 30));
+        println!("Max depth {} over {} commands", path.max_depth(), path.steps());
+    }
 
     Ok(())
 }
+
+const SAMPLE_DATA: &str =
+"forward 5
+down 5
+forward 8
+up 3
+down 8
+forward 2";
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let commands = navigation::parse(SAMPLE_DATA).unwrap();
+        assert_eq!(part1(&commands).unwrap(), 150);
+        assert_eq!(part2(&commands).unwrap(), 900);
+    }
+}
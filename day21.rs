@@ -14,104 +14,133 @@ use std::hash::Hash;
 use std::iter::FromIterator;
 
 use aoc::{dbg2, byte, BitCursor, ByteString};
+use aoc::game::{self, Game};
 
 type ParseResult = (usize, usize);
 
-fn parse(data: &str) -> ParseResult {
-    (7, 9) // Real
-    //(4,8) // Sample
+/// Squares on the puzzle's board.
+const BOARD: u32 = 10;
+
+fn parse(data: &str) -> Result<ParseResult> {
+    let mut starts = data.lines().map(|line| {
+        let (_, pos) = line.rsplit_once(": ").ok_or_else(|| anyhow!("bad line {:?}", line))?;
+        let pos = pos.trim().parse::<usize>()?;
+        if !(1..=BOARD as usize).contains(&pos) {
+            return Err(anyhow!("start {} not on the board 1..={}", pos, BOARD));
+        }
+        Ok(pos)
+    });
+    let mut next = || starts.next().unwrap_or_else(|| Err(anyhow!("missing player")));
+    Ok((next()?, next()?))
 }
 
-fn part1(input: &ParseResult) -> usize {
-    let mut determin_die = (1..=100).cycle();
+/// Dirac Dice on a circular board of squares `1..=board`: each turn a player rolls the die
+/// `rolls` times, moves forward by the total and scores the square landed on.  The first to
+/// reach `target` wins.
+struct DiceGame {
+    board: u32,
+    target: u32,
+    die: u32,
+    rolls: u32,
+}
 
-    let mut pos = vec![input.0, input.1];
-    let mut scores = vec![0, 0];
-    let mut player = 0;
-    let mut nrolls = 0;
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+struct State {
+    pos: [u32; 2],
+    scores: [u32; 2],
+    /// Player to move.
+    player: usize,
+}
 
-    loop {
-        let roll = (0..3).map(|_| determin_die.next().unwrap()).sum::<usize>();
-        nrolls += 3;
-        pos[player] = ((pos[player] - 1 + roll) % 10) + 1;
-        scores[player] += pos[player];
+/// One turn of a deterministic game.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Turn {
+    player: usize,
+    rolls: Vec<u32>,
+    pos: u32,
+    score: u32,
+}
 
-        if scores[player] > 1000 {
-            break;
-        }
+impl DiceGame {
+    fn start(&self, input: &ParseResult) -> State {
+        State { pos: [input.0 as u32, input.1 as u32], scores: [0, 0], player: 0 }
+    }
 
-        player = (player + 1) % 2;
+    fn advance(&self, state: &State, total: u32) -> State {
+        let mut next = state.clone();
+        let p = state.player;
+        next.pos[p] = (state.pos[p] - 1 + total) % self.board + 1;
+        next.scores[p] += next.pos[p];
+        next.player = 1 - p;
+        next
     }
 
+    /// Every total of `rolls` rolls, with the number of ways to roll it.
+    fn totals(&self) -> Vec<(u32, u128)> {
+        let mut ways = vec![1u128];
+        for _ in 0..self.rolls {
+            let mut next = vec![0; ways.len() + self.die as usize];
+            for (t, &w) in ways.iter().enumerate() {
+                for face in 1..=self.die as usize {
+                    next[t + face] += w;
+                }
+            }
+            ways = next;
+        }
+        ways.iter().enumerate().filter(|(_, &w)| w > 0).map(|(t, &w)| (t as u32, w)).collect()
+    }
 
-    nrolls * scores[(player + 1) % 2]
+    /// Play with a die that rolls `1, 2, ..., die, 1, ...` until someone wins.
+    fn deterministic(&self, start: &State) -> Vec<Turn> {
+        let mut die = (1..=self.die).cycle();
+        let mut state = start.clone();
+        let mut trace = Vec::new();
+        while self.winner(&state).is_none() {
+            let rolls = (0..self.rolls).map(|_| die.next().unwrap()).collect::<Vec<_>>();
+            let player = state.player;
+            state = self.advance(&state, rolls.iter().sum());
+            trace.push(Turn { player, rolls, pos: state.pos[player], score: state.scores[player] });
+        }
+        trace
+    }
 }
 
-#[derive(Eq,PartialEq,Clone,Debug,Hash)]
-struct State {
-    pos: Vec<u8>,
-    scores: Vec<u8>,
-    player: u8,
-    count: u64,
-}
+impl Game for DiceGame {
+    type State = State;
 
-fn part2(input: &ParseResult) -> u64 {
-    // Precompute counts of dice rolls.
-    let rolls = {
-        let mut histo = HashMap::new();
-        for i in 1..=3 {
-            for j in 1..=3 {
-                for k in 1..=3 {
-                    *histo.entry((i+j+k)).or_default() += 1;
-                }
-            }
-        }
-        let mut rolls = histo.iter().map(|(k,v)| (*k, *v)).collect::<Vec<(usize, usize)>>();
-        rolls.sort();
-        rolls
-    };
-    dbg2!(&rolls);
-
-    // I expressed this as a game-tree search problem.
-    //
-    // We express the number of universes on the same "path" via the 'count' parameter.  Without
-    // this, the number grows too quickly.  E.g., when you roll 3 dice, there is one universe where
-    // you get 1+1+1, but there are 3 universes where you get 1+1+2.
-    let state = State { pos: vec![input.0 as _, input.1 as _], scores: vec![0, 0], player: 0, count: 1 };
-
-    // Since we need to evaluate the entire space, depth-first search is most efficient.
-    let mut queue = Vec::new();
-    queue.push(state);
-
-    let mut wins = vec![0,0];
-
-    while queue.len() > 0 {
-        let next = queue.pop().unwrap();
-
-        // Enumerate eligible moves.
-        for (roll, newcounts) in rolls.iter() {
-            let mut newstate = next.clone();
-            newstate.pos[next.player as usize] = ((newstate.pos[next.player as usize] - 1 + *roll as u8) % 10) + 1;
-            newstate.scores[next.player as usize] += newstate.pos[next.player as usize];
-            newstate.count *= *newcounts as u64;
-
-            // Count win conditions.
-            if newstate.scores[next.player as usize] >= 21 {
-                wins[next.player as usize] += newstate.count;
-                continue;
-            }
+    fn winner(&self, state: &State) -> Option<usize> {
+        (0..2).find(|&p| state.scores[p] >= self.target)
+    }
 
-            newstate.player = (newstate.player + 1) % 2;
-            queue.push(newstate);
+    fn moves(&self, state: &State) -> Vec<(State, u128)> {
+        self.totals().into_iter().map(|(total, ways)| (self.advance(state, total), ways)).collect()
+    }
+}
+
+fn part1(input: &ParseResult) -> usize {
+    let game = DiceGame { board: BOARD, target: 1000, die: 100, rolls: 3 };
+    let trace = game.deterministic(&game.start(input));
+    if std::env::args().any(|a| a == "--print") {
+        for (i, turn) in trace.iter().enumerate() {
+            println!("{:4}: player {} rolls {:?} and moves to {} for {}", i + 1, turn.player + 1, turn.rolls, turn.pos, turn.score);
         }
     }
+    let last = trace.last().unwrap();
+    let loser = trace.iter().rev().find(|t| t.player != last.player).map_or(0, |t| t.score);
+    trace.len() * game.rolls as usize * loser as usize
+}
+
+fn part2(input: &ParseResult) -> u128 {
+    let game = DiceGame { board: BOARD, target: 21, die: 3, rolls: 3 };
+    let wins = game::win_counts(&game, &game.start(input)).unwrap();
     *wins.iter().max().unwrap()
 }
 
 fn main() -> Result<()> {
     let mut puzzle = aoc::Puzzle::new(2021, 21)?;
     let data = puzzle.get_data()?;
-    let parsed = parse(data);
+    //let data = SAMPLE_DATA;
+    let parsed = parse(data)?;
 
     let answ1 = part1(&parsed);
     dbg!(&answ1);
@@ -122,3 +151,34 @@ fn main() -> Result<()> {
 
     Ok(())
 }
+
+const SAMPLE_DATA: &str =
+"Player 1 starting position: 4
+Player 2 starting position: 8";
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let parsed = parse(SAMPLE_DATA).unwrap();
+        assert_eq!(parsed, (4, 8));
+        assert!(parse("Player 1 starting position: 0\nPlayer 2 starting position: 8").is_err());
+        assert!(parse("Player 1 starting position: 4\nPlayer 2 starting position: 11").is_err());
+        assert_eq!(part1(&parsed), 739785);
+        assert_eq!(part2(&parsed), 444356092776315);
+
+        let game = DiceGame { board: BOARD, target: 1000, die: 100, rolls: 3 };
+        let trace = game.deterministic(&game.start(&parsed));
+        assert_eq!(trace[0], Turn { player: 0, rolls: vec![1, 2, 3], pos: 10, score: 10 });
+        assert_eq!(trace[3], Turn { player: 1, rolls: vec![10, 11, 12], pos: 6, score: 9 });
+        assert_eq!(trace.len(), 331);
+
+        let small = DiceGame { board: BOARD, target: 21, die: 3, rolls: 3 };
+        assert_eq!(small.totals(), vec![(3, 1), (4, 3), (5, 6), (6, 7), (7, 6), (8, 3), (9, 1)]);
+        let coin = DiceGame { board: 4, target: 30, die: 2, rolls: 1 };
+        let wins = game::win_counts(&coin, &coin.start(&(1, 1))).unwrap();
+        assert!(wins[0] > 0 && wins[1] > 0);
+    }
+}
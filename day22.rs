@@ -12,8 +12,11 @@ use std::convert::{TryFrom, TryInto};
 use std::collections::*;
 use std::hash::Hash;
 use std::iter::FromIterator;
+use std::time::Instant;
 
-use aoc::{dbg2, byte, BitCursor, ByteString};
+use aoc::{dbg2, byte, BitCursor, ByteString, XorShift};
+use aoc::cuboid::{Cuboid, CuboidSet};
+use aoc::volume::{self, BoxSplitting, CoordinateCompression, InclusionExclusion, Step, SweepLine, VolumeEngine};
 
 type Range = (i32, i32);
 type ParseResult = (Vec<(bool, Range, Range, Range)>);
@@ -38,121 +41,63 @@ fn parse(data: &str) -> ParseResult {
     .collect::<Vec<_>>()
 }
 
-fn part1(input: &ParseResult) -> u64 {
-    let bounded = input.iter()
-        .copied()
-        .filter(|(_, x, y, z)| {
-            (x.1 >= -50 && x.0 <= 50) &&
-            (y.1 >= -50 && y.0 <= 50) &&
-            (z.1 >= -50 && z.0 <= 50)
-        })
-        .map(|(onoff, x, y, z)| {
-            (onoff,
-             (max(-50, x.0), min(50, x.1)),
-             (max(-50, y.0), min(50, y.1)),
-             (max(-50, z.0), min(50, z.1)),
-             )
+fn steps(input: &ParseResult) -> Vec<Step<3>> {
+    input.iter().map(|&(onoff, x, y, z)| {
+        let lo = [x.0 as i64, y.0 as i64, z.0 as i64];
+        let hi = [x.1 as i64, y.1 as i64, z.1 as i64];
+        (onoff, Cuboid::new(lo, hi).unwrap())
     })
-    .collect::<Vec<_>>();
-    part2(&bounded)
+    .collect::<Vec<_>>()
 }
 
-fn part2(input: &ParseResult) -> u64 {
-    let mut boxes = Vec::new();
-    let mut total = 0u64;
-
-    for (_line, instr) in input.iter().cloned().enumerate() {
-        let (onoff, xr, yr, zr) = instr;
-        let (xlo, xhi) = (xr.0, xr.1);
-        let (ylo, yhi) = (yr.0, yr.1);
-        let (zlo, zhi) = (zr.0, zr.1);
-
-        let next = [[xlo, ylo, zlo], [xhi, yhi, zhi]];
-        let nextvol = (xhi as i64 - xlo as i64 + 1) * (yhi as i64 - ylo as i64 + 1) * (zhi as i64 - zlo as i64 + 1);
-
-        let mut rem = Vec::new();
-        for existing in 0..boxes.len() {
-            if let Some(overlap) = calc_overlap(&next, &boxes[existing]) {
-                // Cut overlapped region out of existing box.
-                delete_subcube(&mut rem, &mut boxes[existing], &overlap);
-                // Subtract deleted region from total.
-                let olx = (overlap[0][0] as i64, overlap[1][0] as i64);
-                let oly = (overlap[0][1] as i64, overlap[1][1] as i64);
-                let olz = (overlap[0][2] as i64, overlap[1][2] as i64);
-                total -= ((olx.1 - olx.0 + 1) * (oly.1 - oly.0 + 1) * (olz.1 - olz.0 + 1)) as u64;
-            }
-        }
-
-        boxes.append(&mut rem);
-        if onoff {
-            total += nextvol as u64;
-            boxes.push(next);
-        }
-    }
-    //dbg2!(boxes.len());
-    //let _dead = [[i32::MIN,i32::MIN,i32::MIN],[i32::MIN,i32::MIN,i32::MIN]];
-    //dbg2!(boxes.iter().filter(|b| *b == &_dead).count());
-    total
+fn part1(input: &ParseResult) -> u64 {
+    let region = Cuboid::new([-50; 3], [50; 3]).unwrap();
+    let bounded = volume::restrict(&steps(input), &region);
+    CoordinateCompression::default().volume(&bounded).unwrap() as u64
 }
 
-fn delete_subcube(rem: &mut Vec<[[i32;3];2]>, haystack: &mut [[i32;3];2], needle: &[[i32;3];2]) {
-    let haystack = {
-        let mut stack = [[i32::MIN,i32::MIN,i32::MIN],[i32::MIN,i32::MIN,i32::MIN]];
-        std::mem::swap(&mut stack, haystack);
-        stack
-    };
-
-    // six (possible) sub-cubes replace haystack.
-
-    if needle[0][0] > haystack[0][0] {
-        // left side cuboid "1"
-        rem.push([haystack[0], [needle[0][0] - 1, haystack[1][1], haystack[1][2]]]);
-    }
-
-    if needle[1][0] < haystack[1][0] {
-        // right side cuboid "2"
-        rem.push([[needle[1][0] + 1, haystack[0][1], haystack[0][2]], haystack[1]]);
-    }
-
-    if needle[0][1] > haystack[0][1] {
-        // bottom side cuboid "4"
-        rem.push([[needle[0][0], haystack[0][1], haystack[0][2]],
-                 [needle[1][0], needle[0][1] - 1, haystack[1][2]]]);
-    }
-
-    if needle[1][1] < haystack[1][1] {
-        // top side cuboid "3"
-        rem.push([[needle[0][0], needle[1][1] + 1, haystack[0][2]],
-                 [needle[1][0], haystack[1][1], haystack[1][2]]]);
-    }
-
-    if needle[0][2] > haystack[0][2] {
-        // front cuboid "5"
-        rem.push([[needle[0][0], needle[0][1], haystack[0][2]],
-                 [needle[1][0], needle[1][1], needle[0][2] - 1]]);
-    }
-
-    if needle[1][2] < haystack[1][2] {
-        // rear cuboid "6"
-        rem.push([[needle[0][0], needle[0][1], needle[1][2] + 1],
-                 [needle[1][0], needle[1][1], haystack[1][2]]]);
-    }
+fn part2(input: &ParseResult) -> u64 {
+    BoxSplitting.volume(&steps(input)).unwrap() as u64
 }
 
-#[inline]
-fn calc_overlap(a: &[[i32;3];2], b: &[[i32;3];2]) -> Option<[[i32;3];2]> {
-    let xhi = min(a[1][0], b[1][0]);
-    let yhi = min(a[1][1], b[1][1]);
-    let zhi = min(a[1][2], b[1][2]);
-
-    let xlo = max(a[0][0], b[0][0]);
-    let ylo = max(a[0][1], b[0][1]);
-    let zlo = max(a[0][2], b[0][2]);
+fn bench_engines(input: &ParseResult) {
+    let engines: Vec<Box<dyn VolumeEngine<3>>> = vec![
+        Box::new(BoxSplitting),
+        Box::new(InclusionExclusion),
+        Box::new(CoordinateCompression::default()),
+        Box::new(SweepLine),
+    ];
+
+    let mut rng = XorShift::new(0x2545f4914f6cdd1d);
+    let mut rand = |n: i64| rng.below(n as u64) as i64;
+    // Many large boxes overlapping everywhere, mostly on.
+    let overlapping = (0..400).map(|_| {
+        let lo = [rand(100_000) - 50_000, rand(100_000) - 50_000, rand(100_000) - 50_000];
+        let hi = [lo[0] + rand(60_000), lo[1] + rand(60_000), lo[2] + rand(60_000)];
+        (rand(4) != 0, Cuboid::new(lo, hi).unwrap())
+    })
+    .collect::<Vec<_>>();
+    // Thin slabs along alternating axes, each cutting through everything before it.
+    let slabs = (0..300).map(|i| {
+        let axis = i % 3;
+        let mut lo = [-100_000; 3];
+        let mut hi = [100_000; 3];
+        lo[axis] = rand(200_000) - 100_000;
+        hi[axis] = lo[axis] + rand(50);
+        (i % 2 == 0, Cuboid::new(lo, hi).unwrap())
+    })
+    .collect::<Vec<_>>();
 
-    if xhi >= xlo && yhi >= ylo && zhi >= zlo {
-        Some([[xlo, ylo, zlo], [xhi, yhi, zhi]])
-    } else {
-        None
+    let cases = [("input", steps(input)), ("overlapping", overlapping), ("slabs", slabs)];
+    for (case, steps) in cases.iter() {
+        let mut answers = Vec::new();
+        for engine in engines.iter() {
+            let start = Instant::now();
+            let answ = engine.volume(steps);
+            println!("{:>12} {:>24}: {:?} in {:?}", case, engine.name(), answ, start.elapsed());
+            answers.extend(answ);
+        }
+        assert!(answers.windows(2).all(|w| w[0] == w[1]), "engines disagree on {}", case);
     }
 }
 
@@ -162,6 +107,11 @@ fn main() -> Result<()> {
     //let data = SAMPLE_DATA;
     let parsed = parse(data);
 
+    if std::env::args().any(|a| a == "--bench") {
+        bench_engines(&parsed);
+        return Ok(());
+    }
+
     let answ1 = part1(&parsed);
     dbg!(&answ1);
     assert_eq!(answ1, 615700);
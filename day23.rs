@@ -12,464 +12,439 @@ use std::convert::{TryFrom, TryInto};
 use std::collections::*;
 use std::hash::Hash;
 use std::iter::FromIterator;
+use std::time::Instant;
 
 use rustc_hash::{FxHashMap as HashMap, FxHashSet as HashSet};
 
 use aoc::{dbg2, byte, BitCursor, ByteString};
 
-#[derive(Eq,PartialEq,Clone,Debug,Hash,PartialOrd,Ord)]
-struct GameState {
-    /// Location (row, column) of each Amphipod, indexed by type (A = 0, B = 1, ...) and member of
-    /// that type (0..=1).
-    locs: [[(u8, u8); 2]; 4],
+/// Marks an empty square in a `State`.
+const EMPTY: u8 = u8::MAX;
+
+/// Layout of the burrow: a straight hallway on row 1 with rooms hanging below it.  Room `i` is
+/// home to species `i` (A = 0, B = 1, ...), which spends `10^i` energy per step.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Burrow {
+    /// Column of the leftmost hallway square.
+    hall_col: usize,
+    hall_len: usize,
+    /// Hallway index of the square above each room.
+    doors: Vec<usize>,
+    depth: usize,
 }
 
-/// Win condition - all amphipods in their correct rooms.
-#[inline]
-fn win(state: &GameState) -> bool {
-    let locs = state.locs;
-    if locs[0][0].1 != 3 ||
-        locs[0][1].1 != 3 ||
-        locs[1][0].1 != 5 ||
-        locs[1][1].1 != 5 ||
-        locs[2][0].1 != 7 ||
-        locs[2][1].1 != 7 ||
-        locs[3][0].1 != 9 ||
-        locs[3][1].1 != 9 {
-            return false;
+/// Contents of every square: the hallway left to right, then each room top to bottom.  Amphipods
+/// of one species are interchangeable, so swapping two of them never gives a new state.
+#[derive(Clone, Debug, Eq, PartialEq, Hash, PartialOrd, Ord)]
+struct State(Vec<u8>);
+
+/// One amphipod walking from square `from` to square `to`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+struct Move {
+    species: u8,
+    from: usize,
+    to: usize,
+    energy: i64,
+}
+
+impl Burrow {
+    fn rooms(&self) -> usize {
+        self.doors.len()
+    }
+
+    /// Squares of room `r`, top first.
+    fn room(&self, r: usize) -> std::ops::Range<usize> {
+        let start = self.hall_len + r * self.depth;
+        start..start + self.depth
+    }
+
+    /// (row, column) of a square, as in the puzzle input.
+    fn coords(&self, square: usize) -> (usize, usize) {
+        if square < self.hall_len {
+            (1, self.hall_col + square)
+        } else {
+            let r = (square - self.hall_len) / self.depth;
+            (2 + (square - self.hall_len) % self.depth, self.hall_col + self.doors[r])
         }
-    for l in 0..4 {
-        for i in 0..2 {
-            if locs[l][i].0 < 2 ||
-                locs[l][i].0 > 3 {
-                    return false;
-                }
+    }
+
+    fn energy(species: u8) -> i64 {
+        10i64.pow(species as u32)
+    }
+
+    fn goal(&self) -> State {
+        let mut squares = vec![EMPTY; self.hall_len];
+        for r in 0..self.rooms() {
+            squares.resize(squares.len() + self.depth, r as u8);
         }
+        State(squares)
     }
-    true
-}
 
-/// COST to move corresponding Amphipod one square.
-const COST: [isize;4] = [1, 10, 100, 1000];
-
-/// Generate new state reflecting moving 'mover' to 'dest' on the existing board 'state'.
-///
-/// Checks that the path through the hallway is clear using 'hallway_occ'.
-///
-/// Returns `None` if the hallway is obstructed, or `Some((cost, newstate))` if the move was
-/// possible.
-#[inline]
-fn gen_state(state: &GameState, hallway_occ: &[bool;12], mover: (usize,usize), dest: (u8,u8)) -> Option<(i64, GameState)> {
-    let start = state.locs[mover.0][mover.1];
-    let dx = if start.1 < dest.1 { 1isize } else { -1 };
-
-    // Check that the walk is unobstructed.
-    let mut x = (start.1 as isize + dx);
-    while x != dest.1 as isize {
-        if hallway_occ[x as usize] {
-            return None;
+    /// Hallway squares after `from` up to and including `to` are empty.
+    fn clear(&self, state: &State, from: usize, to: usize) -> bool {
+        let hall = &state.0[..self.hall_len];
+        if from < to {
+            hall[from + 1..=to].iter().all(|&c| c == EMPTY)
+        } else {
+            hall[to..from].iter().all(|&c| c == EMPTY)
         }
-        x += dx;
     }
-    // Need to check the x == dest.1 case
-    if hallway_occ[x as usize] {
-        return None;
+
+    /// Legal moves: the top amphipod of an unsettled room out to a hallway square that isn't a
+    /// door, or an amphipod in the hallway into the bottom free square of its own room once only
+    /// its own species is there.
+    fn moves(&self, state: &State) -> Vec<Move> {
+        let mut moves = Vec::new();
+        for r in 0..self.rooms() {
+            let room = &state.0[self.room(r)];
+            let top = match room.iter().position(|&c| c != EMPTY) {
+                Some(top) => top,
+                None => continue,
+            };
+            if room[top..].iter().all(|&c| c == r as u8) {
+                continue;
+            }
+            let species = room[top];
+            for h in 0..self.hall_len {
+                if self.doors.contains(&h) || !self.clear(state, self.doors[r], h) {
+                    continue;
+                }
+                let steps = top + 1 + max(h, self.doors[r]) - min(h, self.doors[r]);
+                moves.push(Move {
+                    species,
+                    from: self.room(r).start + top,
+                    to: h,
+                    energy: steps as i64 * Self::energy(species),
+                });
+            }
+        }
+        for h in 0..self.hall_len {
+            let species = state.0[h];
+            if species == EMPTY {
+                continue;
+            }
+            let home = species as usize;
+            let room = &state.0[self.room(home)];
+            if room.iter().any(|&c| c != EMPTY && c != species) || !self.clear(state, h, self.doors[home]) {
+                continue;
+            }
+            let free = room.iter().take_while(|&&c| c == EMPTY).count();
+            let steps = free + max(h, self.doors[home]) - min(h, self.doors[home]);
+            moves.push(Move {
+                species,
+                from: h,
+                to: self.room(home).start + free - 1,
+                energy: steps as i64 * Self::energy(species),
+            });
+        }
+        moves
     }
 
-    // Manhattan distance.
-    let xdist = ((start.1 as isize) - (dest.1 as isize)).abs();
-    let ydist = ((start.0 as isize) - (dest.0 as isize)).abs();
+    fn apply(state: &State, m: &Move) -> State {
+        let mut next = state.clone();
+        next.0[m.to] = next.0[m.from];
+        next.0[m.from] = EMPTY;
+        next
+    }
 
-    let mut newstate = state.clone();
-    newstate.locs[mover.0][mover.1] = dest;
+    /// Squares an amphipod can stop on: everything but the hallway squares above doors.
+    fn stops(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.hall_len + self.rooms() * self.depth).filter(move |sq| !self.doors.contains(sq))
+    }
 
-    let cost = COST[mover.0] * (xdist + ydist);
-    Some((cost as i64, newstate))
-}
+    /// Bits per square in a packed state: enough for every species plus empty.
+    fn square_bits(&self) -> u32 {
+        usize::BITS - self.rooms().leading_zeros()
+    }
+
+    /// Canonical `u128` encoding of a state.  Door squares are always empty and left out.
+    fn pack(&self, state: &State) -> u128 {
+        let bits = self.square_bits();
+        self.stops().fold(0u128, |acc, sq| (acc << bits) | state.0[sq].wrapping_add(1) as u128)
+    }
 
-fn part1() -> i64 {
-    // I skipped parsing and just hand-entered the starting coordinates so that my game state could
-    // be pretty small.
-    let mut locs = [[(0,0);2];4];
-
-    // 'A' locations
-    locs[0][0] = (3,5);
-    locs[0][1] = (2,7);
-
-    // 'B' locations, etc
-    locs[1][0] = (2,5);
-    locs[1][1] = (3,9);
-
-    locs[2][0] = (3,3);
-    locs[2][1] = (2,9);
-
-    locs[3][0] = (2,3);
-    locs[3][1] = (3,7);
-
-    // SAMPLE - override locations with those of the Part 1 sample.
-    //locs[0][0] = (3,3);
-    //locs[0][1] = (3,9);
-    //locs[1][0] = (2,3);
-    //locs[1][1] = (2,7);
-    //locs[2][0] = (2,5);
-    //locs[2][1] = (3,7);
-    //locs[3][0] = (3,5);
-    //locs[3][1] = (2,9);
-
-    // Basic game-tree search using best-first search on the cost function, which we need to
-    // minimize.
-    let mut queue = BinaryHeap::new();
-    let mut visited = HashSet::default();
-    let start = GameState { locs, };
-    queue.push((0i64, start.clone()));
-    visited.insert(start);
-
-    while queue.len() != 0 {
-        let (sco, state) = queue.pop().unwrap();
-        //dbg2!(-sco);
-        //dbg2!(&state);
-        if win(&state) {
-            // Cost is negative because we want to minimize, but Rust's BinaryHeap is a max-heap.
-            return -sco;
+    fn unpack(&self, mut packed: u128) -> State {
+        let bits = self.square_bits();
+        let mut squares = vec![EMPTY; self.hall_len + self.rooms() * self.depth];
+        let stops = self.stops().collect::<Vec<_>>();
+        for &sq in stops.iter().rev() {
+            squares[sq] = ((packed & ((1 << bits) - 1)) as u8).wrapping_sub(1);
+            packed >>= bits;
         }
+        State(squares)
+    }
 
-        // Generate some expanded state to quickly answer valid-move questions.
-        let mut room_occ = [0u8; 4]; // how many correct species are in a room, or is it wrong-type occupied?
-        let mut hallway_occ = [false; 12]; // hallway coord index occupied
-        let mut room_top_occ = [false; 10]; // room[2] coord index occupied
-        for species in 0..4 {
-            for indv in 0..2 {
-                let coords = state.locs[species][indv];
-                if coords.0 >= 2 {
-                    if coords.0 == 2 {
-                        room_top_occ[coords.1 as usize] = true;
-                    }
-                    let room_idx = (coords.1 - 3) as usize/2;
-                    if (species*2 + 3) != coords.1 as usize {
-                        room_occ[room_idx] = 0x80;
-                    } else {
-                        if room_occ[room_idx] != 0x80 {
-                            room_occ[room_idx] += 1;
-                        }
-                    }
-                } else {
-                    hallway_occ[coords.1 as usize] = true;
+    /// Lower bound on the energy left: every amphipod not yet settled walks straight to its room,
+    /// ignoring everything in the way.  Those leaving the wrong part of their own room must step
+    /// out of the doorway and back.  Amphipods entering a room fill it from the free square nearest
+    /// the settled ones upwards, so `k` entering need `1 + 2 + ... + k` steps down.
+    fn heuristic(&self, state: &State) -> i64 {
+        let mut entering = vec![0usize; self.rooms()];
+        let mut energy = 0;
+        for h in 0..self.hall_len {
+            let species = state.0[h];
+            if species != EMPTY {
+                let door = self.doors[species as usize];
+                energy += (max(h, door) - min(h, door)) as i64 * Self::energy(species);
+                entering[species as usize] += 1;
+            }
+        }
+        for r in 0..self.rooms() {
+            let room = &state.0[self.room(r)];
+            let settled = room.iter().rev().take_while(|&&c| c == r as u8).count();
+            for (d, &species) in room[..self.depth - settled].iter().enumerate() {
+                if species == EMPTY {
+                    continue;
                 }
+                let door = self.doors[species as usize];
+                let across = if species as usize == r { 2 } else { max(door, self.doors[r]) - min(door, self.doors[r]) };
+                energy += (d + 1 + across) as i64 * Self::energy(species);
+                entering[species as usize] += 1;
             }
         }
-        //dbg2!(&room_occ);
-        //dbg2!(&hallway_occ);
-
-        // Generate valid moves.
-        for species in 0..4 {
-            // Destination x of this species' organized room.
-            let dest_room_x = (species*2 + 3) as u8;
-            for indv in 0..2 {
-                // we can move (1) from starting room to any hallway square, except a doorway;
-                // (1)(a) also inside starting room
-                // (2) from hallway to final room, iff devoid of other pods
-                // that's it?  cannot move between two hallway squares
-                let coords = state.locs[species][indv];
-
-                // Not strictly necessary, but avoid exploring some useless state.
-                if coords.1 == dest_room_x {
-                    // Won't move if in final room, most remote square
-                    if coords.0 == 3 {
-                        continue;
-                    }
-                    // Or near-square, but the other occupant is correct.
-                    if room_occ[species] == 2 {
-                        continue;
-                    }
+        for (species, &k) in entering.iter().enumerate() {
+            energy += (k * (k + 1) / 2) as i64 * Self::energy(species as u8);
+        }
+        energy
+    }
+
+    /// Cheapest way to get every amphipod home, by Dijkstra over unpacked states.
+    fn dijkstra(&self, start: &State) -> Option<Search> {
+        let goal = self.goal();
+        let mut queue = BinaryHeap::new();
+        let mut best: Best<State> = HashMap::default();
+        queue.push(std::cmp::Reverse((0i64, start.clone())));
+        best.insert(start.clone(), (0, None));
+        let mut expanded = 0;
+
+        while let Some(std::cmp::Reverse((energy, state))) = queue.pop() {
+            if energy > best[&state].0 {
+                continue;
+            }
+            if state == goal {
+                return Some(Search { energy, moves: trace(&best, state), expanded });
+            }
+            expanded += 1;
+            for m in self.moves(&state) {
+                let next = Self::apply(&state, &m);
+                let e = energy + m.energy;
+                if !matches!(best.get(&next), Some(&(b, _)) if b <= e) {
+                    best.insert(next.clone(), (e, Some((state.clone(), m))));
+                    queue.push(std::cmp::Reverse((e, next)));
                 }
+            }
+        }
+        None
+    }
 
-                // Starting in hallway; we can (only) move to the destination room.
-                if coords.0 == 1 {
-                    let occ = room_occ[species];
-                    // Can't move in if a different species is present.
-                    if occ == 0x80 {
-                        continue;
-                    }
-                    assert!(occ == 0 || occ == 1);
-                    // Always move into the furthest unoccupied space (optimal).
-                    let dest_y = 3 - occ;
-                    if let Some((cost, my_move)) = gen_state(&state, &hallway_occ, (species, indv), (dest_y, dest_room_x)) {
-                        if visited.contains(&my_move) {
-                            continue;
-                        }
-                        //dbg2!((cost, &my_move));
-                        visited.insert(my_move.clone());
-                        queue.push((sco - cost, my_move));
-                    }
-                } else {
-                    // Starting in a room; can (only) go to the hallway.
-                    let dest_y = 1;
-
-                    // Can't move to 3,5,7,9 (doorways).  Hallway spans 1..=11.
-                    for dest_x in [1,2,4,6,8,10,11] {
-                        // Can't go through another amphipod.
-                        if coords.0 == 3 && room_top_occ[coords.1 as usize] {
-                            continue;
-                        }
-                        if let Some((cost, my_move)) = gen_state(&state, &hallway_occ, (species, indv), (dest_y, dest_x)) {
-                            if visited.contains(&my_move) {
-                                continue;
-                            }
-                            //dbg2!((cost, &my_move));
-                            visited.insert(my_move.clone());
-                            queue.push((sco - cost, my_move));
-                        }
-                    }
+    /// Cheapest way to get every amphipod home, by A* over packed states.
+    fn solve(&self, start: &State) -> Option<Search> {
+        let goal = self.pack(&self.goal());
+        let mut queue = BinaryHeap::new();
+        let mut best: Best<u128> = HashMap::default();
+        let start = self.pack(start);
+        queue.push(std::cmp::Reverse((self.heuristic(&self.unpack(start)), 0i64, start)));
+        best.insert(start, (0, None));
+        let mut expanded = 0;
+
+        while let Some(std::cmp::Reverse((_, energy, packed))) = queue.pop() {
+            if energy > best[&packed].0 {
+                continue;
+            }
+            if packed == goal {
+                return Some(Search { energy, moves: trace(&best, packed), expanded });
+            }
+            expanded += 1;
+            let state = self.unpack(packed);
+            for m in self.moves(&state) {
+                let next = Self::apply(&state, &m);
+                let key = self.pack(&next);
+                let e = energy + m.energy;
+                if !matches!(best.get(&key), Some(&(b, _)) if b <= e) {
+                    best.insert(key, (e, Some((packed, m))));
+                    queue.push(std::cmp::Reverse((e + self.heuristic(&next), e, key)));
                 }
             }
         }
+        None
     }
+}
 
-    unreachable!();
+/// Result of a search, with the number of states expanded on the way.
+#[derive(Clone, Debug)]
+struct Search {
+    energy: i64,
+    moves: Vec<Move>,
+    expanded: usize,
 }
 
-//////////////////////////////////////////////////////////////////////////////////
-// PART 2
-//////////////////////////////////////////////////////////////////////////////////
+/// Cheapest known energy to reach each state, and the state and move it was reached by.
+type Best<K> = HashMap<K, (i64, Option<(K, Move)>)>;
 
-#[derive(Eq,PartialEq,Clone,Debug,Hash,PartialOrd,Ord)]
-struct GameState2 {
-    /// Location (row, column) of each Amphipod, indexed by type (A = 0, B = 1, ...) and member of
-    /// that type (0..=3).
-    locs: [[(u8, u8); 4]; 4],
+/// Moves leading to `end`, following the predecessors recorded in `best`.
+fn trace<K: Eq + Hash + Clone>(best: &Best<K>, end: K) -> Vec<Move> {
+    let mut moves = Vec::new();
+    let mut cur = end;
+    while let Some((prev, m)) = &best[&cur].1 {
+        moves.push(*m);
+        cur = prev.clone();
+    }
+    moves.reverse();
+    moves
 }
 
-/// Win condition - all amphipods in their correct rooms.
-#[inline]
-fn win2(state: &GameState2) -> bool {
-    let locs = state.locs;
-    if locs[0][0].1 != 3 ||
-        locs[0][1].1 != 3 ||
-        locs[0][2].1 != 3 ||
-        locs[0][3].1 != 3 ||
-        locs[1][0].1 != 5 ||
-        locs[1][1].1 != 5 ||
-        locs[1][2].1 != 5 ||
-        locs[1][3].1 != 5 ||
-        locs[2][0].1 != 7 ||
-        locs[2][1].1 != 7 ||
-        locs[2][2].1 != 7 ||
-        locs[2][3].1 != 7 ||
-        locs[3][0].1 != 9 ||
-        locs[3][1].1 != 9 ||
-        locs[3][2].1 != 9 ||
-        locs[3][3].1 != 9 {
-            return false;
+type ParseResult = (Burrow, State);
+
+fn parse(data: &str) -> Result<ParseResult> {
+    let lines = data.lines().collect::<Vec<_>>();
+    let hall = lines.get(1).ok_or_else(|| anyhow!("missing hallway"))?;
+    let hall_col = hall.find('.').ok_or_else(|| anyhow!("empty hallway"))?;
+    let hall_len = hall[hall_col..].bytes().take_while(|&c| c == b'.').count();
+
+    let room_lines = lines[2..].iter()
+        .take_while(|line| line.bytes().any(|c| c.is_ascii_uppercase()))
+        .collect::<Vec<_>>();
+    let first = room_lines.first().ok_or_else(|| anyhow!("no rooms"))?;
+    let cols = first.bytes()
+        .enumerate()
+        .filter(|(_, c)| c.is_ascii_uppercase())
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+    let doors = cols.iter().map(|&col| {
+        match col.checked_sub(hall_col) {
+            Some(d) if d < hall_len => Ok(d),
+            _ => Err(anyhow!("room at column {} isn't below the hallway", col)),
         }
-    for l in 0..4 {
-        for i in 0..4 {
-            if locs[l][i].0 < 2 ||
-                locs[l][i].0 > 5 {
-                    return false;
-                }
+    })
+    .collect::<Result<Vec<_>>>()?;
+    let burrow = Burrow { hall_col, hall_len, doors, depth: room_lines.len() };
+
+    let mut squares = vec![EMPTY; hall_len + burrow.rooms() * burrow.depth];
+    let mut counts = vec![0; burrow.rooms()];
+    for (d, line) in room_lines.iter().enumerate() {
+        for (r, &col) in cols.iter().enumerate() {
+            let c = line.as_bytes().get(col).copied().unwrap_or(b' ');
+            let species = c.wrapping_sub(b'A');
+            if !c.is_ascii_uppercase() || species as usize >= burrow.rooms() {
+                return Err(anyhow!("bad amphipod {:?} at row {}, column {}", c as char, d + 2, col));
+            }
+            counts[species as usize] += 1;
+            squares[burrow.room(r).start + d] = species;
         }
     }
-    true
-}
-
-/// Generate new state reflecting moving 'mover' to 'dest' on the existing board 'state'.
-///
-/// Checks that the path through the hallway is clear using 'hallway_occ'.
-///
-/// Returns `None` if the hallway is obstructed, or `Some((cost, newstate))` if the move was
-/// possible.
-#[inline]
-fn gen_state2(state: &GameState2, hallway_occ: &[bool;12], mover: (usize,usize), dest: (u8,u8)) -> Option<(i64, GameState2)> {
-    let start = state.locs[mover.0][mover.1];
-    let dx = if start.1 < dest.1 { 1isize } else { -1 };
-
-    // Check that the walk is unobstructed.
-    let mut x = (start.1 as isize + dx);
-    while x != dest.1 as isize {
-        if hallway_occ[x as usize] {
-            return None;
-        }
-        x += dx;
+    if let Some(s) = counts.iter().position(|&n| n != burrow.depth) {
+        return Err(anyhow!("{} amphipods of type {}, need {}", counts[s], (b'A' + s as u8) as char, burrow.depth));
     }
-    // Need to check the x == dest.1 case
-    if hallway_occ[x as usize] {
-        return None;
+    if burrow.stops().count() as u32 * burrow.square_bits() > 128 {
+        return Err(anyhow!("burrow too large to pack into 128 bits"));
     }
+    Ok((burrow, State(squares)))
+}
 
-    // Manhattan distance.
-    let xdist = ((start.1 as isize) - (dest.1 as isize)).abs();
-    let ydist = ((start.0 as isize) - (dest.0 as isize)).abs();
-
-    let mut newstate = state.clone();
-    newstate.locs[mover.0][mover.1] = dest;
-
-    let cost = COST[mover.0] * (xdist + ydist);
-    Some((cost as i64, newstate))
+/// Part 2's input: the two folded-up lines of the diagram go after the first room line.
+fn unfold(data: &str) -> String {
+    let mut lines = data.lines().collect::<Vec<_>>();
+    lines.splice(3..3, ["  #D#C#B#A#", "  #D#B#A#C#"].iter().copied());
+    lines.join("\n")
 }
 
-fn part2() -> i64 {
-    // As in part 1, I just skipped parsing and hand-entered the starting coordinates.
-    let mut locs = [[(0,0);4];4];
-
-    // 'A' locations
-    locs[0][0] = (5,5);
-    locs[0][1] = (2,7);
-
-    // 'B' locations, etc
-    locs[1][0] = (2,5);
-    locs[1][1] = (5,9);
-
-    locs[2][0] = (5,3);
-    locs[2][1] = (2,9);
-
-    locs[3][0] = (2,3);
-    locs[3][1] = (5,7);
-
-    // 3 #D#C#B#A#
-    // 4 #D#B#A#C#
-    locs[0][2] = (4,7);
-    locs[0][3] = (3,9);
-
-    locs[1][2] = (4,5);
-    locs[1][3] = (3,7);
-
-    locs[2][2] = (3,5);
-    locs[2][3] = (4,9);
-
-    locs[3][2] = (3,3);
-    locs[3][3] = (4,3);
-
-    // SAMPLE - override locations with those of the Part 1 sample.
-    //locs[0][0] = (5,3);
-    //locs[0][1] = (5,9);
-    //locs[1][0] = (2,3);
-    //locs[1][1] = (2,7);
-    //locs[2][0] = (2,5);
-    //locs[2][1] = (5,7);
-    //locs[3][0] = (5,5);
-    //locs[3][1] = (2,9);
-
-    // Basic game-tree search using best-first search on the cost function, which we need to
-    // minimize.
-    let mut queue = BinaryHeap::new();
-    let mut visited = HashSet::default();
-    let start = GameState2 { locs, };
-    queue.push((0i64, start.clone()));
-    visited.insert(start);
-
-    let mut _n = 0;
-    while queue.len() != 0 {
-        let (sco, state) = queue.pop().unwrap();
-        //dbg2!(-sco);
-        //dbg2!(&state);
-        if win2(&state) {
-            // Cost is negative because we want to minimize, but Rust's BinaryHeap is a max-heap.
-            return -sco;
-        }
+fn print_moves(burrow: &Burrow, moves: &[Move]) {
+    for m in moves.iter() {
+        println!("{} {:?} -> {:?}: {}", (b'A' + m.species) as char, burrow.coords(m.from), burrow.coords(m.to), m.energy);
+    }
+}
 
-        // Generate some expanded state to quickly answer valid-move questions.
-        let mut room_occ = [0u8; 4]; // how many correct species are in a room, or is it wrong-type occupied?
-        let mut hallway_occ = [false; 12]; // hallway coord index occupied
-        let mut room_top_occ = [[false;3]; 10]; // room[2..=4] coord index occupied
-        for species in 0..4 {
-            for indv in 0..4 {
-                let coords = state.locs[species][indv];
-                if coords.0 >= 2 {
-                    if coords.0 < 5 {
-                        room_top_occ[coords.1 as usize][coords.0 as usize - 2] = true;
-                    }
-                    let room_idx = (coords.1 - 3) as usize/2;
-                    if (species*2 + 3) != coords.1 as usize {
-                        room_occ[room_idx] = 0x80;
-                    } else {
-                        if room_occ[room_idx] != 0x80 {
-                            room_occ[room_idx] += 1;
-                        }
-                    }
-                } else {
-                    hallway_occ[coords.1 as usize] = true;
-                }
-            }
-        }
-        //dbg2!(&room_occ);
-        //dbg2!(&hallway_occ);
-        //dbg2!(&room_top_occ);
-
-        // Generate valid moves.
-        for species in 0..4 {
-            // Destination x of this species' organized room.
-            let dest_room_x = (species*2 + 3) as u8;
-            for indv in 0..4 {
-                // we can move (1) from starting room to any hallway square, except a doorway;
-                // (1)(a) also inside starting room, if non-final
-                // (2) from hallway to final room, iff devoid of other pods
-                // that's it?  cannot move between two hallway squares
-
-                let coords = state.locs[species][indv];
-
-                // Not strictly necessary, but avoid exploring some useless state.
-                if coords.1 == dest_room_x {
-                    // Won't move if in final room, most remote square, or similarly packed in.
-                    if room_occ[species] != 0x80 && room_occ[species] + coords.0 >= 6 {
-                        continue;
-                    }
-                }
+fn part1(input: &ParseResult) -> Result<i64> {
+    let (burrow, start) = input;
+    let search = burrow.solve(start).ok_or_else(|| anyhow!("no solution"))?;
+    if std::env::args().any(|a| a == "--print") {
+        print_moves(burrow, &search.moves);
+    }
+    Ok(search.energy)
+}
 
-                // Starting in hallway; we can (only) move to the destination room.
-                if coords.0 == 1 {
-                    let occ = room_occ[species];
-                    // Can't move in if a different species is present.
-                    if occ == 0x80 {
-                        continue;
-                    }
-                    // Always move into the furthest unoccupied space (optimal).
-                    let dest_y = 5 - occ;
-                    if let Some((cost, my_move)) = gen_state2(&state, &hallway_occ, (species, indv), (dest_y, dest_room_x)) {
-                        if visited.contains(&my_move) {
-                            continue;
-                        }
-                        //dbg2!("hall2room", (cost, &my_move));
-                        visited.insert(my_move.clone());
-                        queue.push((sco - cost, my_move));
-                    }
-                } else {
-                    // Starting in a room; can (only) go to the hallway.
-                    let dest_y = 1;
-
-                    // Can't move to 3,5,7,9 (doorways).  Hallway spans 1..=11.
-                    for dest_x in [1,2,4,6,8,10,11] {
-                        // Can't go through another amphipod.
-                        if coords.0 > 2 && room_top_occ[coords.1 as usize][0..(coords.0 as usize - 2)].iter().any(|o| *o) {
-                            continue;
-                        }
-                        if let Some((cost, my_move)) = gen_state2(&state, &hallway_occ, (species, indv), (dest_y, dest_x)) {
-                            if visited.contains(&my_move) {
-                                continue;
-                            }
-                            //dbg2!("room2hall", (cost, &my_move));
-                            visited.insert(my_move.clone());
-                            queue.push((sco - cost, my_move));
-                        }
-                    }
-                }
-            }
-        }
+fn part2(data: &str) -> Result<i64> {
+    let (burrow, start) = parse(&unfold(data))?;
+    let search = burrow.solve(&start).ok_or_else(|| anyhow!("no solution"))?;
+    if std::env::args().any(|a| a == "--print") {
+        print_moves(&burrow, &search.moves);
     }
+    Ok(search.energy)
+}
 
-    unreachable!();
+/// Compare plain Dijkstra on unpacked states against A* on packed ones.
+fn bench_search(data: &str) -> Result<()> {
+    for (name, data) in [("part 1", data.to_string()), ("part 2", unfold(data))].iter() {
+        let (burrow, start) = parse(data)?;
+        let time = |algo: &str, f: &dyn Fn() -> Option<Search>| {
+            let t = Instant::now();
+            let search = f().unwrap();
+            println!("{} {:>8}: {} energy, {} states expanded in {:?}", name, algo, search.energy, search.expanded, t.elapsed());
+            search
+        };
+        let dijkstra = time("dijkstra", &|| burrow.dijkstra(&start));
+        let astar = time("A*", &|| burrow.solve(&start));
+        assert_eq!(astar.energy, dijkstra.energy);
+    }
+    Ok(())
 }
 
 fn main() -> Result<()> {
     let mut puzzle = aoc::Puzzle::new(2021, 23)?;
-    let _data = puzzle.get_data()?;
+    let data = puzzle.get_data()?;
+    //let data = SAMPLE_DATA;
+    let parsed = parse(data)?;
+
+    if std::env::args().any(|a| a == "--bench") {
+        return bench_search(data);
+    }
 
-    let answ1 = part1();
+    let answ1 = part1(&parsed)?;
     dbg!(&answ1);
     //assert_ne!(answ1, 15282);
     //assert!(answ1 > 15332); // "Too low"
     assert_eq!(answ1, 15538);
-    let answ2 = part2();
+    let answ2 = part2(data)?;
     dbg!(&answ2);
     assert_eq!(answ2, 47258);
     Ok(())
 }
+
+const SAMPLE_DATA: &str =
+"#############
+#...........#
+###B#C#B#D###
+  #A#D#C#A#
+  #########";
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let parsed = parse(SAMPLE_DATA).unwrap();
+        assert_eq!(parsed.0, Burrow { hall_col: 1, hall_len: 11, doors: vec![2, 4, 6, 8], depth: 2 });
+        assert_eq!(part1(&parsed).unwrap(), 12521);
+        assert_eq!(part2(SAMPLE_DATA).unwrap(), 44169);
+
+        // One room, one deep.
+        let tiny = "#####\n#...#\n##A##\n  #";
+        assert_eq!(part1(&parse(tiny).unwrap()).unwrap(), 0);
+        assert!(parse("#####\n#...#\n##B##\n  #").is_err());
+
+        // The heuristic never overestimates, and packing round-trips.
+        let (burrow, start) = parse(&unfold(SAMPLE_DATA)).unwrap();
+        let search = burrow.solve(&start).unwrap();
+        assert!(search.expanded < burrow.dijkstra(&start).unwrap().expanded);
+        let mut state = start;
+        let mut left = search.energy;
+        for m in search.moves.iter() {
+            assert_eq!(burrow.unpack(burrow.pack(&state)), state);
+            assert!(burrow.heuristic(&state) <= left);
+            state = Burrow::apply(&state, m);
+            left -= m.energy;
+        }
+        assert_eq!((state, left), (burrow.goal(), 0));
+    }
+}
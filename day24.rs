@@ -15,145 +15,19 @@ use std::convert::{TryFrom, TryInto};
 use std::collections::*;
 use std::hash::Hash;
 use std::iter::FromIterator;
+use std::sync::{mpsc, Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 
-use rustc_hash::{FxHashMap as HashMap, FxHashSet as HashSet};
-
-use aoc::{dbg2, byte, BitCursor, ByteString};
-
-// Register
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
-enum R {
-    W,X,Y,Z,
-}
-type Im = i64;
-// Instr dst,src
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
-enum I {
-    Inp(R),
-    Add(R,R),
-    AddI(R,Im),
-    Mul(R,R),
-    MulI(R,Im),
-    Div(R,R),
-    DivI(R,Im),
-    Mod(R,R),
-    ModI(R,Im),
-    Eql(R,R),
-    EqlI(R,Im),
-}
+use rayon::prelude::*;
 
-fn parse_reg(input: &str) -> R {
-    let regc = input.chars().next().unwrap();
-    match regc {
-        'w' => R::W,
-        'x' => R::X,
-        'y' => R::Y,
-        'z' => R::Z,
-        _ => {
-            unreachable!("{}", regc);
-        }
-    }
-}
+use rustc_hash::{FxHashMap as HashMap, FxHashSet as HashSet};
 
-enum RegOrImm {
-    Imm(Im),
-    Reg(R),
-}
+use aoc::{dbg2, byte, BitCursor, ByteString, XorShift};
+use aoc::alu::{self, I, R, Machine};
 
-fn parse_regimm(input: &str) -> RegOrImm {
-    input.parse::<Im>()
-        .map(|i| RegOrImm::Imm(i))
-        .unwrap_or_else(|_| RegOrImm::Reg(parse_reg(input)))
-}
 type ParseResult = Vec<I>;
 
-fn parse(input: &str) -> ParseResult {
-    input.lines().map(|line| {
-        let mut words = line.split(' ');
-        let verb = words.next().unwrap();
-        //dbg!(line);
-        let dst = parse_regimm(words.next().unwrap());
-        let src = words.next().map(|w| parse_regimm(w));
-
-        match (verb, dst, src) {
-            ("inp", RegOrImm::Reg(dst), None) => I::Inp(dst),
-            ("add", RegOrImm::Reg(dst), Some(RegOrImm::Reg(r))) => I::Add(dst, r),
-            ("add", RegOrImm::Reg(dst), Some(RegOrImm::Imm(i))) => I::AddI(dst, i),
-            ("mul", RegOrImm::Reg(dst), Some(RegOrImm::Reg(r))) => I::Mul(dst, r),
-            ("mul", RegOrImm::Reg(dst), Some(RegOrImm::Imm(i))) => I::MulI(dst, i),
-            ("div", RegOrImm::Reg(dst), Some(RegOrImm::Reg(r))) => I::Div(dst, r),
-            ("div", RegOrImm::Reg(dst), Some(RegOrImm::Imm(i))) => I::DivI(dst, i),
-            ("mod", RegOrImm::Reg(dst), Some(RegOrImm::Reg(r))) => I::Mod(dst, r),
-            ("mod", RegOrImm::Reg(dst), Some(RegOrImm::Imm(i))) => I::ModI(dst, i),
-            ("eql", RegOrImm::Reg(dst), Some(RegOrImm::Reg(r))) => I::Eql(dst, r),
-            ("eql", RegOrImm::Reg(dst), Some(RegOrImm::Imm(i))) => I::EqlI(dst, i),
-            _ => {
-                unreachable!();
-            }
-        }
-    }).collect()
-}
-
-struct Machine {
-    regs: [i64; 4],
-    pc: isize,
-    program: Vec<I>,
-    inp: Vec<i64>,
-}
-
-impl Machine {
-    fn new(program: &[I]) -> Self {
-        Self {
-            regs: [0; 4],
-            pc: 0,
-            program: program.to_vec(),
-            inp: Vec::new(),
-        }
-    }
-
-    fn run_until(&mut self, endpc: isize) {
-        //let endpc = self.program.len() as isize;
-        while self.pc < endpc {
-            match self.program[self.pc as usize] {
-                I::Inp(dst) => {
-                    self.regs[dst as usize] = self.inp.pop().unwrap();
-                }
-                I::Add(dst, src) => {
-                    self.regs[dst as usize] += self.regs[src as usize];
-                }
-                I::AddI(dst, src) => {
-                    self.regs[dst as usize] += src;
-                }
-                I::Mul(dst, src) => {
-                    self.regs[dst as usize] *= self.regs[src as usize];
-                }
-                I::MulI(dst, src) => {
-                    self.regs[dst as usize] *= src;
-                }
-                I::Div(dst, src) => {
-                    self.regs[dst as usize] /= self.regs[src as usize];
-                }
-                I::DivI(dst, src) => {
-                    self.regs[dst as usize] /= src;
-                }
-                I::Mod(dst, src) => {
-                    self.regs[dst as usize] %= self.regs[src as usize];
-                }
-                I::ModI(dst, src) => {
-                    self.regs[dst as usize] %= src;
-                }
-                I::Eql(dst, src) => {
-                    self.regs[dst as usize] = if self.regs[dst as usize] == self.regs[src as usize] { 1 } else { 0 };
-                }
-                I::EqlI(dst, src) => {
-                    self.regs[dst as usize] = if self.regs[dst as usize] == src { 1 } else { 0 };
-                }
-            };
-            self.pc += 1;
-        }
-    }
-}
-
 fn jit_segment(
     module: &mut JITModule,
     ctx: &mut cranelift_codegen::Context,
@@ -497,37 +371,255 @@ fn part2(program: &ParseResult) -> i64 {
     forwards
 }
 
-fn part2_brute(program: &ParseResult) -> i64 {
-    let emu = JitMachine::new_brute(program);
-    let func = emu.segments[0];
-    for i in 11111111111111..=99999999999999 {
-    //for i in 11811951311485..=11811951311485 {
-    //for i in 11811951311485..=99999999999999 {
-        let res = func(i, 0);
-        if res == 0 {
-            return i;
+/// Number of memo table shards; keeps lock contention between rayon workers low.
+const MEMO_SHARDS: usize = 64;
+
+/// One memo shard, keyed by `(block, z)`.
+type MemoShard = Mutex<HashMap<(usize, i64), Option<(i64, i64)>>>;
+
+/// Shared state for the parallel search over model numbers, one 18-instruction block (digit) at a
+/// time.
+struct ParSearch<'a, F> {
+    /// `step(block, w, z)` runs one block and returns the new `z`.
+    step: &'a F,
+    nblocks: usize,
+    /// Upper bound on `z` at the start of each block from which zero is still reachable: the
+    /// product of the remaining `div z` divisors.  Pushes only ever make `z` larger.
+    z_limit: Vec<i64>,
+    /// Memoized `(largest, smallest)` digit suffix reaching `z == 0` from `(block, z)`, if any.
+    memo: Vec<MemoShard>,
+    cancel: &'a AtomicBool,
+}
+
+impl<'a, F> ParSearch<'a, F>
+where
+    F: Fn(usize, i64, i64) -> i64 + Sync,
+{
+    fn new(program: &[I], step: &'a F, cancel: &'a AtomicBool) -> Self {
+        let divisors = program.chunks(18).map(|block| {
+            block.iter().map(|inst| match inst {
+                I::DivI(R::Z, d) => *d,
+                _ => 1,
+            }).product::<i64>()
+        }).collect::<Vec<_>>();
+
+        let mut z_limit = vec![1i64; divisors.len()];
+        for block in (0..divisors.len()).rev() {
+            let next = z_limit.get(block + 1).copied().unwrap_or(1);
+            z_limit[block] = next.saturating_mul(divisors[block]);
         }
-        if res == -1 {
-            //println!("fault: {}", i);
+
+        Self {
+            step,
+            nblocks: divisors.len(),
+            z_limit,
+            memo: (0..MEMO_SHARDS).map(|_| Mutex::new(HashMap::default())).collect(),
+            cancel,
+        }
+    }
+
+    fn search(&self, block: usize, z: i64) -> Option<(i64, i64)> {
+        if block == self.nblocks {
+            return if z == 0 { Some((0, 0)) } else { None };
+        }
+        if z >= self.z_limit[block] || self.cancel.load(Ordering::Relaxed) {
+            return None;
+        }
+
+        let shard = &self.memo[(z as u64 as usize).wrapping_add(block) % MEMO_SHARDS];
+        if let Some(&res) = shard.lock().unwrap().get(&(block, z)) {
+            return res;
+        }
+
+        // Visit every digit (rather than stopping at the first hit) so that one pass yields both
+        // the largest and the smallest suffix.
+        let place = 10i64.pow((self.nblocks - block - 1) as u32);
+        let mut res: Option<(i64, i64)> = None;
+        for digit in 1..=9 {
+            let z2 = (self.step)(block, digit, z);
+            if let Some((hi, lo)) = self.search(block + 1, z2) {
+                let (hi, lo) = (digit * place + hi, digit * place + lo);
+                res = Some(match res {
+                    None => (hi, lo),
+                    Some((besthi, bestlo)) => (max(besthi, hi), min(bestlo, lo)),
+                });
+            }
+        }
+
+        // A cancelled search may have cut subtrees short; don't poison the table.
+        if !self.cancel.load(Ordering::Relaxed) {
+            shard.lock().unwrap().insert((block, z), res);
         }
-        if i % 10_000_000 == 0 {
-            dbg!(i);
+        res
+    }
+}
+
+/// Find the largest and smallest valid model numbers in one pass, splitting the search across
+/// rayon workers by the first two digits.
+///
+/// Progress and an ETA are reported on stderr as each prefix completes.  Setting `cancel` stops
+/// the search early with an error.
+fn par_search<F>(program: &[I], step: &F, cancel: &AtomicBool) -> Result<(i64, i64)>
+where
+    F: Fn(usize, i64, i64) -> i64 + Sync,
+{
+    let search = ParSearch::new(program, step, cancel);
+    if search.nblocks < 2 {
+        return Err(anyhow!("Need at least 2 blocks to split the search, got {}", search.nblocks));
+    }
+    let prefixes = iproduct!(1..=9i64, 1..=9i64).collect::<Vec<_>>();
+    let place = 10i64.pow((search.nblocks - 2) as u32);
+
+    let start = Instant::now();
+    let done = AtomicUsize::new(0);
+    let results = prefixes.par_iter().filter_map(|&(d0, d1)| {
+        let z = step(1, d1, step(0, d0, 0));
+        let res = search.search(2, z)
+            .map(|(hi, lo)| ((d0 * 10 + d1) * place + hi, (d0 * 10 + d1) * place + lo));
+
+        let n = done.fetch_add(1, Ordering::Relaxed) + 1;
+        let elapsed = start.elapsed();
+        let eta = elapsed.mul_f64((prefixes.len() - n) as f64 / n as f64);
+        eprintln!("[{}/{}] elapsed {:.1?}, ETA {:.1?}", n, prefixes.len(), elapsed, eta);
+        res
+    }).collect::<Vec<_>>();
+
+    if cancel.load(Ordering::Relaxed) {
+        return Err(anyhow!("Search cancelled"));
+    }
+
+    let best = results.iter().map(|r| r.0).max().ok_or_else(|| anyhow!("No valid model number"))?;
+    let worst = results.iter().map(|r| r.1).min().unwrap();
+    Ok((best, worst))
+}
+
+/// Both parts via the parallel exhaustive search, using the JIT for each block.  Gives up with an
+/// error after `timeout`, if set.
+fn part12_parallel(program: &ParseResult, timeout: Option<Duration>) -> Result<(i64, i64)> {
+    let emu = JitMachine::new(program);
+    let segments = &emu.segments;
+    let step = |block: usize, w: i64, z: i64| segments[block](w, z);
+
+    let cancel = Arc::new(AtomicBool::new(false));
+    let (done, finished) = mpsc::channel::<()>();
+    let watchdog = timeout.map(|timeout| {
+        let cancel = cancel.clone();
+        std::thread::spawn(move || {
+            if let Err(mpsc::RecvTimeoutError::Timeout) = finished.recv_timeout(timeout) {
+                cancel.store(true, Ordering::Relaxed);
+            }
+        })
+    });
+
+    let res = par_search(program, &step, &cancel);
+    drop(done);
+    if let Some(watchdog) = watchdog {
+        watchdog.join().unwrap();
+    }
+    res
+}
+
+/// Solve both parts from the digit constraints derived by symbolic execution.
+fn solve_symbolic(program: &ParseResult) -> Result<(i64, i64)> {
+    let analysis = alu::analyze(program)?;
+    print!("{}", analysis);
+
+    let max = analysis.solve(true).ok_or_else(|| anyhow!("No valid model number"))?;
+    let min = analysis.solve(false).unwrap();
+
+    // Double-check against the interpreter.
+    let mut emu = Machine::new(program);
+    assert_eq!(emu.run_model_number(&max), 0);
+    assert_eq!(emu.run_model_number(&min), 0);
+
+    Ok((alu::digits_to_num(&max), alu::digits_to_num(&min)))
+}
+
+/// Time the interpreter, the Cranelift JIT and the build-time compiled program (`aoc::aot`) on the
+/// same pseudo-random model numbers.
+fn bench_backends(program: &ParseResult) {
+    const RUNS: usize = 1_000_000;
+    let mut rng = XorShift::new(0x2545f4914f6cdd1d);
+    let numbers = (0..RUNS).map(|_| {
+        (0..14).map(|_| rng.below(9) as i64 + 1).collect::<Vec<_>>()
+    })
+    .collect::<Vec<_>>();
+
+    let time = |name: &str, f: &dyn Fn(&[i64]) -> i64| -> Vec<i64> {
+        let start = Instant::now();
+        let res = numbers.iter().map(|digits| f(digits)).collect::<Vec<_>>();
+        let elapsed = start.elapsed();
+        println!("{:>12}: {:?} ({:.1} ns/run)", name, elapsed, elapsed.as_nanos() as f64 / RUNS as f64);
+        res
+    };
+
+    let emu = std::cell::RefCell::new(Machine::new(program));
+    let interp = time("interpreter", &|digits| emu.borrow_mut().run_model_number(digits));
+
+    let jit = JitMachine::new_brute(program);
+    let func = jit.segments[0];
+    let cranelift = time("cranelift", &|digits| func(alu::digits_to_num(digits), 0));
+    assert_eq!(cranelift, interp);
+
+    match aoc::aot::DAY24 {
+        Some(aot) => {
+            let compiled = time("aot", &|digits| aot([0; 4], digits).0[R::Z as usize]);
+            assert_eq!(compiled, interp);
         }
+        None => println!("{:>12}: input wasn't cached at build time", "aot"),
     }
-    unreachable!();
 }
 
 fn main() -> Result<()> {
     let mut puzzle = aoc::Puzzle::new(2021, 24)?;
     let data = puzzle.get_data()?;
-    let parsed = parse(data);
+    let parsed = alu::parse(data);
+
+    if std::env::args().any(|a| a == "--bench") {
+        bench_backends(&parsed);
+        return Ok(());
+    }
+
+    let (answ1, answ2) = solve_symbolic(&parsed)?;
+    dbg!(&answ1);
+    assert_eq!(answ1, 52926995971999);
+
+    let timeout = std::env::args()
+        .skip_while(|a| a != "--timeout")
+        .nth(1)
+        .map(|secs| secs.parse().map(Duration::from_secs))
+        .transpose()?;
+    let (par1, par2) = part12_parallel(&parsed, timeout)?;
+    assert_eq!((par1, par2), (answ1, answ2));
 
     //let answ1 = part1(&parsed);
     //dbg!(&answ1);
     //assert_eq!(answ1, 52926995971999);
     //let answ2 = part2(&parsed);
-    let answ2 = part2_brute(&parsed);
     dbg!(&answ2);
     assert_eq!(answ2, 11811951311485);
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let block = alu::monad_block;
+        let src = [block(1, 12, 4), block(1, 11, 2), block(26, -5, 9), block(1, 13, 3), block(26, -2, 1), block(26, -1, 7)]
+            .concat();
+        let program = alu::parse(&src);
+        let step = |blk: usize, w: i64, z: i64| {
+            let mut m = Machine::new(&program[blk * 18..(blk + 1) * 18]);
+            m.regs = [0, 0, 0, z];
+            m.inp = vec![w];
+            m.run_until(18);
+            m.regs[3]
+        };
+        let cancel = AtomicBool::new(false);
+        assert_eq!(par_search(&program, &step, &cancel).unwrap(), solve_symbolic(&program).unwrap());
+        assert!(par_search(&program[..18], &step, &cancel).is_err());
+    }
+}
@@ -3,119 +3,22 @@
 use anyhow::{anyhow, Result};
 use std::collections::HashMap;
 
-fn part1(input: &str) -> String {
-    let mut hist = Vec::new();
+use aoc::diagnostic::{Criterion, Report};
 
-    for line in input.lines() {
-        let mut idx = 0;
-
-        for ch in line.chars() {
-            if hist.len() <= idx {
-                hist.push(HashMap::new());
-            }
-
-            let count = hist[idx].get(&ch).copied().unwrap_or(0);
-            hist[idx].insert(ch, count + 1);
-
-            idx += 1;
-        }
-    }
-
-    let mut gamma = 0u64;
-    let mut mask = 0;
-    for (i, hist) in hist.iter().enumerate() {
-        dbg!(i, hist);
-
-        gamma <<= 1;
-        if hist[&'1'] > hist[&'0'] {
-            gamma |= 0x1;
-        }
-
-        mask <<= 1;
-        mask |= 1;
-    }
-
-    let epsilon = (!gamma & mask);
-
-    dbg!(gamma, epsilon);
-
-    format!("{}", gamma * epsilon)
+fn parse(input: &str) -> Result<Report> {
+    Ok(Report::parse(input, '0', '1')?)
 }
 
-fn histo(lines: &Vec<&str>) -> Vec<HashMap<char, u64>> {
-    let mut hist = Vec::new();
-
-    for line in lines {
-        let mut idx = 0;
-
-        for ch in line.chars() {
-            if hist.len() <= idx {
-                hist.push(HashMap::new());
-            }
-
-            let count = hist[idx].get(&ch).copied().unwrap_or(0);
-            hist[idx].insert(ch, count + 1);
-
-            idx += 1;
-        }
-    }
-
-    return hist;
-}
-
-fn bit_criteria_o2<'a>(lines: &Vec<&'a str>, idx: usize) -> Vec<&'a str> {
-    let hist_o2 = histo(lines);
-    let hist = &hist_o2[idx];
-    let desired_bit = if hist[&'1'] >= hist[&'0'] {
-        '1'
-    } else {
-        '0'
-    };
-
-    lines.iter().filter(|line| {
-        line.chars().nth(idx).unwrap() == desired_bit
-    })
-    .map(|s| *s)
-    .collect()
+fn part1(report: &Report) -> Result<u64> {
+    let gamma = report.rate(Criterion::MOST);
+    let epsilon = report.rate(Criterion::LEAST);
+    gamma.checked_mul(epsilon).ok_or_else(|| anyhow!("{} * {} overflows", gamma, epsilon))
 }
 
-fn bit_criteria_co2<'a>(lines: &Vec<&'a str>, idx: usize) -> Vec<&'a str> {
-    let hist_co2 = histo(lines);
-    let hist = &hist_co2[idx];
-    let desired_bit = if hist[&'1'] >= hist[&'0'] {
-        '0'
-    } else {
-        '1'
-    };
-
-    lines.iter().filter(|line| {
-        line.chars().nth(idx).unwrap() == desired_bit
-    })
-    .map(|s| *s)
-    .collect()
-}
-
-fn part2(input: &str) -> String {
-    let mut lines_o2 = input.lines().collect::<Vec<_>>();
-    let mut lines_co2 = lines_o2.clone();
-    let mut idx = 0;
-    while lines_o2.len() > 1 {
-        lines_o2 = bit_criteria_o2(&lines_o2, idx);
-        idx += 1;
-    }
-    let o2_gen = usize::from_str_radix(lines_o2[0], 2).unwrap();
-    dbg!(o2_gen);
-
-    let mut idx = 0;
-    while lines_co2.len() > 1 {
-        lines_co2 = bit_criteria_co2(&lines_co2, idx);
-        idx += 1;
-    }
-    let co2_scrub = usize::from_str_radix(lines_co2[0], 2).unwrap();
-    dbg!(co2_scrub);
-
-    let lfs = o2_gen * co2_scrub;
-    format!("{}", lfs)
+fn part2(report: &Report) -> Result<u64> {
+    let o2_gen = report.rating(Criterion::MOST).ok_or_else(|| anyhow!("empty report"))?;
+    let co2_scrub = report.rating(Criterion::LEAST).ok_or_else(|| anyhow!("empty report"))?;
+    o2_gen.checked_mul(co2_scrub).ok_or_else(|| anyhow!("{} * {} overflows", o2_gen, co2_scrub))
 }
 
 fn submit(puzzle: &mut aoc::Puzzle, part: aoc::Part, answ: &str) -> Result<()> {
@@ -126,11 +29,46 @@ fn submit(puzzle: &mut aoc::Puzzle, part: aoc::Part, answ: &str) -> Result<()> {
 fn main() -> Result<()> {
     let mut puzzle = aoc::Puzzle::new2021(3)?;
     let data = puzzle.get_data()?;
+    //let data = SAMPLE_DATA;
+    let report = parse(data)?;
+
+    let answ1 = part1(&report)?;
+    dbg!(&answ1);
+    //submit(&mut puzzle, aoc::Part::One, &format!("{}", answ1))?;
 
-    //let answ1 = part1(data);
-    //submit(&mut puzzle, aoc::Part::One, &answ1)?;
-    let answ2 = part2(data);
-    submit(&mut puzzle, aoc::Part::Two, &answ2)?;
+    let answ2 = part2(&report)?;
+    dbg!(&answ2);
+    submit(&mut puzzle, aoc::Part::Two, &format!("{}", answ2))?;
 
     Ok(())
 }
+
+const SAMPLE_DATA: &str =
+"00100
+11110
+10110
+10111
+10101
+01111
+00111
+11100
+10000
+11001
+00010
+01010";
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let report = parse(SAMPLE_DATA).unwrap();
+        assert_eq!(part1(&report).unwrap(), 198);
+        assert_eq!(part2(&report).unwrap(), 230);
+
+        let wide = parse(&format!("{}{}", "1".repeat(32), "0".repeat(32))).unwrap();
+        assert!(part1(&wide).is_err());
+        assert!(part2(&wide).is_err());
+    }
+}
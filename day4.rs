@@ -3,160 +3,18 @@
 use anyhow::{anyhow, Result};
 use std::collections::*;
 
-type Num = i16;
-
-struct Board {
-    board: Vec<Vec<Num>>,
-    rows_unmarked: Vec<HashSet<Num>>,
-    cols_unmarked: Vec<HashSet<Num>>,
-    histo: HashMap<Num, u64>,
-    sum_unmarked: u64,
-    won: bool,
-}
-
-impl Board {
-    fn new(board: Vec<Vec<Num>>) -> Self {
-        let nrows = board.len();
-        let ncols = board[0].len();
-
-        let mut rows = Vec::new();
-        let mut cols = Vec::new();
-        let mut histo = HashMap::new();
-        let mut total = 0;
-
-        for (y, row) in board.iter().enumerate() {
-            if rows.len() <= y {
-                rows.push(HashSet::new());
-            }
-            for (x, val) in row.iter().enumerate() {
-                if cols.len() <= x {
-                    cols.push(HashSet::new());
-                }
-
-                cols[x].insert(*val);
-                rows[y].insert(*val);
-
-                let count = histo.get(val).copied().unwrap_or(0);
-                histo.insert(*val, count + 1);
-
-                total += *val as u64;
-            }
-        }
-
-        Self {
-            board,
-            rows_unmarked: rows,
-            cols_unmarked: cols,
-            histo,
-            sum_unmarked: total,
-            won: false,
-        }
-    }
-
-    fn mark(&mut self, mark: Num) -> bool {
-        let count = if let Some(count) = self.histo.get(&mark) {
-            count
-        } else {
-            return false;
-        };
-
-        self.sum_unmarked -= (mark as u64) * count;
+use aoc::bingo::{Bingo, Win};
 
-        for i in 0..5 {
-            self.rows_unmarked[i].remove(&mark);
-            self.cols_unmarked[i].remove(&mark);
-        }
-        for i in 0..5 {
-            if self.rows_unmarked[i].is_empty() {
-                self.won = true;
-                return true;
-            }
-            if self.cols_unmarked[i].is_empty() {
-                self.won = true;
-                return true;
-            }
-        }
-        false
-    }
+fn parse(input: &str) -> Result<Bingo> {
+    Ok(input.parse()?)
 }
 
-struct Game {
-    marks: Vec<Num>,
-    boards: Vec<Board>,
+fn part1(wins: &[Win]) -> Option<u64> {
+    wins.first().map(|w| w.score)
 }
 
-impl Game {
-    fn new() -> Self {
-        Self { marks: Vec::new(), boards: Vec::new(), }
-    }
-}
-
-fn parse(input: &str) -> Game {
-    let mut result = Game::new();
-
-    let mut lines = input.lines();
-
-    let markline = lines.next().unwrap();
-    for mark in markline.split(',') {
-        result.marks.push(mark.parse::<Num>().unwrap());
-    }
-
-    // blank line
-    while lines.next().is_some() {
-        let mut board = Vec::new();
-        for y in 0..5 {
-            let mut row = Vec::new();
-            let line = lines.next().unwrap();
-            for val in line.split_ascii_whitespace() {
-                row.push(val.parse::<Num>().unwrap());
-            }
-            assert_eq!(row.len(), 5);
-            board.push(row);
-        }
-        assert_eq!(board.len(), 5);
-
-        result.boards.push(Board::new(board));
-    }
-
-    result
-}
-
-fn part1(input: &str) -> String {
-    let game = parse(input);
-    let marks = game.marks;
-    let mut boards = game.boards;
-
-    for mark in marks.iter() {
-        for board in boards.iter_mut() {
-            if board.mark(*mark) {
-                dbg!(mark, board.sum_unmarked);
-                return format!("{}", (*mark as u64) * board.sum_unmarked);
-            }
-        }
-    }
-    unreachable!();
-}
-
-fn part2(input: &str) -> String {
-    let game = parse(input);
-    let marks = game.marks;
-    let mut boards = game.boards;
-
-    let mut last_win = 0;
-
-    for mark in marks.iter() {
-        for board in boards.iter_mut() {
-            if board.won {
-                continue;
-            }
-            if board.mark(*mark) {
-                dbg!(mark, board.sum_unmarked);
-                last_win = (*mark as u64) * board.sum_unmarked;
-                dbg!(last_win);
-            }
-        }
-    }
-    format!("{}", last_win)
+fn part2(wins: &[Win]) -> Option<u64> {
+    wins.last().map(|w| w.score)
 }
 
 fn submit(puzzle: &mut aoc::Puzzle, part: aoc::Part, answ: &str) -> Result<()> {
@@ -167,11 +25,59 @@ fn submit(puzzle: &mut aoc::Puzzle, part: aoc::Part, answ: &str) -> Result<()> {
 fn main() -> Result<()> {
     let mut puzzle = aoc::Puzzle::new2021(4)?;
     let data = puzzle.get_data()?;
+    //let data = SAMPLE_DATA;
+    let game = parse(data)?;
+    let wins = game.play();
+
+    let answ1 = part1(&wins).ok_or_else(|| anyhow!("no board wins"))?;
+    dbg!(&answ1);
+    //submit(&mut puzzle, aoc::Part::One, &format!("{}", answ1))?;
 
-    //let answ1 = part1(data);
-    //submit(&mut puzzle, aoc::Part::One, &answ1)?;
-    let answ2 = part2(data);
-    submit(&mut puzzle, aoc::Part::Two, &answ2)?;
+    let answ2 = part2(&wins).ok_or_else(|| anyhow!("no board wins"))?;
+    dbg!(&answ2);
+    submit(&mut puzzle, aoc::Part::Two, &format!("{}", answ2))?;
+
+    if std::env::args().any(|a| a == "--print") {
+        for (rank, w) in wins.iter().enumerate() {
+            println!("#{}: board {} on call {} ({}), score {}", rank + 1, w.board, w.turn + 1, w.call, w.score);
+        }
+        let diag = game.diagonals(true).play();
+        println!("With diagonals: first {:?}, last {:?}", part1(&diag), part2(&diag));
+    }
 
     Ok(())
 }
+
+const SAMPLE_DATA: &str =
+"7,4,9,5,11,17,23,2,0,14,21,24,10,16,13,6,15,25,12,22,18,20,8,19,3,26,1
+
+22 13 17 11  0
+ 8  2 23  4 24
+21  9 14 16  7
+ 6 10  3 18  5
+ 1 12 20 15 19
+
+ 3 15  0  2 22
+ 9 18 13 17  5
+19  8  7 25 23
+20 11 10 24  4
+14 21 16 12  6
+
+14 21 17 24  4
+10 16 15  9 19
+18  8 23 26 20
+22 11 13  6  5
+ 2  0 12  3  7";
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let wins = parse(SAMPLE_DATA).unwrap().play();
+        assert_eq!(part1(&wins), Some(4512));
+        assert_eq!(part2(&wins), Some(1924));
+        assert_eq!(wins.iter().map(|w| w.board).collect::<Vec<_>>(), vec![2, 0, 1]);
+    }
+}
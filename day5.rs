@@ -2,121 +2,73 @@
 
 use anyhow::{anyhow, Result};
 use std::collections::*;
+use std::time::Instant;
 
-type Num = i16;
-type Pair = (Num, Num);
+use aoc::XorShift;
+use aoc::raster::{Analytic, DenseGrid, OverlapCounter, RowSweep, Segment};
 
-fn parse_pair(input: &str) -> Pair {
-    let mut nums = input.split(',');
-    let v1 = nums.next().unwrap().parse::<Num>().unwrap();
-    let v2 = nums.next().unwrap().parse::<Num>().unwrap();
-    (v1, v2)
-}
+type Num = i32;
 
-fn parse(input: &str) -> Vec<(Pair, Pair)> {
-    let mut res = Vec::new();
-    for line in input.lines() {
-        let mut pairs = line.split(" -> ");
-        let p1 = parse_pair(pairs.next().unwrap());
-        let p2 = parse_pair(pairs.next().unwrap());
-        res.push((p1, p2));
-    }
-    res
+fn parse_pair(input: &str) -> Result<(Num, Num)> {
+    let (x, y) = input.trim().split_once(',').ok_or_else(|| anyhow!("expected 'x,y': {:?}", input))?;
+    Ok((x.parse()?, y.parse()?))
 }
 
-fn fillx(lnpts: &mut HashSet<Pair>, ovlp: &mut HashSet<Pair>, x: Num, y1: Num, y2: Num) {
-    if y1 > y2 {
-        fillx(lnpts, ovlp, x, y2, y1);
-        return;
-    }
-    for y in y1..=y2 {
-        let pt = (x, y);
-        if lnpts.contains(&pt) {
-            ovlp.insert(pt);
-        } else {
-            lnpts.insert(pt);
-        }
-    }
+fn parse(input: &str) -> Result<Vec<Segment<Num>>> {
+    input.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let (a, b) = line.split_once(" -> ").ok_or_else(|| anyhow!("expected 'a -> b': {:?}", line))?;
+            Ok(Segment::new(parse_pair(a)?, parse_pair(b)?))
+        })
+        .collect()
 }
 
-fn filly(lnpts: &mut HashSet<Pair>, ovlp: &mut HashSet<Pair>, y: Num, x1: Num, x2: Num) {
-    if x1 > x2 {
-        filly(lnpts, ovlp, y, x2, x1);
-        return;
-    }
-    for x in x1..=x2 {
-        let pt = (x, y);
-        if lnpts.contains(&pt) {
-            ovlp.insert(pt);
-        } else {
-            lnpts.insert(pt);
-        }
-    }
+/// The grid is fastest, but only fits segments spread over a small area.
+fn count(segments: &[Segment<Num>]) -> Result<u64> {
+    DenseGrid::default().count(segments)
+        .or_else(|| RowSweep.count(segments))
+        .ok_or_else(|| anyhow!("can't count overlaps of {} segments", segments.len()))
 }
 
-fn part1(input: &str) -> String {
-    let inp = parse(input);
-
-    let mut lnpts = HashSet::new();
-    let mut ovlp = HashSet::new();
-    for line in inp.iter() {
-        let ((x1, y1), (x2, y2)) = *line;
-
-        if x1 == x2 {
-            fillx(&mut lnpts, &mut ovlp, x1, y1, y2);
-        } else if y1 == y2 {
-            filly(&mut lnpts, &mut ovlp, y1, x1, x2);
-        } else {
-            continue;
-        }
-    }
-
-    format!("{}", ovlp.len())
+fn part1(segments: &[Segment<Num>]) -> Result<u64> {
+    let straight = segments.iter().copied().filter(|s| s.is_axis_aligned()).collect::<Vec<_>>();
+    count(&straight)
 }
 
-fn filldiag(lnpts: &mut HashSet<Pair>, ovlp: &mut HashSet<Pair>, pt1: Pair, pt2: Pair) {
-    let (x1, y1) = pt1;
-    let (x2, y2) = pt2;
-    if x1 > x2 {
-        filldiag(lnpts, ovlp, pt2, pt1);
-        return;
-    }
-
-    let run = x2 - x1;
-    let rise = y2 - y1;
-    let dy = rise / run;
-    assert!(dy == 1 || dy == -1);
-
-    let mut y = y1;
-    for x in x1..=x2 {
-        let pt = (x, y);
-        if lnpts.contains(&pt) {
-            ovlp.insert(pt);
-        } else {
-            lnpts.insert(pt);
-        }
-        y += dy;
-    }
+fn part2(segments: &[Segment<Num>]) -> Result<u64> {
+    count(segments)
 }
 
-fn part2(input: &str) -> String {
-    let inp = parse(input);
-
-    let mut lnpts = HashSet::new();
-    let mut ovlp = HashSet::new();
-    for line in inp.iter() {
-        let ((x1, y1), (x2, y2)) = *line;
-
-        if x1 == x2 {
-            fillx(&mut lnpts, &mut ovlp, x1, y1, y2);
-        } else if y1 == y2 {
-            filly(&mut lnpts, &mut ovlp, y1, x1, x2);
-        } else {
-            filldiag(&mut lnpts, &mut ovlp, (x1, y1), (x2, y2));
+fn bench_counters(input: &[Segment<Num>]) {
+    let counters: Vec<Box<dyn OverlapCounter<Num>>> = vec![
+        Box::new(DenseGrid::default()),
+        Box::new(RowSweep),
+        Box::new(Analytic),
+    ];
+
+    let mut rng = XorShift::new(0x2545f4914f6cdd1d);
+    let mut rand = |n: Num| rng.below(n as u64) as Num;
+    // Any slope, spread over a large area.
+    let sparse = (0..500).map(|_| {
+        Segment::new((rand(1_000_000), rand(1_000_000)), (rand(1_000_000), rand(1_000_000)))
+    })
+    .collect::<Vec<_>>();
+    // Long horizontal runs stacked on a few rows.
+    let rows = (0..500).map(|_| {
+        let (x, y) = (rand(1_000_000), rand(20));
+        Segment::new((x, y), (x + rand(1_000_000), y))
+    })
+    .collect::<Vec<_>>();
+
+    for (name, segments) in [("input", input), ("sparse", &sparse[..]), ("rows", &rows[..])].iter() {
+        println!("{} ({} segments):", name, segments.len());
+        for c in counters.iter() {
+            let start = Instant::now();
+            let count = c.count(segments);
+            println!("  {:>10}: {:?} in {:?}", c.name(), count, start.elapsed());
         }
     }
-
-    format!("{}", ovlp.len())
 }
 
 fn submit(puzzle: &mut aoc::Puzzle, part: aoc::Part, answ: &str) -> Result<()> {
@@ -127,20 +79,26 @@ fn submit(puzzle: &mut aoc::Puzzle, part: aoc::Part, answ: &str) -> Result<()> {
 fn main() -> Result<()> {
     let mut puzzle = aoc::Puzzle::new2021(5)?;
     let data = puzzle.get_data()?;
+    //let data = SAMPLE_DATA;
+    let segments = parse(data)?;
+
+    if std::env::args().any(|a| a == "--bench") {
+        bench_counters(&segments);
+        return Ok(());
+    }
+
+    let answ1 = part1(&segments)?;
+    dbg!(&answ1);
+    //submit(&mut puzzle, aoc::Part::One, &format!("{}", answ1))?;
 
-    //let answ1 = part1(data);
-    //submit(&mut puzzle, aoc::Part::One, &answ1)?;
-    let answ2 = part2(data);
-    submit(&mut puzzle, aoc::Part::Two, &answ2)?;
+    let answ2 = part2(&segments)?;
+    dbg!(&answ2);
+    submit(&mut puzzle, aoc::Part::Two, &format!("{}", answ2))?;
 
     Ok(())
 }
 
-#[cfg(test)]
-mod test {
-    #[test]
-    fn test() {
-        let answ = super::part1(
+const SAMPLE_DATA: &str =
 "0,9 -> 5,9
 8,0 -> 0,8
 9,4 -> 3,4
@@ -150,7 +108,22 @@ mod test {
 0,9 -> 2,9
 3,4 -> 1,4
 0,0 -> 8,8
-5,5 -> 8,2");
-        assert_eq!(answ, "5");
+5,5 -> 8,2";
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let segments = parse(SAMPLE_DATA).unwrap();
+        assert_eq!(part1(&segments).unwrap(), 5);
+        assert_eq!(part2(&segments).unwrap(), 12);
+        assert_eq!(RowSweep.count(&segments), Some(12));
+        assert_eq!(Analytic.count(&segments), Some(12));
+
+        // Too spread out for the grid.
+        let wide = parse("0,0 -> 100000,100000\n0,100000 -> 100000,0\n").unwrap();
+        assert_eq!(part2(&wide).unwrap(), 1);
     }
 }
@@ -7,6 +7,8 @@ use std::convert::TryFrom;
 use std::collections::*;
 use std::hash::Hash;
 
+use aoc::matrix::{Matrix, Mod, Scalar};
+
 fn day(wheel: &mut[u64; 9], day: usize) {
     // first, process 7-8 day countdowns:
     //wheel[(day + 6) % 7] += wheel[7];
@@ -68,6 +70,25 @@ fn part1(input: &str, days: usize) -> u64 {
     wheel.iter().sum()
 }
 
+/// Fish counts by timer, as a linear map: a fish at `t > 0` moves to `t - 1`, and one at 0 resets
+/// to 6 and spawns another at 8.
+fn lanternfish<T: Scalar>() -> Matrix<T> {
+    let mut transitions = (1..9).map(|t| (t, t - 1, T::one())).collect::<Vec<_>>();
+    transitions.push((0, 6, T::one()));
+    transitions.push((0, 8, T::one()));
+    Matrix::from_transitions(9, transitions).unwrap()
+}
+
+/// Like `part1`, but jumping straight to `days` by matrix powers.  `None` if `T` overflows.
+fn part_matrix<T: Scalar + From<u64>>(input: &str, days: u64) -> Option<T> {
+    let mut counts = [0u64; 9];
+    for fish in input.trim_end().split(',').map(|w| w.parse::<usize>().unwrap()) {
+        counts[fish] += 1;
+    }
+    let counts = counts.iter().map(|&c| T::from(c)).collect::<Vec<_>>();
+    lanternfish::<T>().advance(&counts, days)?.into_iter().try_fold(T::zero(), |acc, c| acc.add(c))
+}
+
 #[cfg(test)]
 mod test2 {
     use super::*;
@@ -90,6 +111,11 @@ mod test2 {
     #[test]
     fn test_part2() {
         assert_eq!(part1("3,4,3,1,2", 256), 26984457539);
+        assert_eq!(part_matrix::<u128>("3,4,3,1,2", 256), Some(26984457539));
+        assert_eq!(part_matrix::<u128>("3,4,3,1,2", 18), Some(26));
+        assert_eq!(part_matrix::<u128>("3,4,3,1,2", 2000), None);
+        let small = part_matrix::<Mod<1_000_000_007>>("3,4,3,1,2", 256).unwrap();
+        assert_eq!(small, Mod::from(26984457539 % 1_000_000_007));
     }
 }
 
@@ -104,6 +130,9 @@ fn main() -> Result<()> {
 
     let answ2 = part1(data, 256);
     dbg!(&answ2);
+    assert_eq!(part_matrix::<u128>(data, 256), Some(answ2 as u128));
+    let far = part_matrix::<Mod<1_000_000_007>>(data, 1_000_000_000_000_000_000).unwrap();
+    println!("after 10^18 days: {}", far);
     puzzle.submit_answer(aoc::Part::Two, &format!("{}", answ2))?;
 
     Ok(())
@@ -0,0 +1,78 @@
+#![allow(dead_code, unused_imports, unused_parens, unused_variables)]
+
+use anyhow::{anyhow, Result};
+
+use aoc::{alu, coproc};
+use aoc::debugger::{Debuggee, Debugger};
+
+fn usage() -> anyhow::Error {
+    anyhow!("usage: debug <alu|coproc> [program-file] [--input <digits>] [--set <reg>=<value>]...")
+}
+
+/// Apply `--set` arguments to the initial register file.
+fn apply_sets<M: Debuggee>(machine: &M, regs: &mut [i64], sets: &[(String, i64)]) -> Result<()> {
+    for (reg, val) in sets.iter() {
+        let r = machine.reg_names().iter().position(|n| n == reg)
+            .ok_or_else(|| anyhow!("No register {:?}", reg))?;
+        regs[r] = *val;
+    }
+    Ok(())
+}
+
+fn repl<M: Debuggee>(machine: M) -> Result<()> {
+    let mut dbg = Debugger::new(machine);
+    let stdin = std::io::stdin();
+    dbg.repl(stdin.lock(), std::io::stdout())
+}
+
+fn main() -> Result<()> {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let mut isa = None;
+    let mut path = None;
+    let mut input = None;
+    let mut sets = Vec::new();
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--input" => input = Some(iter.next().ok_or_else(usage)?.clone()),
+            "--set" => {
+                let kv = iter.next().ok_or_else(usage)?;
+                let (k, v) = kv.split_once('=').ok_or_else(usage)?;
+                sets.push((k.to_owned(), v.parse::<i64>()?));
+            }
+            _ if isa.is_none() => isa = Some(arg.clone()),
+            _ if path.is_none() => path = Some(arg.clone()),
+            _ => return Err(usage()),
+        }
+    }
+
+    let isa = isa.ok_or_else(usage)?;
+    let (year, day) = match isa.as_str() {
+        "alu" => (2021, 24),
+        "coproc" => (2017, 23),
+        _ => return Err(usage()),
+    };
+    let text = match path {
+        Some(path) => std::fs::read_to_string(path)?,
+        None => aoc::Puzzle::new(year, day)?.get_data()?.to_owned(),
+    };
+
+    if isa == "alu" {
+        let mut machine = alu::Machine::new(&alu::parse(&text));
+        let digits = input.unwrap_or_default().chars()
+            .map(|c| c.to_digit(10).map(|d| d as i64).ok_or_else(|| anyhow!("Bad digit {:?}", c)))
+            .collect::<Result<Vec<_>>>()?;
+        machine.inp = digits.into_iter().rev().collect();
+        let mut regs = machine.regs;
+        apply_sets(&machine, &mut regs, &sets)?;
+        machine.regs = regs;
+        repl(machine)
+    } else {
+        let mut machine = coproc::Machine::new(&coproc::parse(&text));
+        let mut regs = machine.regs;
+        apply_sets(&machine, &mut regs, &sets)?;
+        machine.regs = regs;
+        repl(machine)
+    }
+}
@@ -0,0 +1,231 @@
+//! Bit-column statistics of a binary diagnostic report (2021 day 3).
+//!
+//! Each line is a fixed-width number, column 0 being the most significant bit, the order
+//! `BitCursor::parse_be` reads them in.  Rating filters keep a contiguous range of the sorted
+//! numbers, narrowing it one column at a time.
+
+use bitvec::prelude::*;
+use std::fmt;
+
+use crate::{BitCursor, BitWriter};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// Character on line `line` (from 1) that's neither digit.
+    BadChar { line: usize, found: char },
+    /// Line `line` (from 1) isn't as wide as the first.
+    Ragged { line: usize },
+    /// Widths must be from 1 to 64.
+    Width(usize),
+    /// Value number `index` (from 0) needs more bits than the width.
+    TooWide { index: usize, value: u64 },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::BadChar { line, found } => write!(f, "line {}: {:?} isn't a digit", line, found),
+            Error::Ragged { line } => write!(f, "line {}: width differs from the first line", line),
+            Error::Width(w) => write!(f, "width {} not in 1..=64", w),
+            Error::TooWide { index, value } => write!(f, "value {} ({}) doesn't fit the width", index, value),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Common {
+    Most,
+    Least,
+}
+
+/// Which bit to pick from a column's counts.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Criterion {
+    pub common: Common,
+    /// The bit to pick when both are equally common.
+    pub on_tie: bool,
+}
+
+impl Criterion {
+    /// Gamma rate, and oxygen generator rating.
+    pub const MOST: Self = Self { common: Common::Most, on_tie: true };
+    /// Epsilon rate, and CO2 scrubber rating.
+    pub const LEAST: Self = Self { common: Common::Least, on_tie: false };
+
+    /// The bit picked given `ones` and `zeros` set in a column.
+    pub fn pick(&self, ones: usize, zeros: usize) -> bool {
+        match (ones.cmp(&zeros), self.common) {
+            (std::cmp::Ordering::Equal, _) => self.on_tie,
+            (o, Common::Most) => o == std::cmp::Ordering::Greater,
+            (o, Common::Least) => o == std::cmp::Ordering::Less,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Report {
+    width: usize,
+    values: Vec<u64>,
+    /// `values`, sorted, for the rating filters.
+    sorted: Vec<u64>,
+}
+
+impl Report {
+    pub fn new(width: usize, values: Vec<u64>) -> Result<Self, Error> {
+        if width == 0 || width > 64 {
+            return Err(Error::Width(width));
+        }
+        if let Some((index, &value)) = values.iter().enumerate().find(|(_, &v)| width < 64 && v >> width != 0) {
+            return Err(Error::TooWide { index, value });
+        }
+        let mut sorted = values.clone();
+        sorted.sort_unstable();
+        Ok(Self { width, values, sorted })
+    }
+
+    /// One number per line, written with `zero` and `one` for the digits.
+    pub fn parse(input: &str, zero: char, one: char) -> Result<Self, Error> {
+        let mut width = None;
+        let mut values = Vec::new();
+        for (i, line) in input.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+            let line = line.trim();
+            let w = line.chars().count();
+            if *width.get_or_insert(w) != w {
+                return Err(Error::Ragged { line: i + 1 });
+            }
+            if w > 64 {
+                return Err(Error::Width(w));
+            }
+            let v = line.chars().try_fold(0u64, |acc, c| match c {
+                c if c == zero => Ok(acc << 1),
+                c if c == one => Ok(acc << 1 | 1),
+                c => Err(Error::BadChar { line: i + 1, found: c }),
+            })?;
+            values.push(v);
+        }
+        Self::new(width.unwrap_or(1), values)
+    }
+
+    /// Consecutive `width`-bit numbers, ignoring any partial one at the end.
+    pub fn from_bits(bits: &BitSlice<Msb0, u8>, width: usize) -> Result<Self, Error> {
+        if width == 0 || width > 64 {
+            return Err(Error::Width(width));
+        }
+        let mut curs = BitCursor::new(bits);
+        let mut values = Vec::new();
+        while curs.remaining() >= width {
+            values.push(curs.parse_be::<u64>(width));
+        }
+        Self::new(width, values)
+    }
+
+    pub fn to_bits(&self) -> BitVec<Msb0, u8> {
+        let mut out = BitWriter::<Msb0>::new();
+        for &v in self.values.iter() {
+            out.write_be(v, self.width).expect("width checked in new");
+        }
+        out.into_bitvec()
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn values(&self) -> &[u64] {
+        &self.values
+    }
+
+    fn bit(&self, col: usize) -> u64 {
+        1 << (self.width - 1 - col)
+    }
+
+    /// Ones in each column.
+    pub fn column_counts(&self) -> Vec<usize> {
+        let mut counts = vec![0; self.width];
+        for &v in self.values.iter() {
+            for (col, c) in counts.iter_mut().enumerate() {
+                *c += (v & self.bit(col) != 0) as usize;
+            }
+        }
+        counts
+    }
+
+    /// Each column's picked bit, across all numbers: the gamma rate for `Criterion::MOST`, and
+    /// epsilon for `Criterion::LEAST`.  0 for an empty report.
+    pub fn rate(&self, criterion: Criterion) -> u64 {
+        let n = self.values.len();
+        if n == 0 {
+            return 0;
+        }
+        self.column_counts().iter().enumerate().fold(0, |acc, (col, &ones)| {
+            if criterion.pick(ones, n - ones) { acc | self.bit(col) } else { acc }
+        })
+    }
+
+    /// Keep the numbers with each column's picked bit, among those left, until one remains.  A bit
+    /// none of them has is never picked, even as the least common.  If several equal numbers
+    /// survive every column, that's the rating.  `None` if the report is empty.
+    pub fn rating(&self, criterion: Criterion) -> Option<u64> {
+        let (mut lo, mut hi) = (0, self.sorted.len());
+        for col in 0..self.width {
+            if hi - lo <= 1 {
+                break;
+            }
+            // The range shares every column so far, so it's sorted by this one.
+            let bit = self.bit(col);
+            let split = lo + self.sorted[lo..hi].partition_point(|&v| v & bit == 0);
+            if split == lo || split == hi {
+                continue;
+            }
+            if criterion.pick(hi - split, split - lo) {
+                lo = split;
+            } else {
+                hi = split;
+            }
+        }
+        self.sorted.get(lo).copied().filter(|_| lo < hi)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "00100\n11110\n10110\n10111\n10101\n01111\n00111\n11100\n10000\n11001\n00010\n01010";
+
+    #[test]
+    fn ratings() {
+        let r = Report::parse(SAMPLE, '0', '1').unwrap();
+        assert_eq!(r.column_counts(), vec![7, 5, 8, 7, 5]);
+        assert_eq!((r.rate(Criterion::MOST), r.rate(Criterion::LEAST)), (22, 9));
+        assert_eq!((r.rating(Criterion::MOST), r.rating(Criterion::LEAST)), (Some(23), Some(10)));
+
+        let flipped = Report::parse(&SAMPLE.replace('0', ".").replace('1', "#"), '.', '#').unwrap();
+        assert_eq!(flipped, r);
+        assert_eq!(Report::from_bits(&r.to_bits(), 5).unwrap(), r);
+
+        // Every column a tie.
+        let r = Report::parse("00\n01\n10\n11", '0', '1').unwrap();
+        assert_eq!(r.rating(Criterion::MOST), Some(3));
+        assert_eq!(r.rating(Criterion { common: Common::Most, on_tie: false }), Some(0));
+        assert_eq!(r.rating(Criterion::LEAST), Some(0));
+        assert_eq!(r.rating(Criterion { common: Common::Least, on_tie: true }), Some(3));
+        assert_eq!(Report::parse("11\n11\n01", '0', '1').unwrap().rating(Criterion::LEAST), Some(1));
+        // Nobody has a 0 in the first column, so the least common bit there is 1.
+        assert_eq!(Report::parse("11\n11\n10", '0', '1').unwrap().rating(Criterion::LEAST), Some(2));
+        assert_eq!(Report::parse("", '0', '1').unwrap().rating(Criterion::MOST), None);
+
+        // Every number has a 1 in the first column, so epsilon has a 0 there.
+        let r = Report::parse("110\n101\n111", '0', '1').unwrap();
+        assert_eq!((r.rate(Criterion::MOST), r.rate(Criterion::LEAST)), (0b111, 0b000));
+        let r = Report::parse("100\n101\n110", '0', '1').unwrap();
+        assert_eq!((r.rate(Criterion::MOST), r.rate(Criterion::LEAST)), (0b100, 0b011));
+
+        assert_eq!(Report::parse("01\n012", '0', '1'), Err(Error::Ragged { line: 2 }));
+        assert_eq!(Report::parse("01\n0x", '0', '1'), Err(Error::BadChar { line: 2, found: 'x' }));
+        assert_eq!(Report::new(2, vec![3, 4]), Err(Error::TooWide { index: 1, value: 4 }));
+        assert_eq!(Report::new(64, vec![u64::MAX]).unwrap().rate(Criterion::MOST), u64::MAX);
+    }
+}
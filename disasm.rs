@@ -0,0 +1,48 @@
+#![allow(dead_code, unused_imports, unused_parens, unused_variables)]
+
+use anyhow::{anyhow, Result};
+
+use aoc::{alu, coproc};
+use aoc::codegen;
+use aoc::decompile::Decompiler;
+
+fn usage() -> anyhow::Error {
+    anyhow!("usage: disasm <alu|coproc> [program-file] [--dot | --rust]")
+}
+
+fn main() -> Result<()> {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let dot = args.iter().any(|a| a == "--dot");
+    let rust = args.iter().any(|a| a == "--rust");
+    let mut args = args.iter().filter(|a| !a.starts_with("--"));
+
+    let isa = args.next().ok_or_else(usage)?;
+    // Default to this user's cached puzzle input.
+    let (year, day) = match isa.as_str() {
+        "alu" => (2021, 24),
+        "coproc" => (2017, 23),
+        _ => return Err(usage()),
+    };
+    let text = match args.next() {
+        Some(path) => std::fs::read_to_string(path)?,
+        None => aoc::Puzzle::new(year, day)?.get_data()?.to_owned(),
+    };
+
+    let lifted = match isa.as_str() {
+        "alu" => alu::lift(&alu::parse(&text)),
+        _ => coproc::lift(&coproc::parse(&text)),
+    };
+    if rust {
+        let opts = codegen::Options { name: "run".to_owned(), const_fn: false, count_muls: isa == "coproc" };
+        print!("{}", codegen::generate(&lifted, &opts));
+        return Ok(());
+    }
+    let decompiler = Decompiler::new(&lifted);
+
+    if dot {
+        print!("{}", decompiler.dot());
+    } else {
+        print!("{}", decompiler.pseudocode());
+    }
+    Ok(())
+}
@@ -0,0 +1,80 @@
+//! Counting outcomes of two-player turn-based games of chance, where each move happens in some
+//! number of ways (2021 day 21).
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+pub trait Game {
+    /// Everything that decides how the rest of the game can go, including whose turn it is.
+    type State: Clone + Eq + Hash;
+
+    /// The winner (0 or 1) if the game is over.
+    fn winner(&self, state: &Self::State) -> Option<usize>;
+
+    /// States after one more move, each with the number of ways it can happen.
+    fn moves(&self, state: &Self::State) -> Vec<(Self::State, u128)>;
+}
+
+/// Number of ways each player wins from `start`, or `None` on overflow.
+///
+/// Results are memoized per state, so games whose paths keep meeting again are cheap however
+/// many ways there are to play them.  Every path must end.
+pub fn win_counts<G: Game>(game: &G, start: &G::State) -> Option<[u128; 2]> {
+    fn count<G: Game>(game: &G, state: &G::State, memo: &mut HashMap<G::State, Option<[u128; 2]>>) -> Option<[u128; 2]> {
+        if let Some(wins) = memo.get(state) {
+            return *wins;
+        }
+        let wins = match game.winner(state) {
+            Some(w) => {
+                let mut wins = [0; 2];
+                wins[w] = 1;
+                Some(wins)
+            }
+            None => game.moves(state).iter().try_fold([0u128; 2], |acc, (next, ways)| {
+                let sub = count(game, next, memo)?;
+                Some([
+                    acc[0].checked_add(sub[0].checked_mul(*ways)?)?,
+                    acc[1].checked_add(sub[1].checked_mul(*ways)?)?,
+                ])
+            }),
+        };
+        memo.insert(state.clone(), wins);
+        wins
+    }
+    count(game, start, &mut HashMap::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Players alternately add 1 or 2 (one way each) to a shared total; whoever reaches `target`
+    /// first wins.
+    struct Race {
+        target: u32,
+    }
+
+    impl Game for Race {
+        type State = (u32, usize);
+
+        fn winner(&self, &(total, player): &(u32, usize)) -> Option<usize> {
+            // The player who just moved.
+            if total >= self.target { Some(1 - player) } else { None }
+        }
+
+        fn moves(&self, &(total, player): &(u32, usize)) -> Vec<((u32, usize), u128)> {
+            vec![((total + 1, 1 - player), 1), ((total + 2, 1 - player), 1)]
+        }
+    }
+
+    #[test]
+    fn race() {
+        assert_eq!(win_counts(&Race { target: 1 }, &(0, 0)), Some([2, 0]));
+        // Player 0 wins with 2; after 1, player 1 wins either way.
+        assert_eq!(win_counts(&Race { target: 2 }, &(0, 0)), Some([1, 2]));
+        // Every game is a run of 1s and 2s reaching 60, so there are fib(62) of them.
+        let wins = win_counts(&Race { target: 60 }, &(0, 0)).unwrap();
+        assert_eq!(wins[0] + wins[1], 4052739537881);
+        assert_eq!(win_counts(&Race { target: 400 }, &(0, 0)), None);
+    }
+}
@@ -6,6 +6,31 @@ use std::io::ErrorKind;
 mod algo;
 pub use algo::*;
 
+pub use aoc_vm::{alu, codegen, coproc, debugger, decompile};
+
+pub mod bingo;
+pub mod bits;
+pub mod brackets;
+pub mod caves;
+pub mod cuboid;
+pub mod diagnostic;
+pub mod game;
+pub mod matrix;
+pub mod navigation;
+pub mod polymer;
+pub mod raster;
+pub mod registration;
+pub mod segments;
+pub mod snailfish;
+pub mod volume;
+
+/// Cached VM puzzle inputs compiled to Rust by `build.rs`, or `None` if they weren't downloaded
+/// at build time.
+#[allow(clippy::all)]
+pub mod aot {
+    include!(concat!(env!("OUT_DIR"), "/aot.rs"));
+}
+
 // Work around Rust's inability to concatenate / format const strings.
 macro_rules! YEAR_URI {
     () => { "https://adventofcode.com/{year}" };
@@ -368,18 +393,73 @@ pub type ByteString = Vec<u8>;
 pub type byte = u8;
 
 use bitvec::field::BitField;
-use bitvec::order::{BitOrder, Msb0};
+use bitvec::mem::BitMemory;
+use bitvec::order::{BitOrder, Lsb0, Msb0};
 use bitvec::slice::BitSlice;
+use bitvec::vec::BitVec;
+
+/// A `BitCursor` read ran off the end of its input.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct OutOfBits {
+    /// Cursor position at the failed read.
+    pub pos: usize,
+    pub wanted: usize,
+    pub remaining: usize,
+}
+
+impl std::fmt::Display for OutOfBits {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "bit {}: wanted {} bits, {} remaining", self.pos, self.wanted, self.remaining)
+    }
+}
+
+impl std::error::Error for OutOfBits {}
+
+/// A fallible fixed-width read or write that couldn't be done.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum BitError {
+    OutOfBits(OutOfBits),
+    /// `bits` isn't from 1 to `max`, the width of the value's type.
+    Width { bits: usize, max: usize },
+}
+
+impl std::fmt::Display for BitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            BitError::OutOfBits(e) => e.fmt(f),
+            BitError::Width { bits, max } => write!(f, "can't move {} bits of a {}-bit value", bits, max),
+        }
+    }
+}
+
+impl std::error::Error for BitError {}
+
+impl From<OutOfBits> for BitError {
+    fn from(e: OutOfBits) -> Self {
+        BitError::OutOfBits(e)
+    }
+}
+
+/// `bitvec` panics loading or storing zero bits, or more than `T` holds.
+fn check_width<T: BitMemory>(bits: usize) -> Result<(), BitError> {
+    let max = std::mem::size_of::<T>() * 8;
+    if bits == 0 || bits > max {
+        return Err(BitError::Width { bits, max });
+    }
+    Ok(())
+}
 
 /// Cursor type to support cleaner parsing.
 pub struct BitCursor<'a, E: BitOrder> {
     input: &'a BitSlice<E, u8>,
+    pos: usize,
 }
 
 impl<'a, E: BitOrder> BitCursor<'a, E> {
     pub fn new(input: &'a BitSlice<E, u8>) -> Self {
         Self {
             input,
+            pos: 0,
         }
     }
 
@@ -388,22 +468,234 @@ impl<'a, E: BitOrder> BitCursor<'a, E> {
     pub fn as_slice(&self) -> &'a BitSlice<E, u8> {
         self.input
     }
+
+    /// Bits consumed so far.  Sub-cursors continue counting from their parent's position.
+    #[inline]
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    #[inline]
+    pub fn remaining(&self) -> usize {
+        self.input.len()
+    }
+
+    fn check(&self, bits: usize) -> Result<(), OutOfBits> {
+        if bits > self.input.len() {
+            return Err(OutOfBits { pos: self.pos, wanted: bits, remaining: self.input.len() });
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn advance(&mut self, bits: usize) {
+        self.input = &self.input[bits..];
+        self.pos += bits;
+    }
+
+    pub fn skip(&mut self, bits: usize) -> Result<(), OutOfBits> {
+        self.check(bits)?;
+        self.advance(bits);
+        Ok(())
+    }
+
+    /// Split off a cursor over the next `bits` bits, e.g. for a length-prefixed field, and skip
+    /// past them.
+    pub fn sub_cursor(&mut self, bits: usize) -> Result<Self, OutOfBits> {
+        self.check(bits)?;
+        let sub = Self {
+            input: &self.input[..bits],
+            pos: self.pos,
+        };
+        self.advance(bits);
+        Ok(sub)
+    }
 }
 
 impl<'a> BitCursor<'a, Msb0> {
     /// Parse the first `bits` bits from this iterator, consuming them.  `T` should be as wide or
     /// wider than `bits`, probably.
     #[inline]
-    pub fn parse_be<T: bitvec::mem::BitMemory>(&mut self, bits: usize) -> T {
+    pub fn parse_be<T: BitMemory>(&mut self, bits: usize) -> T {
         let res = self.peek_be::<T>(bits);
-        self.input = &self.input[bits..];
+        self.advance(bits);
         res
     }
 
     /// Parse the first `bits` bits from this iterator.  `T` should be as wide or wider than
     /// `bits`, probably.
     #[inline]
-    fn peek_be<T: bitvec::mem::BitMemory>(&self, bits: usize) -> T {
+    pub fn peek_be<T: BitMemory>(&self, bits: usize) -> T {
         self.input[..bits].load_be::<T>()
     }
+
+    pub fn try_parse_be<T: BitMemory>(&mut self, bits: usize) -> Result<T, BitError> {
+        check_width::<T>(bits)?;
+        self.check(bits)?;
+        Ok(self.parse_be(bits))
+    }
+
+    pub fn try_peek_be<T: BitMemory>(&self, bits: usize) -> Result<T, BitError> {
+        check_width::<T>(bits)?;
+        self.check(bits)?;
+        Ok(self.peek_be(bits))
+    }
+}
+
+impl<'a> BitCursor<'a, Lsb0> {
+    /// Parse the first `bits` bits from this iterator, consuming them; the first bit is the least
+    /// significant.
+    #[inline]
+    pub fn parse_le<T: BitMemory>(&mut self, bits: usize) -> T {
+        let res = self.peek_le::<T>(bits);
+        self.advance(bits);
+        res
+    }
+
+    #[inline]
+    pub fn peek_le<T: BitMemory>(&self, bits: usize) -> T {
+        self.input[..bits].load_le::<T>()
+    }
+
+    pub fn try_parse_le<T: BitMemory>(&mut self, bits: usize) -> Result<T, BitError> {
+        check_width::<T>(bits)?;
+        self.check(bits)?;
+        Ok(self.parse_le(bits))
+    }
+
+    pub fn try_peek_le<T: BitMemory>(&self, bits: usize) -> Result<T, BitError> {
+        check_width::<T>(bits)?;
+        self.check(bits)?;
+        Ok(self.peek_le(bits))
+    }
+}
+
+/// The writing counterpart of `BitCursor`, e.g. for building test vectors.
+pub struct BitWriter<E: BitOrder> {
+    out: BitVec<E, u8>,
+}
+
+impl<E: BitOrder> BitWriter<E> {
+    pub fn new() -> Self {
+        Self {
+            out: BitVec::new(),
+        }
+    }
+
+    /// Bits written so far.
+    pub fn position(&self) -> usize {
+        self.out.len()
+    }
+
+    pub fn write_bits(&mut self, bits: &BitSlice<E, u8>) {
+        self.out.extend_from_bitslice(bits);
+    }
+
+    pub fn as_slice(&self) -> &BitSlice<E, u8> {
+        &self.out
+    }
+
+    pub fn into_bitvec(self) -> BitVec<E, u8> {
+        self.out
+    }
+
+    /// Zero-padded to a whole number of bytes.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.out.into_vec()
+    }
+}
+
+impl<E: BitOrder> Default for BitWriter<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BitWriter<Msb0> {
+    /// Append the low `bits` bits of `val`, most significant first.
+    pub fn write_be<T: BitMemory>(&mut self, val: T, bits: usize) -> Result<(), BitError> {
+        check_width::<T>(bits)?;
+        let start = self.out.len();
+        self.out.resize(start + bits, false);
+        self.out[start..].store_be(val);
+        Ok(())
+    }
+}
+
+impl BitWriter<Lsb0> {
+    /// Append the low `bits` bits of `val`, least significant first.
+    pub fn write_le<T: BitMemory>(&mut self, val: T, bits: usize) -> Result<(), BitError> {
+        check_width::<T>(bits)?;
+        let start = self.out.len();
+        self.out.resize(start + bits, false);
+        self.out[start..].store_le(val);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitvec::view::BitView;
+
+    #[test]
+    fn bit_cursor_roundtrip() {
+        let mut w = BitWriter::<Msb0>::new();
+        w.write_be(0b101u8, 3).unwrap();
+        w.write_be(0x1234u16, 16).unwrap();
+        w.write_be(1u8, 1).unwrap();
+        assert_eq!(w.position(), 20);
+        let bytes = w.into_bytes();
+        assert_eq!(bytes, [0xa2, 0x46, 0x90]);
+
+        let mut curs = BitCursor::new(bytes.view_bits::<Msb0>());
+        assert_eq!(curs.try_peek_be::<u8>(3), Ok(0b101));
+        curs.skip(3).unwrap();
+        let mut sub = curs.sub_cursor(16).unwrap();
+        assert_eq!((curs.position(), curs.remaining()), (19, 5));
+        assert_eq!(sub.try_parse_be::<u8>(8), Ok(0x12));
+        assert_eq!(sub.try_parse_be::<u16>(9), Err(BitError::OutOfBits(OutOfBits { pos: 11, wanted: 9, remaining: 8 })));
+        assert_eq!(curs.try_parse_be::<u8>(1), Ok(1));
+
+        let mut w = BitWriter::<Lsb0>::new();
+        w.write_le(0x2d_u8, 6).unwrap();
+        w.write_le(0x3_u8, 2).unwrap();
+        let bytes = w.into_bytes();
+        assert_eq!(bytes, [0xed]);
+        let mut curs = BitCursor::new(bytes.view_bits::<Lsb0>());
+        assert_eq!(curs.try_parse_le::<u8>(6), Ok(0x2d));
+        assert_eq!(curs.try_peek_le::<u8>(2), Ok(0x3));
+        assert!(curs.try_peek_le::<u8>(3).is_err());
+    }
+
+    #[test]
+    fn bit_widths() {
+        let bytes = [0xffu8; 4];
+        fn width<T>(bits: usize, max: usize) -> Result<T, BitError> {
+            Err(BitError::Width { bits, max })
+        }
+
+        let mut curs = BitCursor::new(bytes.view_bits::<Msb0>());
+        assert_eq!(curs.try_parse_be::<u8>(0), width(0, 8));
+        assert_eq!(curs.try_parse_be::<u8>(9), width(9, 8));
+        assert_eq!(curs.try_peek_be::<u16>(0), width(0, 16));
+        assert_eq!(curs.try_peek_be::<u16>(17), width(17, 16));
+        assert_eq!(curs.position(), 0);
+        assert_eq!(curs.try_parse_be::<u32>(32), Ok(!0));
+
+        let mut curs = BitCursor::new(bytes.view_bits::<Lsb0>());
+        assert_eq!(curs.try_parse_le::<u8>(0), width(0, 8));
+        assert_eq!(curs.try_parse_le::<u8>(9), width(9, 8));
+        assert_eq!(curs.try_peek_le::<u16>(0), width(0, 16));
+        assert_eq!(curs.try_peek_le::<u16>(17), width(17, 16));
+        assert_eq!(curs.try_parse_le::<u32>(32), Ok(!0));
+
+        let mut w = BitWriter::<Msb0>::new();
+        assert_eq!(w.write_be(1u8, 0), width(0, 8));
+        assert_eq!(w.write_be(1u8, 9), width(9, 8));
+        let mut w2 = BitWriter::<Lsb0>::new();
+        assert_eq!(w2.write_le(1u16, 0), width(0, 16));
+        assert_eq!(w2.write_le(1u16, 17), width(17, 16));
+        assert_eq!((w.position(), w2.position()), (0, 0));
+    }
 }
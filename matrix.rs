@@ -0,0 +1,185 @@
+//! Square matrices for advancing linear recurrences many steps at once, by exponentiation by
+//! squaring (2021 days 6 and 14).
+
+use std::fmt;
+use std::ops::{Index, IndexMut};
+
+/// Entries of a `Matrix`.  Arithmetic returns `None` on overflow.
+pub trait Scalar: Copy + Eq + fmt::Debug {
+    fn zero() -> Self;
+    fn one() -> Self;
+    fn add(self, other: Self) -> Option<Self>;
+    fn mul(self, other: Self) -> Option<Self>;
+}
+
+impl Scalar for u128 {
+    fn zero() -> Self {
+        0
+    }
+
+    fn one() -> Self {
+        1
+    }
+
+    fn add(self, other: Self) -> Option<Self> {
+        self.checked_add(other)
+    }
+
+    fn mul(self, other: Self) -> Option<Self> {
+        self.checked_mul(other)
+    }
+}
+
+/// Integers modulo `M`, which never overflow.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct Mod<const M: u64>(pub u64);
+
+impl<const M: u64> From<u64> for Mod<M> {
+    fn from(v: u64) -> Self {
+        Mod(v % M)
+    }
+}
+
+impl<const M: u64> fmt::Display for Mod<M> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (mod {})", self.0, M)
+    }
+}
+
+impl<const M: u64> Scalar for Mod<M> {
+    fn zero() -> Self {
+        Mod(0)
+    }
+
+    fn one() -> Self {
+        Mod(1 % M)
+    }
+
+    fn add(self, other: Self) -> Option<Self> {
+        Some(Mod(((self.0 as u128 + other.0 as u128) % M as u128) as u64))
+    }
+
+    fn mul(self, other: Self) -> Option<Self> {
+        Some(Mod((self.0 as u128 * other.0 as u128 % M as u128) as u64))
+    }
+}
+
+/// `n` by `n` matrix, row-major.  Used as a linear map on column vectors: entry `(i, j)` is how
+/// much of component `j` feeds into component `i` each step.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Matrix<T> {
+    n: usize,
+    data: Vec<T>,
+}
+
+impl<T> Index<(usize, usize)> for Matrix<T> {
+    type Output = T;
+
+    fn index(&self, (i, j): (usize, usize)) -> &T {
+        &self.data[i * self.n + j]
+    }
+}
+
+impl<T> IndexMut<(usize, usize)> for Matrix<T> {
+    fn index_mut(&mut self, (i, j): (usize, usize)) -> &mut T {
+        &mut self.data[i * self.n + j]
+    }
+}
+
+impl<T: Scalar> Matrix<T> {
+    pub fn zeros(n: usize) -> Self {
+        Self { n, data: vec![T::zero(); n * n] }
+    }
+
+    pub fn identity(n: usize) -> Self {
+        let mut m = Self::zeros(n);
+        for i in 0..n {
+            m[(i, i)] = T::one();
+        }
+        m
+    }
+
+    /// Transition matrix of a system where, each step, every unit of component `from` turns into
+    /// `count` units of component `to`, for each `(from, to, count)`.  Repeats add up.
+    pub fn from_transitions<I>(n: usize, transitions: I) -> Option<Self>
+        where I: IntoIterator<Item = (usize, usize, T)>
+    {
+        let mut m = Self::zeros(n);
+        for (from, to, count) in transitions {
+            m[(to, from)] = m[(to, from)].add(count)?;
+        }
+        Some(m)
+    }
+
+    pub fn size(&self) -> usize {
+        self.n
+    }
+
+    pub fn mul(&self, other: &Self) -> Option<Self> {
+        assert_eq!(self.n, other.n);
+        let n = self.n;
+        let mut res = Self::zeros(n);
+        for i in 0..n {
+            for k in 0..n {
+                let a = self[(i, k)];
+                if a == T::zero() {
+                    continue;
+                }
+                for j in 0..n {
+                    res[(i, j)] = res[(i, j)].add(a.mul(other[(k, j)])?)?;
+                }
+            }
+        }
+        Some(res)
+    }
+
+    pub fn apply(&self, v: &[T]) -> Option<Vec<T>> {
+        assert_eq!(self.n, v.len());
+        (0..self.n).map(|i| {
+            (0..self.n).try_fold(T::zero(), |acc, j| acc.add(self[(i, j)].mul(v[j])?))
+        })
+        .collect()
+    }
+
+    /// `self` to the power `e`.  `None` if it, or a power of two below `e` on the way, overflows.
+    pub fn pow(&self, mut e: u64) -> Option<Self> {
+        let mut res = Self::identity(self.n);
+        let mut sq = self.clone();
+        while e > 0 {
+            if e & 1 == 1 {
+                res = res.mul(&sq)?;
+            }
+            e >>= 1;
+            if e > 0 {
+                sq = sq.mul(&sq)?;
+            }
+        }
+        Some(res)
+    }
+
+    /// The state `steps` steps after `v`, in `O(n^3 log steps)`.
+    pub fn advance(&self, v: &[T], steps: u64) -> Option<Vec<T>> {
+        self.pow(steps)?.apply(v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fibonacci() {
+        // (a, b) -> (b, a + b)
+        let m = Matrix::<u128>::from_transitions(2, vec![(1, 0, 1), (0, 1, 1), (1, 1, 1)]).unwrap();
+        assert_eq!(m.advance(&[0, 1], 10), Some(vec![55, 89]));
+        assert_eq!(m.advance(&[0, 1], 0), Some(vec![0, 1]));
+        assert_eq!(m.advance(&[0, 1], 185), Some(vec![205697230343233228174223751303346572685, 332825110087067562321196029789634457848]));
+        assert_eq!(m.advance(&[0, 1], 190), None);
+
+        let m = Matrix::<Mod<1_000_000_007>>::from_transitions(2, vec![(1, 0, Mod(1)), (0, 1, Mod(1)), (1, 1, Mod(1))]).unwrap();
+        let fib = m.advance(&[Mod(0), Mod(1)], 185).unwrap();
+        assert_eq!(fib[1], Mod::from((332825110087067562321196029789634457848u128 % 1_000_000_007) as u64));
+        // Pisano period of 10^9 + 7.
+        assert_eq!(m.advance(&[Mod(0), Mod(1)], 2_000_000_016).unwrap(), vec![Mod(0), Mod(1)]);
+    }
+}
@@ -0,0 +1,234 @@
+//! Steering the submarine with `forward`/`down`/`up` commands (2021 day 2).
+//!
+//! What a command does depends on the `Navigator`: part 1 moves directly, part 2 steers by aim.
+//! Either way the result is the whole trajectory, one position per command.
+
+use std::fmt;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// Line number (from 1) that isn't a command and an amount.
+    Syntax(usize),
+    UnknownCommand(usize, String),
+    BadAmount(usize, String),
+    /// Command number (from 1) whose result doesn't fit.
+    Overflow(usize),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Syntax(line) => write!(f, "line {}: expected 'command amount'", line),
+            Error::UnknownCommand(line, cmd) => write!(f, "line {}: unknown command {:?}", line, cmd),
+            Error::BadAmount(line, n) => write!(f, "line {}: bad amount {:?}", line, n),
+            Error::Overflow(step) => write!(f, "command {}: position overflows", step),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Command {
+    Forward(i64),
+    Down(i64),
+    Up(i64),
+}
+
+/// One command per line, like `forward 5`.
+pub fn parse(input: &str) -> Result<Vec<Command>, Error> {
+    input.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            let line_no = i + 1;
+            let mut words = line.split_ascii_whitespace();
+            let (cmd, n) = match (words.next(), words.next(), words.next()) {
+                (Some(cmd), Some(n), None) => (cmd, n),
+                _ => return Err(Error::Syntax(line_no)),
+            };
+            let n = n.parse::<i64>().map_err(|_| Error::BadAmount(line_no, n.to_string()))?;
+            match cmd {
+                "forward" => Ok(Command::Forward(n)),
+                "down" => Ok(Command::Down(n)),
+                "up" => Ok(Command::Up(n)),
+                _ => Err(Error::UnknownCommand(line_no, cmd.to_string())),
+            }
+        })
+        .collect()
+}
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Position {
+    pub horizontal: i64,
+    pub depth: i64,
+}
+
+impl Position {
+    /// The puzzle answer.
+    pub fn product(&self) -> Option<i64> {
+        self.horizontal.checked_mul(self.depth)
+    }
+}
+
+/// What commands mean.
+pub trait Navigator {
+    /// Anything besides the position that commands change.
+    type State: Copy + fmt::Debug;
+
+    fn start(&self) -> Self::State;
+
+    /// State after `cmd`, or `None` on overflow.
+    fn apply(&self, state: Self::State, cmd: Command) -> Option<Self::State>;
+
+    fn position(&self, state: &Self::State) -> Position;
+}
+
+/// `down` and `up` change depth directly (part 1).
+pub struct Plain;
+
+impl Navigator for Plain {
+    type State = Position;
+
+    fn start(&self) -> Position {
+        Position::default()
+    }
+
+    fn apply(&self, p: Position, cmd: Command) -> Option<Position> {
+        Some(match cmd {
+            Command::Forward(n) => Position { horizontal: p.horizontal.checked_add(n)?, ..p },
+            Command::Down(n) => Position { depth: p.depth.checked_add(n)?, ..p },
+            Command::Up(n) => Position { depth: p.depth.checked_sub(n)?, ..p },
+        })
+    }
+
+    fn position(&self, p: &Position) -> Position {
+        *p
+    }
+}
+
+/// `down` and `up` change the aim, and `forward` dives by the aim per unit (part 2).
+pub struct Aimed;
+
+impl Navigator for Aimed {
+    /// Position and aim.
+    type State = (Position, i64);
+
+    fn start(&self) -> (Position, i64) {
+        (Position::default(), 0)
+    }
+
+    fn apply(&self, (p, aim): (Position, i64), cmd: Command) -> Option<(Position, i64)> {
+        Some(match cmd {
+            Command::Forward(n) => {
+                let depth = p.depth.checked_add(aim.checked_mul(n)?)?;
+                (Position { horizontal: p.horizontal.checked_add(n)?, depth }, aim)
+            }
+            Command::Down(n) => (p, aim.checked_add(n)?),
+            Command::Up(n) => (p, aim.checked_sub(n)?),
+        })
+    }
+
+    fn position(&self, (p, _): &(Position, i64)) -> Position {
+        *p
+    }
+}
+
+/// Where the submarine was before any command and after each one.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Trajectory {
+    positions: Vec<Position>,
+}
+
+impl Trajectory {
+    pub fn new<N: Navigator>(nav: &N, commands: &[Command]) -> Result<Self, Error> {
+        let mut state = nav.start();
+        let mut positions = vec![nav.position(&state)];
+        for (i, &cmd) in commands.iter().enumerate() {
+            state = nav.apply(state, cmd).ok_or(Error::Overflow(i + 1))?;
+            positions.push(nav.position(&state));
+        }
+        Ok(Self { positions })
+    }
+
+    pub fn positions(&self) -> &[Position] {
+        &self.positions
+    }
+
+    /// Number of commands.
+    pub fn steps(&self) -> usize {
+        self.positions.len() - 1
+    }
+
+    /// Position after `k` commands.
+    pub fn at(&self, k: usize) -> Option<Position> {
+        self.positions.get(k).copied()
+    }
+
+    pub fn end(&self) -> Position {
+        *self.positions.last().unwrap()
+    }
+
+    pub fn max_depth(&self) -> i64 {
+        self.positions.iter().map(|p| p.depth).max().unwrap()
+    }
+
+    /// Text plot, `cols` by `rows` characters, scaled to fit: horizontal position to the right and
+    /// depth downwards.
+    pub fn plot(&self, cols: usize, rows: usize) -> String {
+        assert!(cols > 0 && rows > 0);
+        let range = |f: fn(&Position) -> i64| {
+            let lo = self.positions.iter().map(f).min().unwrap();
+            let hi = self.positions.iter().map(f).max().unwrap();
+            (lo as i128, (hi as i128 - lo as i128).max(1))
+        };
+        let (h0, hspan) = range(|p| p.horizontal);
+        let (d0, dspan) = range(|p| p.depth);
+        let cell = |p: &Position| {
+            let c = (p.horizontal as i128 - h0) * (cols as i128 - 1) / hspan;
+            let r = (p.depth as i128 - d0) * (rows as i128 - 1) / dspan;
+            (c, r)
+        };
+
+        let mut grid = vec![vec![b' '; cols]; rows];
+        for w in self.positions.windows(2) {
+            let ((c0, r0), (c1, r1)) = (cell(&w[0]), cell(&w[1]));
+            let n = (c1 - c0).abs().max((r1 - r0).abs()).max(1);
+            for k in 0..=n {
+                let c = c0 + (c1 - c0) * k / n;
+                let r = r0 + (r1 - r0) * k / n;
+                grid[r as usize][c as usize] = b'#';
+            }
+        }
+        let (c, r) = cell(&self.positions[0]);
+        grid[r as usize][c as usize] = b'S';
+        let (c, r) = cell(&self.end());
+        grid[r as usize][c as usize] = b'E';
+        grid.iter().map(|row| String::from_utf8_lossy(row).trim_end().to_string()).collect::<Vec<_>>().join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn navigators() {
+        let cmds = parse("forward 5\ndown 5\nforward 8\nup 3\ndown 8\nforward 2").unwrap();
+        let plain = Trajectory::new(&Plain, &cmds).unwrap();
+        let aimed = Trajectory::new(&Aimed, &cmds).unwrap();
+        assert_eq!(plain.end(), Position { horizontal: 15, depth: 10 });
+        assert_eq!(aimed.end().product(), Some(900));
+        assert_eq!(aimed.at(3), Some(Position { horizontal: 13, depth: 40 }));
+        assert_eq!(aimed.at(7), None);
+        assert_eq!((plain.max_depth(), aimed.max_depth(), aimed.steps()), (10, 60, 6));
+
+        assert_eq!(Trajectory::new(&Plain, &[Command::Up(3)]).unwrap().plot(5, 3), "E\n#\nS");
+        assert_eq!(Trajectory::new(&Aimed, &[Command::Down(1), Command::Forward(4)]).unwrap().plot(5, 5), "S\n #\n  #\n   #\n    E");
+
+        assert_eq!(parse("forward 1\nback 2"), Err(Error::UnknownCommand(2, "back".to_string())));
+        assert_eq!(parse("down x"), Err(Error::BadAmount(1, "x".to_string())));
+        assert_eq!(parse("\nup"), Err(Error::Syntax(2)));
+        assert_eq!(Trajectory::new(&Aimed, &[Command::Down(i64::MAX), Command::Forward(2)]), Err(Error::Overflow(2)));
+    }
+}
@@ -0,0 +1,214 @@
+//! Polymer growth by pair insertion (2021 day 14), tracked as counts of adjacent pairs.
+//!
+//! Element letters are interned into dense indices, so pair counts live in a flat `k * k` array
+//! for `k` distinct elements.  Rules may insert any number of elements between a pair.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::matrix::{Matrix, Scalar};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Error {
+    EmptyTemplate,
+    /// A rule's left side isn't exactly two elements.
+    BadPair(String),
+    DuplicateRule(String),
+    /// Line number (from 1) that doesn't parse.
+    Syntax(usize),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::EmptyTemplate => write!(f, "empty template"),
+            Error::BadPair(p) => write!(f, "rule for {:?} isn't for a pair", p),
+            Error::DuplicateRule(p) => write!(f, "more than one rule for {:?}", p),
+            Error::Syntax(line) => write!(f, "line {}: expected 'AB -> C'", line),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[derive(Clone, Debug)]
+pub struct Polymer {
+    /// Letter of each element index.
+    elems: Vec<u8>,
+    /// Pairs each pair turns into in one step (just itself without a rule).
+    rewrites: Vec<Vec<usize>>,
+    /// Count of each pair, indexed by `first * elems.len() + second`.
+    counts: Vec<u128>,
+    /// The last element never changes, and is the only one that doesn't start a pair.
+    last: usize,
+    /// Number of elements, kept so that no element count can overflow.
+    len: u128,
+    steps: usize,
+}
+
+impl Polymer {
+    pub fn new<'a, I>(template: &[u8], rules: I) -> Result<Self, Error>
+        where I: IntoIterator<Item = (&'a [u8], &'a [u8])>
+    {
+        let rules = rules.into_iter().collect::<Vec<_>>();
+        let mut elems = template.iter()
+            .chain(rules.iter().flat_map(|(pair, ins)| pair.iter().chain(ins.iter())))
+            .copied()
+            .collect::<Vec<_>>();
+        elems.sort_unstable();
+        elems.dedup();
+        let k = elems.len();
+        let index = |e: u8| elems.binary_search(&e).unwrap();
+        let pair = |a: u8, b: u8| index(a) * k + index(b);
+
+        let mut rewrites = (0..k * k).map(|p| vec![p]).collect::<Vec<_>>();
+        let mut seen = vec![false; k * k];
+        for (from, ins) in rules.iter() {
+            let name = || String::from_utf8_lossy(from).into_owned();
+            if from.len() != 2 {
+                return Err(Error::BadPair(name()));
+            }
+            let p = pair(from[0], from[1]);
+            if seen[p] {
+                return Err(Error::DuplicateRule(name()));
+            }
+            seen[p] = true;
+            let chain = std::iter::once(from[0]).chain(ins.iter().copied()).chain(std::iter::once(from[1])).collect::<Vec<_>>();
+            rewrites[p] = chain.windows(2).map(|w| pair(w[0], w[1])).collect();
+        }
+
+        let last = *template.last().ok_or(Error::EmptyTemplate)?;
+        let mut counts = vec![0; k * k];
+        for w in template.windows(2) {
+            counts[pair(w[0], w[1])] += 1;
+        }
+        Ok(Self { rewrites, counts, last: index(last), len: template.len() as u128, steps: 0, elems })
+    }
+
+    /// Apply every rule once.  `None`, leaving the polymer unchanged, if its length overflows.
+    pub fn step(&mut self) -> Option<()> {
+        let mut next = vec![0u128; self.counts.len()];
+        let mut len = self.len;
+        for (p, &count) in self.counts.iter().enumerate().filter(|(_, &c)| c > 0) {
+            let inserted = self.rewrites[p].len() as u128 - 1;
+            len = len.checked_add(count.checked_mul(inserted)?)?;
+            // No pair count exceeds the length.
+            for &q in self.rewrites[p].iter() {
+                next[q] += count;
+            }
+        }
+        self.counts = next;
+        self.len = len;
+        self.steps += 1;
+        Some(())
+    }
+
+    /// Steps applied so far.
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /// Letters of the interned elements, in index order.
+    pub fn elements(&self) -> &[u8] {
+        &self.elems
+    }
+
+    pub fn pair_counts(&self) -> &[u128] {
+        &self.counts
+    }
+
+    /// Count of each element, in index order, given counts for every pair.
+    pub fn counts_from_pairs<T: Scalar>(&self, pairs: &[T]) -> Option<Vec<T>> {
+        let k = self.elems.len();
+        let mut counts = vec![T::zero(); k];
+        counts[self.last] = T::one();
+        for (p, &c) in pairs.iter().enumerate() {
+            counts[p / k] = counts[p / k].add(c)?;
+        }
+        Some(counts)
+    }
+
+    /// Exact count of each element, in index order.
+    pub fn element_counts(&self) -> Vec<u128> {
+        // None exceeds the length, which `step` made sure fits.
+        self.counts_from_pairs(&self.counts).unwrap()
+    }
+
+    /// Number of elements in the polymer.
+    pub fn length(&self) -> u128 {
+        self.len
+    }
+
+    /// One step as a linear map on pair counts.
+    pub fn matrix<T: Scalar>(&self) -> Matrix<T> {
+        let transitions = self.rewrites.iter()
+            .enumerate()
+            .flat_map(|(p, qs)| qs.iter().map(move |&q| (p, q, T::one())));
+        Matrix::from_transitions(self.counts.len(), transitions).unwrap()
+    }
+}
+
+impl FromStr for Polymer {
+    type Err = Error;
+
+    /// The puzzle format: a template line, a blank line, then rules like `CH -> B`.
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let mut lines = s.lines();
+        let template = lines.next().unwrap_or("").trim();
+        let rules = lines.enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                let (pair, ins) = line.split_once(" -> ").ok_or(Error::Syntax(i + 2))?;
+                Ok((pair.trim().as_bytes(), ins.trim().as_bytes()))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        Polymer::new(template.as_bytes(), rules)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Apply rules to the literal string.
+    fn expand(s: &[u8], rules: &[(&[u8], &[u8])]) -> Vec<u8> {
+        let mut res = vec![s[0]];
+        for w in s.windows(2) {
+            if let Some((_, ins)) = rules.iter().find(|(p, _)| *p == w) {
+                res.extend_from_slice(ins);
+            }
+            res.push(w[1]);
+        }
+        res
+    }
+
+    #[test]
+    fn multi_insert() {
+        let rules: Vec<(&[u8], &[u8])> = vec![(b"AB", b"CA"), (b"CA", b""), (b"BB", b"DDD"), (b"AD", b"B")];
+        let mut p = Polymer::new(b"ABBA", rules.iter().copied()).unwrap();
+        let mut s = b"ABBA".to_vec();
+        for _ in 0..6 {
+            p.step().unwrap();
+            s = expand(&s, &rules);
+            let counts = p.elements().iter().map(|&e| s.iter().filter(|&&c| c == e).count() as u128).collect::<Vec<_>>();
+            assert_eq!(p.element_counts(), counts);
+            assert_eq!(p.length(), s.len() as u128);
+        }
+        let m = Polymer::new(b"ABBA", rules.iter().copied()).unwrap().matrix::<u128>();
+        assert_eq!(m.advance(&Polymer::new(b"ABBA", rules.iter().copied()).unwrap().counts, 6).as_deref(), Some(p.pair_counts()));
+
+        // Length 2^n + 1 after n steps, which overflows on the 128th.
+        let mut p = "AB\n\nAB -> A\nAA -> A".parse::<Polymer>().unwrap();
+        for _ in 0..127 {
+            p.step().unwrap();
+        }
+        assert_eq!(p.step(), None);
+        assert_eq!((p.steps(), p.length()), (127, (1 << 127) + 1));
+        assert_eq!(p.element_counts(), vec![1 << 127, 1]);
+
+        assert_eq!("AB\n\nABC -> D".parse::<Polymer>().unwrap_err(), Error::BadPair("ABC".to_string()));
+        assert_eq!("AB\n\nAB -> C\nAB -> D".parse::<Polymer>().unwrap_err(), Error::DuplicateRule("AB".to_string()));
+        assert_eq!("AB\n\nAB => C".parse::<Polymer>().unwrap_err(), Error::Syntax(3));
+        assert_eq!("\n\nAB -> C".parse::<Polymer>().unwrap_err(), Error::EmptyTemplate);
+    }
+}
@@ -0,0 +1,466 @@
+//! Lattice points of line segments, and counting the points where segments overlap (2021 day 5).
+//!
+//! Segments have integer endpoints and any slope.  With `g = gcd(|dx|, |dy|)`, a segment's lattice
+//! points are its endpoints and the `g - 1` evenly spaced points between them, a step of
+//! `(dx / g, dy / g)` apart.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::convert::TryFrom;
+use std::fmt;
+use std::hash::Hash;
+
+/// Integer coordinate types.  Arithmetic is done in checked `i128`.  Intersections always work for
+/// coordinates below 2^40 in magnitude (so for any 32-bit type); past that they can overflow, and
+/// `Segment::intersect` and `Analytic` give up.
+pub trait Coord: Copy + Ord + Hash + fmt::Debug + Into<i64> + TryFrom<i64> {}
+
+impl<T: Copy + Ord + Hash + fmt::Debug + Into<i64> + TryFrom<i64>> Coord for T {}
+
+pub type Point<T> = (T, T);
+
+fn wide<T: Coord>((x, y): Point<T>) -> (i128, i128) {
+    (x.into() as i128, y.into() as i128)
+}
+
+/// Only for points between two `T` points, which fit.
+fn narrow<T: Coord>((x, y): (i128, i128)) -> Point<T> {
+    let fit = |v: i128| match T::try_from(v as i64) {
+        Ok(v) => v,
+        Err(_) => unreachable!(),
+    };
+    (fit(x), fit(y))
+}
+
+fn gcd(mut a: i128, mut b: i128) -> i128 {
+    a = a.abs();
+    b = b.abs();
+    while b != 0 {
+        let t = a % b;
+        a = b;
+        b = t;
+    }
+    a
+}
+
+/// Intermediate value past `i128`.
+struct Overflow;
+
+fn mul(a: i128, b: i128) -> Result<i128, Overflow> {
+    a.checked_mul(b).ok_or(Overflow)
+}
+
+fn cross((ax, ay): (i128, i128), (bx, by): (i128, i128)) -> Result<i128, Overflow> {
+    mul(ax, by)?.checked_sub(mul(ay, bx)?).ok_or(Overflow)
+}
+
+fn dot((ax, ay): (i128, i128), (bx, by): (i128, i128)) -> Result<i128, Overflow> {
+    mul(ax, bx)?.checked_add(mul(ay, by)?).ok_or(Overflow)
+}
+
+fn sub((ax, ay): (i128, i128), (bx, by): (i128, i128)) -> (i128, i128) {
+    (ax - bx, ay - by)
+}
+
+/// The closed segment from `a` to `b`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Segment<T> {
+    pub a: Point<T>,
+    pub b: Point<T>,
+}
+
+/// Where two segments meet.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Intersection<T> {
+    /// A single point `(x / d, y / d)`, in lowest terms with `d > 0`.  Not necessarily a lattice
+    /// point.
+    Point { x: i128, y: i128, d: i128 },
+    /// The segments are collinear and share more than one point.
+    Overlap(Segment<T>),
+}
+
+impl<T: Coord> Segment<T> {
+    pub fn new(a: Point<T>, b: Point<T>) -> Self {
+        Self { a, b }
+    }
+
+    fn delta(&self) -> (i128, i128) {
+        sub(wide(self.b), wide(self.a))
+    }
+
+    pub fn is_point(&self) -> bool {
+        self.a == self.b
+    }
+
+    /// Horizontal or vertical.
+    pub fn is_axis_aligned(&self) -> bool {
+        self.a.0 == self.b.0 || self.a.1 == self.b.1
+    }
+
+    /// At 45 degrees.
+    pub fn is_diagonal(&self) -> bool {
+        let (dx, dy) = self.delta();
+        dx != 0 && dx.abs() == dy.abs()
+    }
+
+    /// Step between consecutive lattice points, and how many steps there are.
+    fn step(&self) -> ((i128, i128), i128) {
+        let (dx, dy) = self.delta();
+        let g = gcd(dx, dy);
+        if g == 0 { ((0, 0), 0) } else { ((dx / g, dy / g), g) }
+    }
+
+    /// Number of lattice points.
+    pub fn lattice_len(&self) -> u64 {
+        self.step().1 as u64 + 1
+    }
+
+    /// Lattice points from `a` to `b`.
+    pub fn points(&self) -> impl Iterator<Item = Point<T>> {
+        let (x0, y0) = wide(self.a);
+        let ((sx, sy), n) = self.step();
+        (0..=n).map(move |k| narrow((x0 + k * sx, y0 + k * sy)))
+    }
+
+    /// Whether lattice point `p` is some whole number of steps from `a`, without going past `b`.
+    fn contains_wide(&self, p: (i128, i128)) -> bool {
+        let ((sx, sy), n) = self.step();
+        let (dx, dy) = sub(p, wide(self.a));
+        let k = if sx != 0 {
+            dx / sx
+        } else if sy != 0 {
+            dy / sy
+        } else {
+            0
+        };
+        (0..=n).contains(&k) && (k * sx, k * sy) == (dx, dy)
+    }
+
+    pub fn contains(&self, p: Point<T>) -> bool {
+        self.contains_wide(wide(p))
+    }
+
+    /// Where `self` and `other` meet, if they do.  Also `None` if the arithmetic would overflow,
+    /// which can only happen for coordinates of 2^40 or more (see `Coord`).
+    pub fn intersect(&self, other: &Self) -> Option<Intersection<T>> {
+        self.checked_intersect(other).unwrap_or(None)
+    }
+
+    fn checked_intersect(&self, other: &Self) -> Result<Option<Intersection<T>>, Overflow> {
+        let (p, r) = (wide(self.a), self.delta());
+        let (q, s) = (wide(other.a), other.delta());
+        let at = |(x, y): (i128, i128)| Intersection::Point { x, y, d: 1 };
+        if r == (0, 0) {
+            return Ok(if other.contains_wide(p) { Some(at(p)) } else { None });
+        }
+        if s == (0, 0) {
+            return Ok(if self.contains_wide(q) { Some(at(q)) } else { None });
+        }
+
+        let qp = sub(q, p);
+        let denom = cross(r, s)?;
+        if denom == 0 {
+            if cross(qp, r)? != 0 {
+                return Ok(None);
+            }
+            // Collinear: compare positions along `r`.
+            let key = |e: (i128, i128)| dot(sub(e, p), r);
+            let (mut lo, mut hi) = (wide(other.a), wide(other.b));
+            if key(lo)? > key(hi)? {
+                std::mem::swap(&mut lo, &mut hi);
+            }
+            let lo = if key(lo)? > 0 { lo } else { p };
+            let hi = if key(hi)? < dot(r, r)? { hi } else { wide(self.b) };
+            return Ok(match key(lo)?.cmp(&key(hi)?) {
+                std::cmp::Ordering::Greater => None,
+                std::cmp::Ordering::Equal => Some(at(lo)),
+                std::cmp::Ordering::Less => Some(Intersection::Overlap(Segment::new(narrow(lo), narrow(hi)))),
+            });
+        }
+
+        // `p + t r == q + u s`, with `t = tn / denom` and `u = un / denom`.
+        let sign = denom.signum();
+        let (tn, un, denom) = (mul(cross(qp, s)?, sign)?, mul(cross(qp, r)?, sign)?, mul(denom, sign)?);
+        if tn < 0 || tn > denom || un < 0 || un > denom {
+            return Ok(None);
+        }
+        let along = |p: i128, r: i128| mul(p, denom)?.checked_add(mul(r, tn)?).ok_or(Overflow);
+        let (x, y) = (along(p.0, r.0)?, along(p.1, r.1)?);
+        if x == i128::MIN || y == i128::MIN {
+            return Err(Overflow);
+        }
+        let g = gcd(gcd(x, y), denom);
+        Ok(Some(Intersection::Point { x: x / g, y: y / g, d: denom / g }))
+    }
+}
+
+impl<T: Coord> Intersection<T> {
+    /// The point, if this is a single lattice point.
+    pub fn lattice_point(&self) -> Option<Point<T>> {
+        match *self {
+            Intersection::Point { x, y, d: 1 } => Some(narrow((x, y))),
+            _ => None,
+        }
+    }
+
+    /// Number of lattice points in the intersection.
+    pub fn lattice_len(&self) -> u64 {
+        match self {
+            Intersection::Point { d, .. } => (*d == 1) as u64,
+            Intersection::Overlap(s) => s.lattice_len(),
+        }
+    }
+}
+
+pub trait OverlapCounter<T> {
+    fn name(&self) -> &'static str;
+
+    /// Number of lattice points on at least two of `segments`, or `None` if working storage
+    /// doesn't fit.
+    fn count(&self, segments: &[Segment<T>]) -> Option<u64>;
+}
+
+/// Paint every lattice point onto a grid covering the bounding box.
+pub struct DenseGrid {
+    /// Refuse grids with more cells than this.
+    pub max_cells: usize,
+}
+
+impl Default for DenseGrid {
+    fn default() -> Self {
+        Self { max_cells: 1 << 28 }
+    }
+}
+
+impl<T: Coord> OverlapCounter<T> for DenseGrid {
+    fn name(&self) -> &'static str {
+        "dense grid"
+    }
+
+    fn count(&self, segments: &[Segment<T>]) -> Option<u64> {
+        if segments.is_empty() {
+            return Some(0);
+        }
+        let ends = segments.iter().flat_map(|s| vec![wide(s.a), wide(s.b)]).collect::<Vec<_>>();
+        let (x0, x1) = (ends.iter().map(|p| p.0).min()?, ends.iter().map(|p| p.0).max()?);
+        let (y0, y1) = (ends.iter().map(|p| p.1).min()?, ends.iter().map(|p| p.1).max()?);
+        let w = usize::try_from(x1 - x0 + 1).ok()?;
+        let h = usize::try_from(y1 - y0 + 1).ok()?;
+        let cells = w.checked_mul(h).filter(|&c| c <= self.max_cells)?;
+
+        // 0, 1, or 2 for "two or more".
+        let mut grid = vec![0u8; cells];
+        for s in segments.iter() {
+            for p in s.points() {
+                let (x, y) = wide(p);
+                let c = &mut grid[(y - y0) as usize * w + (x - x0) as usize];
+                *c = (*c + 1).min(2);
+            }
+        }
+        Some(grid.iter().filter(|&&c| c == 2).count() as u64)
+    }
+}
+
+/// Horizontal segments as intervals and others point by point, swept row by row.  Storage follows
+/// the segments rather than the bounding box, and long horizontal segments cost nothing extra.
+pub struct RowSweep;
+
+impl<T: Coord> OverlapCounter<T> for RowSweep {
+    fn name(&self) -> &'static str {
+        "row sweep"
+    }
+
+    fn count(&self, segments: &[Segment<T>]) -> Option<u64> {
+        let mut rows: BTreeMap<i128, Vec<(i128, i32)>> = BTreeMap::new();
+        for s in segments.iter() {
+            let (a, b) = (wide(s.a), wide(s.b));
+            if a.1 == b.1 {
+                let row = rows.entry(a.1).or_default();
+                row.push((a.0.min(b.0), 1));
+                row.push((a.0.max(b.0) + 1, -1));
+            } else {
+                for (x, y) in s.points().map(wide) {
+                    let row = rows.entry(y).or_default();
+                    row.push((x, 1));
+                    row.push((x + 1, -1));
+                }
+            }
+        }
+
+        let mut total = 0u64;
+        for events in rows.values_mut() {
+            events.sort_unstable();
+            let mut depth = 0;
+            for (i, &(x, d)) in events.iter().enumerate() {
+                depth += d;
+                if depth >= 2 {
+                    total += (events[i + 1].0 - x) as u64;
+                }
+            }
+        }
+        Some(total)
+    }
+}
+
+/// Intersect every pair of segments, without visiting any lattice points.  Collinear overlaps are
+/// merged per line, so points covered by three or more segments count once.  Quadratic in the
+/// number of segments.  `None` if the arithmetic overflows (see `Coord`).
+pub struct Analytic;
+
+/// A line through lattice points: its primitive direction (pointing right, or up if vertical) and
+/// `dy * x - dx * y`, which is the same everywhere on it.
+type Line = ((i128, i128), i128);
+
+impl Analytic {
+    fn line(s: (i128, i128), p: (i128, i128)) -> Result<Line, Overflow> {
+        let g = gcd(s.0, s.1);
+        let (mut dx, mut dy) = (s.0 / g, s.1 / g);
+        if dx < 0 || (dx == 0 && dy < 0) {
+            dx = -dx;
+            dy = -dy;
+        }
+        Ok(((dx, dy), cross((dy, dx), p)?))
+    }
+
+    /// Position of lattice point `p` along `line`, in steps.
+    fn position(((dx, dy), _): Line, p: (i128, i128)) -> i128 {
+        if dx != 0 { p.0.div_euclid(dx) } else { p.1.div_euclid(dy) }
+    }
+}
+
+impl<T: Coord> OverlapCounter<T> for Analytic {
+    fn name(&self) -> &'static str {
+        "analytic"
+    }
+
+    fn count(&self, segments: &[Segment<T>]) -> Option<u64> {
+        let mut points = HashSet::new();
+        let mut overlaps: HashMap<Line, Vec<(i128, i128)>> = HashMap::new();
+        for (i, s) in segments.iter().enumerate() {
+            for t in segments[i + 1..].iter() {
+                match s.checked_intersect(t).ok()? {
+                    Some(Intersection::Point { x, y, d: 1 }) => {
+                        points.insert((x, y));
+                    }
+                    Some(Intersection::Overlap(o)) => {
+                        let (a, b) = (wide(o.a), wide(o.b));
+                        let line = Self::line(o.delta(), a).ok()?;
+                        let (pa, pb) = (Self::position(line, a), Self::position(line, b));
+                        overlaps.entry(line).or_default().push((pa.min(pb), pa.max(pb)));
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        let mut total = 0u64;
+        for spans in overlaps.values_mut() {
+            spans.sort_unstable();
+            let mut merged: Vec<(i128, i128)> = Vec::new();
+            for &(lo, hi) in spans.iter() {
+                match merged.last_mut() {
+                    Some(last) if lo <= last.1 + 1 => last.1 = last.1.max(hi),
+                    _ => merged.push((lo, hi)),
+                }
+            }
+            total += merged.iter().map(|(lo, hi)| (hi - lo + 1) as u64).sum::<u64>();
+            *spans = merged;
+        }
+
+        // Crossings not already counted in an overlap.
+        let dirs = overlaps.keys().map(|&(dir, _)| dir).collect::<HashSet<_>>();
+        let covered = |p: (i128, i128)| dirs.iter().any(|&dir| {
+            let line = match Self::line(dir, p) {
+                Ok(line) => line,
+                // Can't be the line of any overlap, whose offsets all fit.
+                Err(_) => return false,
+            };
+            match overlaps.get(&line) {
+                Some(spans) => {
+                    let pos = Self::position(line, p);
+                    let i = spans.partition_point(|&(_, hi)| hi < pos);
+                    i < spans.len() && spans[i].0 <= pos
+                }
+                None => false,
+            }
+        });
+        total += points.into_iter().filter(|&p| !covered(p)).count() as u64;
+        Some(total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::XorShift;
+
+    fn seg(a: (i32, i32), b: (i32, i32)) -> Segment<i32> {
+        Segment::new(a, b)
+    }
+
+    #[test]
+    fn lattice_points() {
+        assert_eq!(seg((0, 0), (6, 4)).points().collect::<Vec<_>>(), vec![(0, 0), (3, 2), (6, 4)]);
+        assert_eq!(seg((2, 5), (2, 3)).points().collect::<Vec<_>>(), vec![(2, 5), (2, 4), (2, 3)]);
+        assert_eq!(seg((1, 1), (1, 1)).lattice_len(), 1);
+        assert_eq!(Segment::<u8>::new((250, 0), (0, 250)).points().count(), 251);
+    }
+
+    #[test]
+    fn intersections() {
+        let x = seg((0, 0), (4, 4)).intersect(&seg((0, 4), (4, 0)));
+        assert_eq!(x, Some(Intersection::Point { x: 2, y: 2, d: 1 }));
+        assert_eq!(x.unwrap().lattice_point(), Some((2, 2)));
+        let x = seg((0, 0), (1, 1)).intersect(&seg((0, 1), (1, 0))).unwrap();
+        assert_eq!((x, x.lattice_len()), (Intersection::Point { x: 1, y: 1, d: 2 }, 0));
+        assert_eq!(seg((0, 0), (6, 3)).intersect(&seg((8, 4), (4, 2))), Some(Intersection::Overlap(seg((4, 2), (6, 3)))));
+        assert_eq!(seg((0, 0), (2, 0)).intersect(&seg((2, 0), (5, 0))), Some(Intersection::Point { x: 2, y: 0, d: 1 }));
+        assert_eq!(seg((0, 0), (2, 0)).intersect(&seg((3, 0), (5, 0))), None);
+        assert_eq!(seg((0, 0), (2, 0)).intersect(&seg((0, 1), (2, 1))), None);
+        assert_eq!(seg((0, 0), (4, 0)).intersect(&seg((2, 0), (2, 0))), Some(Intersection::Point { x: 2, y: 0, d: 1 }));
+    }
+
+    #[test]
+    fn counters_agree() {
+        let mut rng = XorShift::new(0x9e3779b97f4a7c15);
+        let mut rand = |n: i32| rng.below(n as u64) as i32;
+        let counters: Vec<Box<dyn OverlapCounter<i32>>> = vec![Box::new(DenseGrid::default()), Box::new(RowSweep), Box::new(Analytic)];
+        for round in 0..30 {
+            // Few distinct slopes on a small grid, so there are plenty of collinear overlaps.
+            let segments = (0..20).map(|_| {
+                let a = (rand(12) - 3, rand(12) - 3);
+                let (dx, dy) = [(1, 0), (0, 1), (1, 1), (1, -1), (2, 1), (1, 3)][rand(6) as usize];
+                let k = rand(5) - (round % 2);
+                seg(a, (a.0 + k * dx, a.1 + k * dy))
+            })
+            .collect::<Vec<_>>();
+            let mut seen: HashMap<(i32, i32), u32> = HashMap::new();
+            for p in segments.iter().flat_map(|s| s.points()) {
+                *seen.entry(p).or_default() += 1;
+            }
+            let expected = seen.values().filter(|&&c| c >= 2).count() as u64;
+            for c in counters.iter() {
+                assert_eq!(c.count(&segments), Some(expected), "{}", c.name());
+            }
+        }
+        assert_eq!(DenseGrid { max_cells: 10 }.count(&[seg((0, 0), (10, 0)), seg((0, 1), (0, 1))]), None);
+    }
+
+    #[test]
+    fn wide_coordinates() {
+        let b = (1i64 << 39) - 1;
+        let (s, t) = (Segment::new((-b, -b + 1), (b - 1, b)), Segment::new((-b, b - 3), (b, -b + 2)));
+        let at = Intersection::Point { x: -1649267441661, y: 549755813882, d: 2199023255543 };
+        assert_eq!(s.intersect(&t), Some(at));
+        let (u, v) = (Segment::new((-b, -b), (b, b)), Segment::new((-b, b), (b, -b)));
+        assert_eq!(u.intersect(&v), Some(Intersection::Point { x: 0, y: 0, d: 1 }));
+        let w = Segment::new((0, 0), (b, b));
+        assert_eq!(Analytic.count(&[s, t, u, v, w]), Some(b as u64 + 1));
+
+        // Past 2^40 the arithmetic can overflow; that's a refusal, not a panic.
+        let (lo, hi) = (i64::MIN, i64::MAX);
+        let (u, v) = (Segment::new((lo, lo), (hi, hi)), Segment::new((lo, hi), (hi, lo)));
+        assert!(u.contains((0, 0)));
+        assert_eq!(u.intersect(&v), None);
+        assert_eq!(Analytic.count(&[u, v]), None);
+    }
+}
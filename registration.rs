@@ -0,0 +1,485 @@
+//! Integer point-cloud registration for the 2021 day 19 scanners.
+//!
+//! Scanners only ever differ by one of the 24 axis-aligned rotations plus a translation, so
+//! everything stays in exact integer arithmetic.
+
+use anyhow::{anyhow, Result};
+use rayon::prelude::*;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+
+use crate::XorShift;
+
+pub type Point = [i64; 3];
+
+fn sub(a: Point, b: Point) -> Point {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn add(a: Point, b: Point) -> Point {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn dist2(a: Point, b: Point) -> i64 {
+    let d = sub(a, b);
+    d[0] * d[0] + d[1] * d[1] + d[2] * d[2]
+}
+
+pub fn manhattan(a: Point, b: Point) -> i64 {
+    let d = sub(a, b);
+    d[0].abs() + d[1].abs() + d[2].abs()
+}
+
+fn det(m: [[i64; 3]; 3]) -> i64 {
+    m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+        - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+        + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+}
+
+/// A proper rotation: a signed permutation matrix with determinant 1.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Rotation(pub [[i64; 3]; 3]);
+
+impl Rotation {
+    pub const IDENTITY: Rotation = Rotation([[1, 0, 0], [0, 1, 0], [0, 0, 1]]);
+
+    /// All 24, starting with the identity.
+    pub fn all() -> Vec<Rotation> {
+        const PERMS: [[usize; 3]; 6] = [[0, 1, 2], [0, 2, 1], [1, 0, 2], [1, 2, 0], [2, 0, 1], [2, 1, 0]];
+        let mut res = Vec::with_capacity(24);
+        for perm in PERMS.iter() {
+            for signs in 0..8 {
+                let mut m = [[0; 3]; 3];
+                for (row, &col) in perm.iter().enumerate() {
+                    m[row][col] = if signs & (1 << row) != 0 { -1 } else { 1 };
+                }
+                if det(m) == 1 {
+                    res.push(Rotation(m));
+                }
+            }
+        }
+        debug_assert_eq!(res.len(), 24);
+        res
+    }
+
+    pub fn apply(&self, p: Point) -> Point {
+        let m = &self.0;
+        [
+            m[0][0] * p[0] + m[0][1] * p[1] + m[0][2] * p[2],
+            m[1][0] * p[0] + m[1][1] * p[1] + m[1][2] * p[2],
+            m[2][0] * p[0] + m[2][1] * p[1] + m[2][2] * p[2],
+        ]
+    }
+
+    /// `self` after `other`.
+    pub fn compose(&self, other: &Rotation) -> Rotation {
+        let mut m = [[0; 3]; 3];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, v) in row.iter_mut().enumerate() {
+                *v = (0..3).map(|k| self.0[i][k] * other.0[k][j]).sum();
+            }
+        }
+        Rotation(m)
+    }
+
+    pub fn inverse(&self) -> Rotation {
+        let mut m = [[0; 3]; 3];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, v) in row.iter_mut().enumerate() {
+                *v = self.0[j][i];
+            }
+        }
+        Rotation(m)
+    }
+}
+
+/// `p -> rot * p + offset`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Transform {
+    pub rot: Rotation,
+    pub offset: Point,
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform { rot: Rotation::IDENTITY, offset: [0; 3] };
+
+    pub fn apply(&self, p: Point) -> Point {
+        add(self.rot.apply(p), self.offset)
+    }
+
+    /// `self` after `other`.
+    pub fn compose(&self, other: &Transform) -> Transform {
+        Transform {
+            rot: self.rot.compose(&other.rot),
+            offset: self.apply(other.offset),
+        }
+    }
+
+    pub fn inverse(&self) -> Transform {
+        let rot = self.rot.inverse();
+        let o = rot.apply(self.offset);
+        Transform { rot, offset: [-o[0], -o[1], -o[2]] }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Config {
+    /// Beacons two scanners must share to count as overlapping.
+    pub overlap: usize,
+    /// Hypotheses tried by the fallback when the exact solve is degenerate.
+    pub ransac_iters: usize,
+    /// Seeds the fallback's hypothesis sampling; the low bit is ignored.
+    pub seed: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            overlap: 12,
+            ransac_iters: 200,
+            seed: 0x9e3779b97f4a7c15,
+        }
+    }
+}
+
+/// One scanner's beacons, with rotation-invariant fingerprints.
+#[derive(Clone, Debug)]
+pub struct Scan {
+    pub points: Vec<Point>,
+    /// Per point, the sorted squared distances to every other point.
+    fingerprints: Vec<Vec<i64>>,
+    /// All pairwise squared distances, sorted.
+    distances: Vec<i64>,
+}
+
+/// Size of the multiset intersection of two sorted lists.
+fn common(a: &[i64], b: &[i64]) -> usize {
+    let (mut i, mut j, mut n) = (0, 0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] < b[j] {
+            i += 1;
+        } else if a[i] > b[j] {
+            j += 1;
+        } else {
+            n += 1;
+            i += 1;
+            j += 1;
+        }
+    }
+    n
+}
+
+/// Result of registering one scan against another.
+#[derive(Clone, Debug)]
+pub struct Alignment {
+    /// Maps the second scan's coordinates into the first's.
+    pub transform: Transform,
+    /// Corresponding `(first, second)` point indices under `transform`.
+    pub pairs: Vec<(usize, usize)>,
+}
+
+impl Scan {
+    pub fn new(points: Vec<Point>) -> Self {
+        let fingerprints = points.iter().map(|&p| {
+            let mut d = points.iter().filter(|&&q| q != p).map(|&q| dist2(p, q)).collect::<Vec<_>>();
+            d.sort_unstable();
+            d
+        })
+        .collect::<Vec<_>>();
+        let mut distances = Vec::new();
+        for (i, &p) in points.iter().enumerate() {
+            distances.extend(points[i + 1..].iter().map(|&q| dist2(p, q)));
+        }
+        distances.sort_unstable();
+        Self { points, fingerprints, distances }
+    }
+
+    /// Point pairs whose fingerprints agree well enough to be the same beacon; each point is
+    /// paired with its best match only.
+    fn candidates(&self, other: &Scan, cfg: &Config) -> Vec<(usize, usize)> {
+        let need = cfg.overlap.saturating_sub(1);
+        let mut res = Vec::new();
+        for (i, fa) in self.fingerprints.iter().enumerate() {
+            let best = other.fingerprints.iter().enumerate()
+                .map(|(j, fb)| (common(fa, fb), j))
+                .filter(|&(n, _)| n >= need)
+                .max();
+            if let Some((_, j)) = best {
+                res.push((i, j));
+            }
+        }
+        res
+    }
+
+    fn inliers(&self, other: &Scan, lookup: &HashMap<Point, usize>, t: &Transform) -> Vec<(usize, usize)> {
+        other.points.iter().enumerate()
+            .filter_map(|(j, &p)| lookup.get(&t.apply(p)).map(|&i| (i, j)))
+            .collect()
+    }
+
+    /// Find the transform taking `other`'s coordinates into ours, if the scans overlap.
+    ///
+    /// Four corresponding points pin down the rotation exactly unless they're coplanar; then (or
+    /// if the four contain a false match) we fall back to scoring random two-point hypotheses.
+    pub fn register(&self, other: &Scan, cfg: &Config) -> Result<Option<Alignment>> {
+        let need = cfg.overlap;
+        if common(&self.distances, &other.distances) < need * need.saturating_sub(1) / 2 {
+            return Ok(None);
+        }
+        let cands = self.candidates(other, cfg);
+        if cands.len() < need.max(2) {
+            return Ok(None);
+        }
+
+        let lookup = self.points.iter().enumerate().map(|(i, &p)| (p, i)).collect::<HashMap<_, _>>();
+        let rotations = Rotation::all();
+        let hypothesis = |c: &[(usize, usize)]| -> Vec<Transform> {
+            let (a0, b0) = (self.points[c[0].0], other.points[c[0].1]);
+            rotations.iter()
+                .filter(|r| c[1..].iter().all(|&(i, j)| {
+                    r.apply(sub(other.points[j], b0)) == sub(self.points[i], a0)
+                }))
+                .map(|&rot| Transform { rot, offset: sub(a0, rot.apply(b0)) })
+                .collect()
+        };
+        let accept = |t: &Transform| -> Option<Alignment> {
+            let pairs = self.inliers(other, &lookup, t);
+            if pairs.len() >= need {
+                Some(Alignment { transform: *t, pairs })
+            } else {
+                None
+            }
+        };
+
+        let a = |k: usize| sub(self.points[cands[k].0], self.points[cands[0].0]);
+        if cands.len() >= 4 && det([a(1), a(2), a(3)]) != 0 {
+            if let Some(found) = hypothesis(&cands[..4]).iter().find_map(&accept) {
+                return Ok(Some(found));
+            }
+        }
+
+        let mut rng = XorShift::new(cfg.seed);
+        let mut rand = |n: usize| rng.below(n as u64) as usize;
+        let mut best: Option<Alignment> = None;
+        for _ in 0..cfg.ransac_iters {
+            let (i, j) = (rand(cands.len()), rand(cands.len()));
+            if i == j {
+                continue;
+            }
+            for t in hypothesis(&[cands[i], cands[j]]).iter() {
+                if let Some(found) = accept(t) {
+                    if best.as_ref().map_or(true, |b| found.pairs.len() > b.pairs.len()) {
+                        best = Some(found);
+                    }
+                }
+            }
+        }
+        match best {
+            Some(found) => Ok(Some(found)),
+            None => Err(anyhow!("{} fingerprint matches but no transform explains {} of them", cands.len(), need)),
+        }
+    }
+}
+
+/// An overlap found between scans `.0` and `.1`.
+pub type Edge = (usize, usize, Alignment);
+
+#[derive(Clone, Debug)]
+pub struct Solution {
+    /// Each scan's transform into scan 0's frame.
+    pub transforms: Vec<Transform>,
+    /// All pairwise overlaps.
+    pub edges: Vec<Edge>,
+    /// Pairs whose registration failed, with why.  They're treated as not overlapping.
+    pub failed: Vec<(usize, usize, String)>,
+}
+
+/// Register every pair of scans in parallel, then chain transforms out from scan 0 along a BFS
+/// tree of the overlap graph.  A pair that fails to register is just a missing edge.  Fails if
+/// some scans can't be reached from scan 0, or if an overlap disagrees with the transforms.
+pub fn solve(scans: &[Scan], cfg: &Config) -> Result<Solution> {
+    let n = scans.len();
+    let pairs = (0..n).flat_map(|i| (i + 1..n).map(move |j| (i, j))).collect::<Vec<_>>();
+    let results = pairs.par_iter()
+        .map(|&(i, j)| (i, j, scans[i].register(&scans[j], cfg)))
+        .collect::<Vec<_>>();
+    let mut edges = Vec::new();
+    let mut failed = Vec::new();
+    for (i, j, res) in results.into_iter() {
+        match res {
+            Ok(Some(a)) => edges.push((i, j, a)),
+            Ok(None) => (),
+            Err(e) => failed.push((i, j, format!("{:#}", e))),
+        }
+    }
+
+    // adj[i]: (neighbour, transform from neighbour's frame into i's)
+    let mut adj = vec![Vec::new(); n];
+    for (i, j, a) in edges.iter() {
+        adj[*i].push((*j, a.transform));
+        adj[*j].push((*i, a.transform.inverse()));
+    }
+
+    let mut transforms: Vec<Option<Transform>> = vec![None; n];
+    let mut components = Vec::new();
+    for root in 0..n {
+        if transforms[root].is_some() {
+            continue;
+        }
+        transforms[root] = Some(Transform::IDENTITY);
+        let mut component = vec![root];
+        let mut queue = VecDeque::from(vec![root]);
+        while let Some(i) = queue.pop_front() {
+            let ti = transforms[i].unwrap();
+            for &(j, t) in adj[i].iter() {
+                if transforms[j].is_none() {
+                    transforms[j] = Some(ti.compose(&t));
+                    component.push(j);
+                    queue.push_back(j);
+                }
+            }
+        }
+        component.sort_unstable();
+        components.push(component);
+    }
+    if components.len() > 1 {
+        return Err(anyhow!("Overlap graph is disconnected: {:?} (failed pairs: {:?})", components, failed));
+    }
+    let transforms = transforms.into_iter().map(Option::unwrap).collect::<Vec<_>>();
+
+    // Every overlap, not just the tree edges, should agree on where its matched beacons are.
+    let inconsistent = edges.iter()
+        .filter_map(|(i, j, a)| {
+            let bad = a.pairs.iter()
+                .filter(|&&(p, q)| transforms[*i].apply(scans[*i].points[p]) != transforms[*j].apply(scans[*j].points[q]))
+                .count();
+            if bad > 0 {
+                Some((*i, *j, bad))
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+    if !inconsistent.is_empty() {
+        return Err(anyhow!("Overlaps disagree with the chained transforms (scan, scan, mismatched pairs): {:?}", inconsistent));
+    }
+
+    Ok(Solution { transforms, edges, failed })
+}
+
+/// Scanner positions and the deduplicated beacons, given each scan's transform into a common
+/// frame.
+pub fn merge(scans: &[Scan], transforms: &[Transform]) -> (Vec<Point>, BTreeSet<Point>) {
+    let positions = transforms.iter().map(|t| t.offset).collect();
+    let beacons = scans.iter().zip(transforms.iter())
+        .flat_map(|(s, t)| s.points.iter().map(move |&p| t.apply(p)))
+        .collect();
+    (positions, beacons)
+}
+
+/// Check the points are distinct, since fingerprints rely on it.
+pub fn check_distinct(points: &[Point]) -> Result<()> {
+    let mut seen = HashSet::new();
+    for p in points.iter() {
+        if !seen.insert(p) {
+            return Err(anyhow!("Duplicate point {:?}", p));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cloud(n: usize) -> Vec<Point> {
+        let mut rng = XorShift::new(12345);
+        (0..n).map(|_| {
+            let mut c = [0; 3];
+            for v in c.iter_mut() {
+                *v = rng.below(1000) as i64 - 500;
+            }
+            c
+        })
+        .collect()
+    }
+
+    #[test]
+    fn rotations() {
+        let all = Rotation::all();
+        assert_eq!(all.iter().collect::<HashSet<_>>().len(), 24);
+        for r in all.iter() {
+            assert_eq!(r.compose(&r.inverse()), Rotation::IDENTITY);
+        }
+        let t = Transform { rot: all[7], offset: [5, -3, 2] };
+        let u = Transform { rot: all[13], offset: [1, 1, 1] };
+        let p = [3, 4, 5];
+        assert_eq!(t.compose(&u).apply(p), t.apply(u.apply(p)));
+        assert_eq!(t.inverse().apply(t.apply(p)), p);
+    }
+
+    #[test]
+    fn register_recovers_transform() {
+        let points = cloud(20);
+        let truth = Transform { rot: Rotation::all()[17], offset: [68, -1246, -43] };
+        // Second scan sees the first 14 beacons plus some of its own.
+        let mut other = points[..14].iter().map(|&p| truth.inverse().apply(p)).collect::<Vec<_>>();
+        other.extend([[900, 900, 900], [-900, 17, 4]].iter());
+        let (a, b) = (Scan::new(points.clone()), Scan::new(other));
+
+        let found = a.register(&b, &Config::default()).unwrap().unwrap();
+        assert_eq!(found.transform, truth);
+        assert_eq!(found.pairs.len(), 14);
+        let cfg = Config { overlap: 15, ..Config::default() };
+        assert!(a.register(&b, &cfg).unwrap().is_none());
+
+        // Coplanar overlap: exact solve is impossible, the fallback still gets it.
+        let flat = points.iter().map(|p| [p[0], p[1], 7]).collect::<Vec<_>>();
+        let other = flat[..12].iter().map(|&p| truth.inverse().apply(p)).collect::<Vec<_>>();
+        let (flat, other) = (Scan::new(flat), Scan::new(other));
+        let found = flat.register(&other, &Config::default()).unwrap().unwrap();
+        assert_eq!(found.transform, truth);
+        let found = flat.register(&other, &Config { seed: 0, ..Config::default() }).unwrap().unwrap();
+        assert_eq!(found.transform, truth);
+    }
+
+    #[test]
+    fn solve_graph() {
+        let points = cloud(40);
+        let rots = Rotation::all();
+        // A chain: scan 2 only overlaps scan 0 through scan 1.
+        let t1 = Transform { rot: rots[5], offset: [100, 0, -20] };
+        let t2 = Transform { rot: rots[21], offset: [-7, 300, 2] };
+        let view = |t: &Transform, idx: std::ops::Range<usize>| {
+            Scan::new(points[idx].iter().map(|&p| t.inverse().apply(p)).collect())
+        };
+        let scans = vec![view(&Transform::IDENTITY, 0..20), view(&t1, 8..28), view(&t2, 16..40)];
+        let cfg = Config { overlap: 12, ..Config::default() };
+        let sol = solve(&scans, &cfg).unwrap();
+        assert_eq!(sol.transforms, vec![Transform::IDENTITY, t1, t2]);
+        assert!(sol.failed.is_empty());
+        let (positions, beacons) = merge(&scans, &sol.transforms);
+        assert_eq!(positions, vec![[0; 3], t1.offset, t2.offset]);
+        assert_eq!(beacons.len(), 40);
+
+        let lonely = view(&t1, 28..40);
+        assert!(solve(&[scans[0].clone(), lonely], &cfg).is_err());
+
+        // Scans 0 and 2 share only coplanar points, which can't register without RANSAC; the
+        // graph is still connected through scan 1.
+        let extra = cloud(52);
+        let flat = extra[40..].iter().map(|p| [p[0], p[1], 7]).collect::<Vec<_>>();
+        let mut s0 = flat.clone();
+        s0.extend_from_slice(&extra[..20]);
+        let mut s2 = extra[20..40].to_vec();
+        s2.extend_from_slice(&flat);
+        let scans = vec![
+            Scan::new(s0),
+            Scan::new(extra[8..32].iter().map(|&p| t1.inverse().apply(p)).collect()),
+            Scan::new(s2.iter().map(|&p| t2.inverse().apply(p)).collect()),
+        ];
+        let cfg = Config { overlap: 12, ransac_iters: 0, ..Config::default() };
+        let sol = solve(&scans, &cfg).unwrap();
+        assert_eq!(sol.transforms, vec![Transform::IDENTITY, t1, t2]);
+        assert_eq!(sol.failed.iter().map(|&(i, j, _)| (i, j)).collect::<Vec<_>>(), vec![(0, 2)]);
+    }
+}
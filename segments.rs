@@ -0,0 +1,249 @@
+//! Unscrambling segment displays whose wires got crossed (2021 day 8).
+//!
+//! A display lights a fixed set of segments for each glyph.  With the wires permuted, all we see is
+//! which wires are on; solving finds the permutation that makes every observed pattern a glyph.
+//! Segments and wires are bits of a `u32`, named `a`, `b`, ... in text.
+
+use std::fmt;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// Not the name of a segment on this display.
+    BadSegment(char),
+    /// No wiring explains the observations.
+    Contradiction,
+    /// More than one wiring explains the observations.
+    Ambiguous,
+    /// Decodes to no glyph.
+    UnknownPattern(String),
+    /// Segments are bits of a `u32`, so at most 32.
+    TooManySegments(usize),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::BadSegment(c) => write!(f, "no segment {:?}", c),
+            Error::Contradiction => write!(f, "no wiring fits the observations"),
+            Error::Ambiguous => write!(f, "more than one wiring fits the observations"),
+            Error::UnknownPattern(p) => write!(f, "{:?} isn't a glyph", p),
+            Error::TooManySegments(n) => write!(f, "{} segments, at most 32 supported", n),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[derive(Clone, Debug)]
+pub struct SegmentDisplay {
+    segments: usize,
+    glyphs: Vec<(char, u32)>,
+}
+
+/// Which segment each wire drives.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Wiring {
+    segment: Vec<usize>,
+}
+
+impl SegmentDisplay {
+    /// `glyphs` gives each symbol's lit segments by name.
+    pub fn new(segments: usize, glyphs: &[(char, &str)]) -> Result<Self, Error> {
+        if segments > 32 {
+            return Err(Error::TooManySegments(segments));
+        }
+        let mut display = Self { segments, glyphs: Vec::new() };
+        display.glyphs = glyphs.iter()
+            .map(|&(c, segs)| Ok((c, display.mask(segs)?)))
+            .collect::<Result<_, Error>>()?;
+        Ok(display)
+    }
+
+    /// ```text
+    ///  aaaa
+    /// b    c
+    /// b    c
+    ///  dddd
+    /// e    f
+    /// e    f
+    ///  gggg
+    /// ```
+    pub fn seven_segment() -> Self {
+        Self::new(7, &[
+            ('0', "abcefg"), ('1', "cf"), ('2', "acdeg"), ('3', "acdfg"), ('4', "bcdf"),
+            ('5', "abdfg"), ('6', "abdefg"), ('7', "acf"), ('8', "abcdefg"), ('9', "abcdfg"),
+        ]).unwrap()
+    }
+
+    /// Digits and a few symbols that use the diagonals and centre bars:
+    ///
+    /// ```text
+    ///  aaaaaaa
+    /// f h  i  j b
+    /// f  h i j  b
+    ///  ggg   kkk
+    /// e  n m l  c
+    /// e n  m  l c
+    ///  ddddddd
+    /// ```
+    pub fn fourteen_segment() -> Self {
+        Self::new(14, &[
+            ('0', "abcdefjn"), ('1', "bcj"), ('2', "abdegk"), ('3', "abcdk"), ('4', "bcfgk"),
+            ('5', "acdfgk"), ('6', "acdefgk"), ('7', "ajn"), ('8', "abcdefgk"), ('9', "abcdfgk"),
+            ('+', "gikm"), ('X', "hjln"), ('Y', "hjm"), ('T', "aim"), ('V', "efnj"),
+        ]).unwrap()
+    }
+
+    pub fn segments(&self) -> usize {
+        self.segments
+    }
+
+    /// Bitmask of named segments (or wires).
+    pub fn mask(&self, segs: &str) -> Result<u32, Error> {
+        segs.chars().try_fold(0, |acc, c| {
+            let i = (c as u32).wrapping_sub('a' as u32);
+            if (i as usize) < self.segments { Ok(acc | 1 << i) } else { Err(Error::BadSegment(c)) }
+        })
+    }
+
+    fn all(&self) -> u32 {
+        ((1u64 << self.segments) - 1) as u32
+    }
+
+    /// Narrow `cand[w]`, the segments wire `w` might drive, until nothing changes.  `false` if some
+    /// wire is left with nothing.
+    fn propagate(&self, observed: &[u32], cand: &mut [u32]) -> bool {
+        let all = self.all();
+        loop {
+            let before = cand.to_vec();
+            for &o in observed.iter() {
+                // Glyphs `o` could still be, and what that allows for wires on and off.
+                let (mut on, mut off, mut any) = (0, 0, false);
+                for &(_, g) in self.glyphs.iter().filter(|(_, g)| g.count_ones() == o.count_ones()) {
+                    let fits = (0..self.segments).all(|w| {
+                        let lit = if o & (1 << w) != 0 { g } else { all & !g };
+                        cand[w] & lit != 0
+                    });
+                    if fits {
+                        on |= g;
+                        off |= all & !g;
+                        any = true;
+                    }
+                }
+                if !any {
+                    return false;
+                }
+                for (w, c) in cand.iter_mut().enumerate() {
+                    *c &= if o & (1 << w) != 0 { on } else { off };
+                }
+            }
+            // A segment settled for one wire is out for the rest.
+            for w in 0..self.segments {
+                if cand[w].count_ones() == 1 {
+                    for v in (0..self.segments).filter(|&v| v != w) {
+                        cand[v] &= !cand[w];
+                    }
+                }
+            }
+            if cand.contains(&0) {
+                return false;
+            }
+            if cand == &before[..] {
+                return true;
+            }
+        }
+    }
+
+    /// Propagate, then branch on the least certain wire, collecting up to two wirings.
+    fn search(&self, observed: &[u32], mut cand: Vec<u32>, found: &mut Vec<Wiring>) {
+        if found.len() > 1 || !self.propagate(observed, &mut cand) {
+            return;
+        }
+        let w = match (0..self.segments).filter(|&w| cand[w].count_ones() > 1).min_by_key(|&w| cand[w].count_ones()) {
+            Some(w) => w,
+            None => {
+                let wiring = Wiring { segment: cand.iter().map(|c| c.trailing_zeros() as usize).collect() };
+                let glyphs = observed.iter().map(|&o| self.glyph(wiring.apply(o))).collect::<Option<Vec<_>>>();
+                if glyphs.is_some() {
+                    found.push(wiring);
+                }
+                return;
+            }
+        };
+        for s in (0..self.segments).filter(|s| cand[w] & (1 << s) != 0) {
+            let mut next = cand.clone();
+            next[w] = 1 << s;
+            self.search(observed, next, found);
+        }
+    }
+
+    /// The one wiring under which every observed pattern is a glyph.
+    pub fn solve(&self, observed: &[&str]) -> Result<Wiring, Error> {
+        let observed = observed.iter().map(|o| self.mask(o)).collect::<Result<Vec<_>, _>>()?;
+        let mut found = Vec::new();
+        self.search(&observed, vec![self.all(); self.segments], &mut found);
+        match found.len() {
+            0 => Err(Error::Contradiction),
+            1 => Ok(found.pop().unwrap()),
+            _ => Err(Error::Ambiguous),
+        }
+    }
+
+    fn glyph(&self, segs: u32) -> Option<char> {
+        self.glyphs.iter().find(|&&(_, g)| g == segs).map(|&(c, _)| c)
+    }
+
+    /// The glyph shown by wire pattern `pattern`.
+    pub fn decode(&self, wiring: &Wiring, pattern: &str) -> Result<char, Error> {
+        self.glyph(wiring.apply(self.mask(pattern)?)).ok_or_else(|| Error::UnknownPattern(pattern.to_string()))
+    }
+}
+
+impl Wiring {
+    /// Segments lit by the wires in `wires`.
+    pub fn apply(&self, wires: u32) -> u32 {
+        self.segment.iter().enumerate().filter(|(w, _)| wires & (1 << w) != 0).fold(0, |acc, (_, s)| acc | 1 << s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// How `display` looks with wire `w` driving segment `perm[w]`.
+    fn scramble(display: &SegmentDisplay, perm: &[usize]) -> Vec<(char, String)> {
+        display.glyphs.iter().map(|&(c, g)| {
+            let wires = (0..perm.len()).filter(|&w| g & (1 << perm[w]) != 0);
+            (c, wires.map(|w| (b'a' + w as u8) as char).collect())
+        })
+        .collect()
+    }
+
+    #[test]
+    fn displays() {
+        let seven = SegmentDisplay::seven_segment();
+        let line = "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab";
+        let wiring = seven.solve(&line.split(' ').collect::<Vec<_>>()).unwrap();
+        let out = ["cdfeb", "fcadb", "cdfeb", "cdbaf"].iter().map(|p| seven.decode(&wiring, p).unwrap()).collect::<String>();
+        assert_eq!(out, "5353");
+
+        let fourteen = SegmentDisplay::fourteen_segment();
+        let perm = [3, 12, 0, 7, 13, 5, 1, 9, 11, 2, 6, 4, 10, 8];
+        let seen = scramble(&fourteen, &perm);
+        let wiring = fourteen.solve(&seen.iter().map(|(_, p)| p.as_str()).collect::<Vec<_>>()).unwrap();
+        assert_eq!(wiring.segment, perm);
+        for (c, p) in seen.iter() {
+            assert_eq!(fourteen.decode(&wiring, p), Ok(*c));
+        }
+
+        assert_eq!(seven.solve(&["ab", "abc"]), Err(Error::Ambiguous));
+        assert_eq!(seven.solve(&["ab", "cd"]), Err(Error::Contradiction));
+        assert!(matches!(SegmentDisplay::new(33, &[]), Err(Error::TooManySegments(33))));
+        assert_eq!(seven.solve(&["abh"]), Err(Error::BadSegment('h')));
+        assert_eq!(seven.decode(&wiring_of(&seven), "ab"), Err(Error::UnknownPattern("ab".to_string())));
+    }
+
+    fn wiring_of(display: &SegmentDisplay) -> Wiring {
+        Wiring { segment: (0..display.segments()).collect() }
+    }
+}
@@ -0,0 +1,273 @@
+//! 2021 day 18 snailfish numbers.
+//!
+//! A number is stored flat: its regular numbers in left-to-right order, each tagged with how many
+//! pairs enclose it.  Reduction keeps nesting at most 4 deep, and the sum of two reduced numbers
+//! is at most 5 deep; at that depth the leftmost too-deep regular number is always the left half
+//! of an exploding pair, so no tree is needed.
+
+use rayon::prelude::*;
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign};
+use std::str::FromStr;
+
+/// Deepest nesting `FromStr` accepts; see the module docs.
+const MAX_DEPTH: u8 = 5;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+struct Elem {
+    val: u64,
+    depth: u8,
+}
+
+/// The empty number is the identity for `+`, so that `Sum` works on empty iterators.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct SnailNum {
+    elems: Vec<Elem>,
+}
+
+impl SnailNum {
+    /// Explode the leftmost pair nested inside four pairs, if any.
+    pub fn explode(&mut self) -> bool {
+        let i = match self.elems.iter().position(|e| e.depth > 4) {
+            Some(i) => i,
+            None => return false,
+        };
+        let (left, right) = (self.elems[i], self.elems[i + 1]);
+        debug_assert_eq!(left.depth, right.depth);
+        if i > 0 {
+            self.elems[i - 1].val += left.val;
+        }
+        if let Some(next) = self.elems.get_mut(i + 2) {
+            next.val += right.val;
+        }
+        self.elems[i] = Elem { val: 0, depth: left.depth - 1 };
+        self.elems.remove(i + 1);
+        true
+    }
+
+    /// Split the leftmost regular number of 10 or more, if any.
+    pub fn split(&mut self) -> bool {
+        let i = match self.elems.iter().position(|e| e.val >= 10) {
+            Some(i) => i,
+            None => return false,
+        };
+        let Elem { val, depth } = self.elems[i];
+        self.elems[i] = Elem { val: val / 2, depth: depth + 1 };
+        self.elems.insert(i + 1, Elem { val: val - val / 2, depth: depth + 1 });
+        true
+    }
+
+    pub fn reduce(&mut self) {
+        while self.explode() || self.split() {}
+    }
+
+    pub fn magnitude(&self) -> u64 {
+        fn mag(elems: &[Elem], i: &mut usize, depth: u8) -> u64 {
+            if elems[*i].depth == depth {
+                *i += 1;
+                return elems[*i - 1].val;
+            }
+            3 * mag(elems, i, depth + 1) + 2 * mag(elems, i, depth + 1)
+        }
+        if self.elems.is_empty() {
+            return 0;
+        }
+        mag(&self.elems, &mut 0, 0)
+    }
+
+    fn fmt_from(&self, f: &mut fmt::Formatter, i: &mut usize, depth: u8) -> fmt::Result {
+        if self.elems[*i].depth == depth {
+            *i += 1;
+            return write!(f, "{}", self.elems[*i - 1].val);
+        }
+        write!(f, "[")?;
+        self.fmt_from(f, i, depth + 1)?;
+        write!(f, ",")?;
+        self.fmt_from(f, i, depth + 1)?;
+        write!(f, "]")
+    }
+}
+
+impl AddAssign<&SnailNum> for SnailNum {
+    fn add_assign(&mut self, rhs: &SnailNum) {
+        if rhs.elems.is_empty() {
+            return;
+        }
+        if self.elems.is_empty() {
+            self.elems = rhs.elems.clone();
+            self.reduce();
+            return;
+        }
+        // Operands are reduced first to keep within MAX_DEPTH.
+        self.reduce();
+        let mut rhs = rhs.clone();
+        rhs.reduce();
+        self.elems.extend(rhs.elems);
+        for e in self.elems.iter_mut() {
+            e.depth += 1;
+        }
+        self.reduce();
+    }
+}
+
+impl Add for SnailNum {
+    type Output = SnailNum;
+
+    fn add(mut self, rhs: SnailNum) -> SnailNum {
+        self += &rhs;
+        self
+    }
+}
+
+impl Add for &SnailNum {
+    type Output = SnailNum;
+
+    fn add(self, rhs: &SnailNum) -> SnailNum {
+        let mut res = self.clone();
+        res += rhs;
+        res
+    }
+}
+
+impl Sum for SnailNum {
+    fn sum<It: Iterator<Item = SnailNum>>(iter: It) -> SnailNum {
+        iter.fold(SnailNum::default(), |acc, n| acc + n)
+    }
+}
+
+impl<'a> Sum<&'a SnailNum> for SnailNum {
+    fn sum<It: Iterator<Item = &'a SnailNum>>(iter: It) -> SnailNum {
+        iter.fold(SnailNum::default(), |mut acc, n| {
+            acc += n;
+            acc
+        })
+    }
+}
+
+impl fmt::Display for SnailNum {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.elems.is_empty() {
+            return Ok(());
+        }
+        self.fmt_from(f, &mut 0, 0)
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ParseError {
+    /// Byte offset into the input.
+    pub pos: usize,
+    pub msg: &'static str,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "column {}: {}", self.pos + 1, self.msg)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+    elems: Vec<Elem>,
+}
+
+impl<'a> Parser<'a> {
+    fn error<T>(&self, msg: &'static str) -> Result<T, ParseError> {
+        Err(ParseError { pos: self.pos, msg })
+    }
+
+    fn expect(&mut self, c: u8, msg: &'static str) -> Result<(), ParseError> {
+        if self.input.get(self.pos) != Some(&c) {
+            return self.error(msg);
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn expr(&mut self, depth: u8) -> Result<(), ParseError> {
+        match self.input.get(self.pos) {
+            Some(b'[') => {
+                if depth == MAX_DEPTH {
+                    return self.error("nested too deeply");
+                }
+                self.pos += 1;
+                self.expr(depth + 1)?;
+                self.expect(b',', "expected ','")?;
+                self.expr(depth + 1)?;
+                self.expect(b']', "expected ']'")
+            }
+            Some(c) if c.is_ascii_digit() => {
+                let start = self.pos;
+                let mut val = 0u64;
+                while let Some(c) = self.input.get(self.pos).filter(|c| c.is_ascii_digit()) {
+                    val = match val.checked_mul(10).and_then(|v| v.checked_add((c - b'0') as u64)) {
+                        Some(v) if v <= u32::MAX as u64 => v,
+                        _ => return Err(ParseError { pos: start, msg: "number too large" }),
+                    };
+                    self.pos += 1;
+                }
+                self.elems.push(Elem { val, depth });
+                Ok(())
+            }
+            _ => self.error("expected '[' or a number"),
+        }
+    }
+}
+
+impl FromStr for SnailNum {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<SnailNum, ParseError> {
+        let mut parser = Parser { input: s.as_bytes(), pos: 0, elems: Vec::new() };
+        parser.expr(0)?;
+        if parser.pos != s.len() {
+            return parser.error("trailing characters");
+        }
+        Ok(SnailNum { elems: parser.elems })
+    }
+}
+
+/// Largest magnitude of the sum of two different numbers from `nums`, in either order.
+pub fn max_pair_magnitude(nums: &[SnailNum]) -> Option<u64> {
+    (0..nums.len()).into_par_iter()
+        .flat_map_iter(|i| (0..nums.len()).filter(move |&j| j != i).map(move |j| (i, j)))
+        .map(|(i, j)| (&nums[i] + &nums[j]).magnitude())
+        .max()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn num(s: &str) -> SnailNum {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn sums() {
+        let nums = ["[1,1]", "[2,2]", "[3,3]", "[4,4]", "[5,5]", "[6,6]"].iter().map(|s| num(s)).collect::<Vec<_>>();
+        assert_eq!(nums[..4].iter().sum::<SnailNum>().to_string(), "[[[[1,1],[2,2]],[3,3]],[4,4]]");
+        assert_eq!(nums.iter().sum::<SnailNum>().to_string(), "[[[[5,0],[7,4]],[5,5]],[6,6]]");
+
+        let sum = num("[[[[4,3],4],4],[7,[[8,4],9]]]") + num("[1,1]");
+        assert_eq!(sum.to_string(), "[[[[0,7],4],[[7,8],[6,0]]],[8,1]]");
+        assert_eq!(num("[[1,2],[[3,4],5]]").magnitude(), 143);
+        assert_eq!(max_pair_magnitude(&nums[..2]), Some((&nums[1] + &nums[0]).magnitude()));
+        assert_eq!(max_pair_magnitude(&nums[..1]), None);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let err = |s: &str| s.parse::<SnailNum>().unwrap_err();
+        assert_eq!(err("[1,2"), ParseError { pos: 4, msg: "expected ']'" });
+        assert_eq!(err("[1;2]"), ParseError { pos: 2, msg: "expected ','" });
+        assert_eq!(err("[1,2]x"), ParseError { pos: 5, msg: "trailing characters" });
+        assert_eq!(err("[[[[[[1,2],3],4],5],6],7]"), ParseError { pos: 5, msg: "nested too deeply" });
+        assert_eq!(err("[99999999999,1]"), ParseError { pos: 1, msg: "number too large" });
+        assert_eq!(num("[[[[[1,2],3],4],5],6]").to_string(), "[[[[[1,2],3],4],5],6]");
+    }
+}
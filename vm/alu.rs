@@ -0,0 +1,720 @@
+//! The 2021 day 24 "ALU" instruction set: parser, interpreter, and a symbolic evaluator that
+//! derives the digit constraints of MONAD-style programs directly.
+
+use anyhow::{anyhow, Result};
+use std::fmt;
+
+use crate::debugger::{Debuggee, UndoRecord};
+use crate::decompile::{BinOp, Lifted, Op, Operand};
+
+// Register
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum R {
+    W,X,Y,Z,
+}
+pub type Im = i64;
+// Instr dst,src
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum I {
+    Inp(R),
+    Add(R,R),
+    AddI(R,Im),
+    Mul(R,R),
+    MulI(R,Im),
+    Div(R,R),
+    DivI(R,Im),
+    Mod(R,R),
+    ModI(R,Im),
+    Eql(R,R),
+    EqlI(R,Im),
+}
+
+fn parse_reg(input: &str) -> R {
+    let regc = input.chars().next().unwrap();
+    match regc {
+        'w' => R::W,
+        'x' => R::X,
+        'y' => R::Y,
+        'z' => R::Z,
+        _ => {
+            unreachable!("{}", regc);
+        }
+    }
+}
+
+enum RegOrImm {
+    Imm(Im),
+    Reg(R),
+}
+
+fn parse_regimm(input: &str) -> RegOrImm {
+    input.parse::<Im>()
+        .map(RegOrImm::Imm)
+        .unwrap_or_else(|_| RegOrImm::Reg(parse_reg(input)))
+}
+
+pub fn parse(input: &str) -> Vec<I> {
+    input.lines().map(|line| {
+        let mut words = line.split(' ');
+        let verb = words.next().unwrap();
+        //dbg!(line);
+        let dst = parse_regimm(words.next().unwrap());
+        let src = words.next().map(parse_regimm);
+
+        match (verb, dst, src) {
+            ("inp", RegOrImm::Reg(dst), None) => I::Inp(dst),
+            ("add", RegOrImm::Reg(dst), Some(RegOrImm::Reg(r))) => I::Add(dst, r),
+            ("add", RegOrImm::Reg(dst), Some(RegOrImm::Imm(i))) => I::AddI(dst, i),
+            ("mul", RegOrImm::Reg(dst), Some(RegOrImm::Reg(r))) => I::Mul(dst, r),
+            ("mul", RegOrImm::Reg(dst), Some(RegOrImm::Imm(i))) => I::MulI(dst, i),
+            ("div", RegOrImm::Reg(dst), Some(RegOrImm::Reg(r))) => I::Div(dst, r),
+            ("div", RegOrImm::Reg(dst), Some(RegOrImm::Imm(i))) => I::DivI(dst, i),
+            ("mod", RegOrImm::Reg(dst), Some(RegOrImm::Reg(r))) => I::Mod(dst, r),
+            ("mod", RegOrImm::Reg(dst), Some(RegOrImm::Imm(i))) => I::ModI(dst, i),
+            ("eql", RegOrImm::Reg(dst), Some(RegOrImm::Reg(r))) => I::Eql(dst, r),
+            ("eql", RegOrImm::Reg(dst), Some(RegOrImm::Imm(i))) => I::EqlI(dst, i),
+            _ => {
+                unreachable!();
+            }
+        }
+    }).collect()
+}
+
+pub struct Machine {
+    pub regs: [i64; 4],
+    pub pc: isize,
+    pub program: Vec<I>,
+    /// Pending inputs, consumed from the back.
+    pub inp: Vec<i64>,
+}
+
+impl Machine {
+    pub fn new(program: &[I]) -> Self {
+        Self {
+            regs: [0; 4],
+            pc: 0,
+            program: program.to_vec(),
+            inp: Vec::new(),
+        }
+    }
+
+    /// Execute the instruction at `pc`.
+    pub fn step(&mut self) {
+        match self.program[self.pc as usize] {
+            I::Inp(dst) => {
+                self.regs[dst as usize] = self.inp.pop().unwrap();
+            }
+            I::Add(dst, src) => {
+                self.regs[dst as usize] += self.regs[src as usize];
+            }
+            I::AddI(dst, src) => {
+                self.regs[dst as usize] += src;
+            }
+            I::Mul(dst, src) => {
+                self.regs[dst as usize] *= self.regs[src as usize];
+            }
+            I::MulI(dst, src) => {
+                self.regs[dst as usize] *= src;
+            }
+            I::Div(dst, src) => {
+                self.regs[dst as usize] /= self.regs[src as usize];
+            }
+            I::DivI(dst, src) => {
+                self.regs[dst as usize] /= src;
+            }
+            I::Mod(dst, src) => {
+                self.regs[dst as usize] %= self.regs[src as usize];
+            }
+            I::ModI(dst, src) => {
+                self.regs[dst as usize] %= src;
+            }
+            I::Eql(dst, src) => {
+                self.regs[dst as usize] = if self.regs[dst as usize] == self.regs[src as usize] { 1 } else { 0 };
+            }
+            I::EqlI(dst, src) => {
+                self.regs[dst as usize] = if self.regs[dst as usize] == src { 1 } else { 0 };
+            }
+        };
+        self.pc += 1;
+    }
+
+    pub fn run_until(&mut self, endpc: isize) {
+        //let endpc = self.program.len() as isize;
+        while self.pc < endpc {
+            self.step();
+        }
+    }
+
+    /// Run the whole program on a model number (most significant digit is read first) and return
+    /// the final `z`.
+    pub fn run_model_number(&mut self, digits: &[i64]) -> i64 {
+        self.regs = [0; 4];
+        self.pc = 0;
+        self.inp = digits.iter().rev().copied().collect();
+        self.run_until(self.program.len() as isize);
+        self.regs[R::Z as usize]
+    }
+}
+
+impl Debuggee for Machine {
+    fn reg_names(&self) -> &[&'static str] {
+        &["w", "x", "y", "z"]
+    }
+
+    fn regs(&self) -> &[i64] {
+        &self.regs
+    }
+
+    fn pc(&self) -> isize {
+        self.pc
+    }
+
+    fn program_len(&self) -> usize {
+        self.program.len()
+    }
+
+    fn describe(&self, pc: isize) -> String {
+        format!("{:?}", self.program[pc as usize])
+    }
+
+    fn step_recorded(&mut self) -> std::result::Result<UndoRecord, String> {
+        let pc = self.pc;
+        let inst = self.program[pc as usize];
+        let (dst, src) = match inst {
+            I::Inp(dst) => (dst, None),
+            I::Add(d, s) | I::Mul(d, s) | I::Div(d, s) | I::Mod(d, s) | I::Eql(d, s) => {
+                (d, Some(self.regs[s as usize]))
+            }
+            I::AddI(d, i) | I::MulI(d, i) | I::DivI(d, i) | I::ModI(d, i) | I::EqlI(d, i) => (d, Some(i)),
+        };
+        let a = self.regs[dst as usize];
+
+        // The puzzle defines these as crashing the ALU.
+        match (inst, src) {
+            (I::Inp(_), _) if self.inp.is_empty() => return Err("out of input".to_owned()),
+            (I::Div(..), Some(0)) | (I::DivI(..), Some(0)) => return Err("division by zero".to_owned()),
+            (I::Mod(..), Some(b)) | (I::ModI(..), Some(b)) if a < 0 || b <= 0 => {
+                return Err(format!("{} mod {}", a, b));
+            }
+            _ => {}
+        }
+
+        let input = match inst {
+            I::Inp(_) => self.inp.last().copied(),
+            _ => None,
+        };
+        self.step();
+        Ok(UndoRecord { pc, reg: Some((dst as usize, a)), input, })
+    }
+
+    fn undo(&mut self, rec: &UndoRecord) {
+        if let Some((r, v)) = rec.reg {
+            self.regs[r] = v;
+        }
+        if let Some(i) = rec.input {
+            self.inp.push(i);
+        }
+        self.pc = rec.pc;
+    }
+}
+
+/// Translate to the decompiler's IR.
+pub fn lift(program: &[I]) -> Lifted {
+    let r = |reg: R| reg as usize;
+    let ops = program.iter().map(|inst| match *inst {
+        I::Inp(dst) => Op::Input(r(dst)),
+        I::Add(dst, src) => Op::Bin(BinOp::Add, r(dst), Operand::Reg(r(src))),
+        I::AddI(dst, src) => Op::Bin(BinOp::Add, r(dst), Operand::Imm(src)),
+        I::Mul(dst, src) => Op::Bin(BinOp::Mul, r(dst), Operand::Reg(r(src))),
+        I::MulI(dst, src) => Op::Bin(BinOp::Mul, r(dst), Operand::Imm(src)),
+        I::Div(dst, src) => Op::Bin(BinOp::Div, r(dst), Operand::Reg(r(src))),
+        I::DivI(dst, src) => Op::Bin(BinOp::Div, r(dst), Operand::Imm(src)),
+        I::Mod(dst, src) => Op::Bin(BinOp::Mod, r(dst), Operand::Reg(r(src))),
+        I::ModI(dst, src) => Op::Bin(BinOp::Mod, r(dst), Operand::Imm(src)),
+        I::Eql(dst, src) => Op::Bin(BinOp::Eql, r(dst), Operand::Reg(r(src))),
+        I::EqlI(dst, src) => Op::Bin(BinOp::Eql, r(dst), Operand::Imm(src)),
+    }).collect();
+
+    Lifted {
+        reg_names: vec!["w", "x", "y", "z"],
+        ops,
+        // MONAD validity is decided by z alone.
+        live_out: vec![R::Z as usize],
+    }
+}
+
+/// Model number digits are `1..=9`.
+const DIGIT_MIN: i64 = 1;
+const DIGIT_MAX: i64 = 9;
+
+/// Symbolic register value.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Sym {
+    Const(i64),
+    /// `d[idx] + off`, where `d[idx]` is the `idx`th input digit.
+    Lin(usize, i64),
+    /// A number in base `base`, represented as a stack of digits (least significant last).  Each
+    /// digit is a `Const`, `Lin` or `Opaque` known to lie within `0..base`.  This is how MONAD
+    /// programs use `z`: `mul z 26` pushes, `div z 26` pops and `mod z 26` peeks.
+    Stack { base: i64, digits: Vec<Sym> },
+    /// Unknown expression, bounded by the inclusive interval `[lo, hi]`.
+    Opaque(i64, i64),
+}
+
+impl Sym {
+    /// Inclusive range of values this expression can take.
+    pub fn range(&self) -> (i64, i64) {
+        match self {
+            Sym::Const(c) => (*c, *c),
+            Sym::Lin(_, off) => (DIGIT_MIN + off, DIGIT_MAX + off),
+            Sym::Stack { base, digits } => {
+                let fold = |pick: fn((i64, i64)) -> i64| digits.iter().fold(0i64, |acc, d| {
+                    acc.saturating_mul(*base).saturating_add(pick(d.range()))
+                });
+                (fold(|r| r.0), fold(|r| r.1))
+            }
+            Sym::Opaque(lo, hi) => (*lo, *hi),
+        }
+    }
+
+    fn opaque(&self) -> Sym {
+        let (lo, hi) = self.range();
+        if lo == hi {
+            Sym::Const(lo)
+        } else {
+            Sym::Opaque(lo, hi)
+        }
+    }
+
+    /// View this value as a base-`base` digit stack, if every digit can be shown to be in range.
+    fn as_digits(&self, base: i64) -> Option<Vec<Sym>> {
+        match self {
+            Sym::Const(0) => Some(Vec::new()),
+            Sym::Stack { base: b, digits } if *b == base => Some(digits.clone()),
+            Sym::Stack { .. } => None,
+            _ => {
+                let (lo, hi) = self.range();
+                if lo >= 0 && hi < base {
+                    Some(vec![self.clone()])
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    fn from_digits(base: i64, mut digits: Vec<Sym>) -> Sym {
+        match digits.len() {
+            0 => Sym::Const(0),
+            1 => digits.pop().unwrap(),
+            _ => Sym::Stack { base, digits },
+        }
+    }
+
+    fn add(&self, other: &Sym) -> Sym {
+        match (self, other) {
+            (Sym::Const(a), Sym::Const(b)) => Sym::Const(a + b),
+            (x, Sym::Const(0)) | (Sym::Const(0), x) => x.clone(),
+            (Sym::Lin(i, off), Sym::Const(c)) | (Sym::Const(c), Sym::Lin(i, off)) => {
+                Sym::Lin(*i, off + c)
+            }
+            (Sym::Stack { base, digits }, y) => {
+                // Adding into the least significant digit is exact as long as it doesn't carry.
+                let mut digits = digits.clone();
+                let top = digits.pop().unwrap().add(y);
+                let (lo, hi) = top.range();
+                if lo >= 0 && hi < *base {
+                    digits.push(top);
+                    Sym::Stack { base: *base, digits }
+                } else {
+                    let (lo2, hi2) = y.range();
+                    let (lo1, hi1) = self.range();
+                    Sym::Opaque(lo1.saturating_add(lo2), hi1.saturating_add(hi2))
+                }
+            }
+            _ => {
+                let (lo1, hi1) = self.range();
+                let (lo2, hi2) = other.range();
+                Sym::Opaque(lo1.saturating_add(lo2), hi1.saturating_add(hi2))
+            }
+        }
+    }
+
+    fn mul(&self, other: &Sym) -> Sym {
+        match (self, other) {
+            (Sym::Const(a), Sym::Const(b)) => Sym::Const(a * b),
+            (_, Sym::Const(0)) | (Sym::Const(0), _) => Sym::Const(0),
+            (x, Sym::Const(1)) | (Sym::Const(1), x) => x.clone(),
+            (Sym::Stack { base, digits }, Sym::Const(c)) if c == base => {
+                let mut digits = digits.clone();
+                digits.push(Sym::Const(0));
+                Sym::Stack { base: *base, digits }
+            }
+            (x, Sym::Const(c)) if *c > 1 && x.as_digits(*c).is_some() => {
+                let mut digits = x.as_digits(*c).unwrap();
+                digits.push(Sym::Const(0));
+                Sym::from_digits(*c, digits)
+            }
+            _ => {
+                let (lo1, hi1) = self.range();
+                let (lo2, hi2) = other.range();
+                let corners = [
+                    lo1.saturating_mul(lo2),
+                    lo1.saturating_mul(hi2),
+                    hi1.saturating_mul(lo2),
+                    hi1.saturating_mul(hi2),
+                ];
+                Sym::Opaque(*corners.iter().min().unwrap(), *corners.iter().max().unwrap())
+            }
+        }
+    }
+
+    fn div(&self, other: &Sym) -> Result<Sym> {
+        match (self, other) {
+            (_, Sym::Const(0)) => Err(anyhow!("Division by zero")),
+            (Sym::Const(a), Sym::Const(b)) => Ok(Sym::Const(a / b)),
+            (x, Sym::Const(1)) => Ok(x.clone()),
+            (x, Sym::Const(c)) if *c > 1 => {
+                if let Some(mut digits) = x.as_digits(*c) {
+                    digits.pop();
+                    return Ok(Sym::from_digits(*c, digits));
+                }
+                let (lo, hi) = x.range();
+                Ok(Sym::Opaque(lo / c, hi / c).opaque())
+            }
+            _ => {
+                let (lo, hi) = self.range();
+                let (dlo, dhi) = other.range();
+                if dlo <= 0 && dhi >= 0 {
+                    return Err(anyhow!("Division by possibly-zero {:?}", other));
+                }
+                let m = std::cmp::max(lo.saturating_abs(), hi.saturating_abs());
+                Ok(Sym::Opaque(-m, m))
+            }
+        }
+    }
+
+    fn rem(&self, other: &Sym) -> Result<Sym> {
+        match (self, other) {
+            (Sym::Const(a), Sym::Const(b)) if *b > 0 && *a >= 0 => Ok(Sym::Const(a % b)),
+            (x, Sym::Const(c)) if *c > 0 => {
+                if x.range().0 < 0 {
+                    return Err(anyhow!("mod of possibly-negative {:?}", x));
+                }
+                if let Some(digits) = x.as_digits(*c) {
+                    return Ok(digits.last().cloned().unwrap_or(Sym::Const(0)));
+                }
+                Ok(Sym::Opaque(0, std::cmp::min(c - 1, x.range().1)).opaque())
+            }
+            _ => Err(anyhow!("mod with non-positive or non-constant divisor {:?}", other)),
+        }
+    }
+}
+
+impl fmt::Display for Sym {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Sym::Const(c) => write!(f, "{}", c),
+            Sym::Lin(i, 0) => write!(f, "d[{}]", i),
+            Sym::Lin(i, off) if *off < 0 => write!(f, "d[{}] - {}", i, -off),
+            Sym::Lin(i, off) => write!(f, "d[{}] + {}", i, off),
+            Sym::Stack { base, digits } => {
+                write!(f, "[")?;
+                for (i, d) in digits.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", d)?;
+                }
+                write!(f, "]_{}", base)
+            }
+            Sym::Opaque(lo, hi) => write!(f, "?[{}..={}]", lo, hi),
+        }
+    }
+}
+
+/// A (dis)equality between two digit expressions (`Const` or `Lin`) that must hold for the
+/// program to end with `z == 0`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Constraint {
+    pub lhs: Sym,
+    pub rhs: Sym,
+    pub eq: bool,
+}
+
+impl Constraint {
+    fn inputs(&self) -> Vec<usize> {
+        let mut res = Vec::new();
+        for s in [&self.lhs, &self.rhs].iter() {
+            if let Sym::Lin(i, _) = s {
+                if !res.contains(i) {
+                    res.push(*i);
+                }
+            }
+        }
+        res
+    }
+
+    fn holds(&self, digits: &[i64]) -> bool {
+        let val = |s: &Sym| match s {
+            Sym::Const(c) => *c,
+            Sym::Lin(i, off) => digits[*i] + off,
+            _ => unreachable!(),
+        };
+        (val(&self.lhs) == val(&self.rhs)) == self.eq
+    }
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Present pairwise constraints as `d[later] == d[earlier] + k`, which is how one would
+        // write them down by hand.
+        let op = if self.eq { "==" } else { "!=" };
+        match (&self.lhs, &self.rhs) {
+            (Sym::Lin(i, a), Sym::Lin(j, b)) => {
+                let (i, a, j, b) = if i > j { (i, a, j, b) } else { (j, b, i, a) };
+                write!(f, "d[{}] {} {}", i, op, Sym::Lin(*j, b - a))
+            }
+            (Sym::Lin(i, a), Sym::Const(c)) | (Sym::Const(c), Sym::Lin(i, a)) => {
+                write!(f, "d[{}] {} {}", i, op, c - a)
+            }
+            (l, r) => write!(f, "{} {} {}", l, op, r),
+        }
+    }
+}
+
+/// Result of symbolically executing an ALU program.
+///
+/// The program accepts exactly the model numbers satisfying all `constraints` of any one of the
+/// `paths`.
+#[derive(Clone, Debug)]
+pub struct Analysis {
+    pub ninputs: usize,
+    pub paths: Vec<Vec<Constraint>>,
+}
+
+/// Symbolically execute `program`, forking on every `eql` whose outcome the interval analysis
+/// can't decide, and keep the paths that can end with `z == 0`.
+pub fn analyze(program: &[I]) -> Result<Analysis> {
+    let ninputs = program.iter().filter(|i| matches!(i, I::Inp(_))).count();
+    let mut paths = Vec::new();
+    let regs = [Sym::Const(0), Sym::Const(0), Sym::Const(0), Sym::Const(0)];
+    analyze_from(program, 0, regs, 0, &mut Vec::new(), ninputs, &mut paths)?;
+    Ok(Analysis { ninputs, paths, })
+}
+
+fn analyze_from(
+    program: &[I],
+    mut pc: usize,
+    mut regs: [Sym; 4],
+    mut ninp: usize,
+    constraints: &mut Vec<Constraint>,
+    ninputs: usize,
+    paths: &mut Vec<Vec<Constraint>>,
+    ) -> Result<()>
+{
+    while pc < program.len() {
+        let (dst, src) = match program[pc] {
+            I::Inp(dst) => {
+                regs[dst as usize] = Sym::Lin(ninp, 0);
+                ninp += 1;
+                pc += 1;
+                continue;
+            }
+            I::Add(d, s) | I::Mul(d, s) | I::Div(d, s) | I::Mod(d, s) | I::Eql(d, s) => {
+                (d, regs[s as usize].clone())
+            }
+            I::AddI(d, i) | I::MulI(d, i) | I::DivI(d, i) | I::ModI(d, i) | I::EqlI(d, i) => {
+                (d, Sym::Const(i))
+            }
+        };
+        let a = &regs[dst as usize];
+
+        let res = match program[pc] {
+            I::Add(..) | I::AddI(..) => a.add(&src),
+            I::Mul(..) | I::MulI(..) => a.mul(&src),
+            I::Div(..) | I::DivI(..) => a.div(&src)?,
+            I::Mod(..) | I::ModI(..) => a.rem(&src)?,
+            I::Eql(..) | I::EqlI(..) => {
+                let (lo1, hi1) = a.range();
+                let (lo2, hi2) = src.range();
+                if hi1 < lo2 || hi2 < lo1 {
+                    // Disjoint intervals: never equal.
+                    Sym::Const(0)
+                } else if lo1 == hi1 && lo2 == hi2 || *a == src && matches!(a, Sym::Lin(..) | Sym::Const(_)) {
+                    // Only exact forms compare equal as values; two `Opaque`s with the same range
+                    // needn't be.
+                    Sym::Const(1)
+                } else {
+                    // Constraints are only expressed over single digits.
+                    let simple = |s: &Sym| matches!(s, Sym::Lin(..) | Sym::Const(_));
+                    if !simple(a) || !simple(&src) {
+                        return Err(anyhow!("pc {}: can't decide {} == {}", pc, a, src));
+                    }
+
+                    // Fork.  Follow each branch only if its constraints remain satisfiable.
+                    for eq in [true, false] {
+                        constraints.push(Constraint { lhs: a.clone(), rhs: src.clone(), eq, });
+                        if solve(ninputs, constraints, true).is_some() {
+                            let mut regs2 = regs.clone();
+                            regs2[dst as usize] = Sym::Const(eq as i64);
+                            analyze_from(program, pc + 1, regs2, ninp, constraints, ninputs, paths)?;
+                        }
+                        constraints.pop();
+                    }
+                    return Ok(());
+                }
+            }
+            I::Inp(_) => unreachable!(),
+        };
+        regs[dst as usize] = res;
+        pc += 1;
+    }
+
+    let (lo, hi) = regs[R::Z as usize].range();
+    if lo <= 0 && hi >= 0 {
+        if lo != 0 || hi != 0 {
+            return Err(anyhow!("Final z {} not fully determined", regs[R::Z as usize]));
+        }
+        paths.push(constraints.clone());
+    }
+    Ok(())
+}
+
+/// Find the largest (or smallest) digit string satisfying `constraints`, by arc consistency and
+/// backtracking in most-significant-first order.
+fn solve(ninputs: usize, constraints: &[Constraint], largest: bool) -> Option<Vec<i64>> {
+    let full = (DIGIT_MIN..=DIGIT_MAX).collect::<Vec<_>>();
+    let mut domains = vec![full; ninputs];
+    if !propagate(&mut domains, constraints) {
+        return None;
+    }
+    solve_from(0, &mut domains, constraints, largest)
+}
+
+fn solve_from(idx: usize, domains: &mut [Vec<i64>], constraints: &[Constraint], largest: bool) -> Option<Vec<i64>> {
+    if idx == domains.len() {
+        return Some(domains.iter().map(|d| d[0]).collect());
+    }
+    let mut candidates = domains[idx].clone();
+    if largest {
+        candidates.reverse();
+    }
+    for v in candidates {
+        let mut trial = domains.to_vec();
+        trial[idx] = vec![v];
+        if propagate(&mut trial, constraints) {
+            if let Some(res) = solve_from(idx + 1, &mut trial, constraints, largest) {
+                return Some(res);
+            }
+        }
+    }
+    None
+}
+
+/// Prune domains until every constraint is arc consistent.  Returns false if some domain empties.
+fn propagate(domains: &mut [Vec<i64>], constraints: &[Constraint]) -> bool {
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for c in constraints.iter() {
+            let inputs = c.inputs();
+            for (k, &i) in inputs.iter().enumerate() {
+                let other = inputs.get(1 - k).copied();
+                let mut digits = vec![0; domains.len()];
+                let before = domains[i].len();
+                let kept = domains[i].iter().copied().filter(|&v| {
+                    digits[i] = v;
+                    match other {
+                        None => c.holds(&digits),
+                        Some(j) => domains[j].iter().any(|&w| {
+                            digits[j] = w;
+                            c.holds(&digits)
+                        }),
+                    }
+                }).collect::<Vec<_>>();
+                if kept.is_empty() {
+                    return false;
+                }
+                if kept.len() != before {
+                    domains[i] = kept;
+                    changed = true;
+                }
+            }
+        }
+    }
+    true
+}
+
+impl Analysis {
+    /// Largest (`largest == true`) or smallest accepted model number, as digits.
+    pub fn solve(&self, largest: bool) -> Option<Vec<i64>> {
+        let solutions = self.paths.iter()
+            .filter_map(|path| solve(self.ninputs, path, largest));
+        if largest {
+            solutions.max()
+        } else {
+            solutions.min()
+        }
+    }
+}
+
+impl fmt::Display for Analysis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, path) in self.paths.iter().enumerate() {
+            writeln!(f, "Path {}:", i)?;
+            for c in path.iter() {
+                writeln!(f, "  {}", c)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Source of one MONAD block: pushes `d + b` if `top + a != d`, after popping if `div` is 26.
+/// Handy for building test programs.
+pub fn monad_block(div: i64, a: i64, b: i64) -> String {
+    format!("inp w\nmul x 0\nadd x z\nmod x 26\ndiv z {}\nadd x {}\neql x w\neql x 0\n\
+             mul y 0\nadd y 25\nmul y x\nadd y 1\nmul z y\nmul y 0\nadd y w\nadd y {}\n\
+             mul y x\nadd z y\n", div, a, b)
+}
+
+/// Concatenate decimal digits into a number.
+pub fn digits_to_num(digits: &[i64]) -> i64 {
+    digits.iter().fold(0, |acc, d| acc * 10 + d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn monad_constraints() {
+        // d[0] pushed with +4; d[1] pushed with +2; d[2] pops d[1] + 2 - 5; d[3] pops d[0] + 4 - 1.
+        let block = monad_block;
+        let text = [block(1, 12, 4), block(1, 11, 2), block(26, -5, 9), block(26, -1, 7)].concat();
+        let program = parse(&text);
+        let analysis = analyze(&program).unwrap();
+
+        assert_eq!(analysis.paths.len(), 1);
+        let desc = analysis.paths[0].iter().map(|c| format!("{}", c)).collect::<Vec<_>>();
+        assert_eq!(desc, ["d[2] == d[1] - 3", "d[3] == d[0] + 3"]);
+
+        let max = analysis.solve(true).unwrap();
+        let min = analysis.solve(false).unwrap();
+        assert_eq!(digits_to_num(&max), 6969);
+        assert_eq!(digits_to_num(&min), 1414);
+
+        let mut m = Machine::new(&program);
+        assert_eq!(m.run_model_number(&max), 0);
+        assert_eq!(m.run_model_number(&min), 0);
+        assert_ne!(m.run_model_number(&[6, 9, 6, 7]), 0);
+    }
+
+    #[test]
+    fn opaque_not_equal() {
+        // Two unrelated products of digits, with the same range.
+        let program = parse("inp x\ninp y\nmul x y\ninp z\ninp w\nmul z w\neql x z\nmul x 0\nadd z x\nmul z 0\n");
+        assert!(analyze(&program).is_err());
+    }
+}
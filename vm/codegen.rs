@@ -0,0 +1,210 @@
+//! Ahead-of-time compilation of lifted VM programs to Rust source, so that rustc's optimizer can
+//! have a go at them.  `build.rs` uses this to embed the cached puzzle inputs as `aoc::aot`.
+
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use crate::decompile::{BinOp, Decompiler, Lifted, Op, Operand};
+
+pub struct Options {
+    pub name: String,
+    /// Emit a `const fn`.
+    pub const_fn: bool,
+    /// Count executed multiplications (2017 day 23 part 1); otherwise the count returned is 0.
+    pub count_muls: bool,
+}
+
+/// Rust type of the generated function, for a program with `nregs` registers.
+pub fn fn_type(nregs: usize) -> String {
+    format!("fn([i64; {}], &[i64]) -> ([i64; {}], u64)", nregs, nregs)
+}
+
+/// Generate `fn name(regs: [i64; N], input: &[i64]) -> ([i64; N], u64)`, returning the final
+/// registers and the multiplication count.
+///
+/// Straight-line programs compile to straight-line code.  Otherwise the function dispatches on
+/// basic blocks in a `loop { match pc { .. } }`; computed jumps make every instruction a block.
+pub fn generate(prog: &Lifted, opts: &Options) -> String {
+    let names = &prog.reg_names;
+    let n = prog.ops.len();
+    for reserved in ["regs", "input", "inp", "pc", "muls"].iter() {
+        assert!(!names.contains(reserved));
+    }
+
+    let dynamic = prog.ops.iter().any(|op| matches!(op, Op::Jnz(_, Operand::Reg(_))));
+    let has_jumps = prog.ops.iter().any(|op| matches!(op, Op::Jnz(..)));
+    let starts = if dynamic {
+        (0..n).collect::<BTreeSet<_>>()
+    } else {
+        Decompiler::new(prog).blocks.iter().map(|b| b.start).collect()
+    };
+
+    let mut res = String::new();
+    writeln!(res, "#[allow(unused_mut, unused_variables, unused_assignments, unreachable_code)]").unwrap();
+    writeln!(res, "pub {}fn {}(regs: [i64; {}], input: &[i64]) -> ([i64; {}], u64) {{",
+             if opts.const_fn { "const " } else { "" }, opts.name, names.len(), names.len()).unwrap();
+    let regs = names.iter().map(|n| format!("mut {}", n)).collect::<Vec<_>>().join(", ");
+    writeln!(res, "    let [{}] = regs;", regs).unwrap();
+    writeln!(res, "    let mut inp = 0usize;").unwrap();
+    writeln!(res, "    let mut muls = 0u64;").unwrap();
+
+    let operand = |o: Operand| match o {
+        Operand::Reg(r) => names[r].to_owned(),
+        Operand::Imm(i) => format!("{}i64", i),
+    };
+    // Statement(s) for the jump `Jnz(cond, off)` at `pc`.
+    let jump = |pc: usize, cond: Operand, off: Operand| -> String {
+        let target = match off {
+            Operand::Imm(off) => {
+                let t = pc as i64 + off;
+                if t < 0 || t >= n as i64 {
+                    "break;".to_owned()
+                } else {
+                    format!("pc = {}; continue;", t)
+                }
+            }
+            Operand::Reg(r) => format!(
+                "let t = {}i64 + {}; if t < 0 || t >= {}i64 {{ break; }} pc = t as usize; continue;",
+                pc, names[r], n),
+        };
+        match cond {
+            Operand::Imm(0) => String::new(),
+            Operand::Imm(_) => target,
+            Operand::Reg(r) => format!("if {} != 0 {{ {} }}", names[r], target),
+        }
+    };
+
+    // No fallthrough after an unconditional jump.
+    let jumps_away = |pc: usize| matches!(prog.ops[pc], Op::Jnz(Operand::Imm(c), _) if c != 0);
+
+    let indent = if has_jumps { "                " } else { "    " };
+    let mut body = String::new();
+    for pc in 0..n {
+        if has_jumps && starts.contains(&pc) {
+            if pc > 0 {
+                if !jumps_away(pc - 1) {
+                    writeln!(body, "{}pc = {};", indent, pc).unwrap();
+                }
+                writeln!(body, "            }}").unwrap();
+            }
+            writeln!(body, "            {} => {{", pc).unwrap();
+        }
+        let stmt = match prog.ops[pc] {
+            Op::Input(d) => format!("{} = input[inp]; inp += 1;", names[d]),
+            Op::Set(d, s) => format!("{} = {};", names[d], operand(s)),
+            Op::Bin(op, d, s) => {
+                let (dst, src) = (names[d], operand(s));
+                let mut stmt = match op {
+                    BinOp::Add => format!("{} = {}.wrapping_add({});", dst, dst, src),
+                    BinOp::Sub => format!("{} = {}.wrapping_sub({});", dst, dst, src),
+                    BinOp::Mul => format!("{} = {}.wrapping_mul({});", dst, dst, src),
+                    BinOp::Div => format!("{} = {} / {};", dst, dst, src),
+                    BinOp::Mod => format!("{} = {} % {};", dst, dst, src),
+                    BinOp::Eql => format!("{} = ({} == {}) as i64;", dst, dst, src),
+                    BinOp::Ne => format!("{} = ({} != {}) as i64;", dst, dst, src),
+                };
+                if op == BinOp::Mul && opts.count_muls {
+                    stmt.push_str(" muls += 1;");
+                }
+                stmt
+            }
+            Op::Jnz(cond, off) => jump(pc, cond, off),
+        };
+        if !stmt.is_empty() {
+            writeln!(body, "{}{}", indent, stmt).unwrap();
+        }
+    }
+
+    if has_jumps {
+        writeln!(res, "    let mut pc = 0usize;").unwrap();
+        writeln!(res, "    loop {{").unwrap();
+        writeln!(res, "        match pc {{").unwrap();
+        res.push_str(&body);
+        if n == 0 || !jumps_away(n - 1) {
+            writeln!(res, "{}break;", indent).unwrap();
+        }
+        writeln!(res, "            }}").unwrap();
+        writeln!(res, "            _ => break,").unwrap();
+        writeln!(res, "        }}").unwrap();
+        writeln!(res, "    }}").unwrap();
+    } else {
+        res.push_str(&body);
+    }
+    writeln!(res, "    ([{}], muls)", names.join(", ")).unwrap();
+    writeln!(res, "}}").unwrap();
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{alu, coproc};
+    use std::process::Command;
+
+    #[test]
+    fn loop_dispatch() {
+        let program = coproc::lift(&coproc::parse("set b 3\nsub b 1\nmul c b\njnz b -2\njnz 1 5"));
+        let opts = Options { name: "f".to_owned(), const_fn: true, count_muls: true };
+        let src = generate(&program, &opts);
+        assert!(src.contains("pub const fn f(regs: [i64; 8], input: &[i64]) -> ([i64; 8], u64) {"));
+        assert!(src.contains("            1 => {
+                b = b.wrapping_sub(1i64);
+                c = c.wrapping_mul(b); muls += 1;
+                if b != 0 { pc = 1; continue; }
+                pc = 4;
+            }
+            4 => {
+                break;
+            }
+"));
+    }
+
+    #[test]
+    fn straight_line() {
+        let program = alu::lift(&alu::parse("inp w\nadd z w\nmod z 2\neql z 0"));
+        let opts = Options { name: "f".to_owned(), const_fn: false, count_muls: false };
+        assert!(generate(&program, &opts).ends_with("    w = input[inp]; inp += 1;
+    z = z.wrapping_add(w);
+    z = z % 2i64;
+    z = (z == 0i64) as i64;
+    ([w, x, y, z], muls)
+}
+"));
+    }
+
+    /// Compile the generated functions with rustc and check that they end up in the same state
+    /// as the interpreters.
+    #[test]
+    fn compiled_matches_interpreter() {
+        let coproc_src = "set a 3\nset c 1\nset d -3\nsub b -2\nmul c b\nsub a 1\njnz a d\nmul c c";
+        let alu_src = "inp w\nadd x w\nmod x 3\ninp y\nmul y x\nadd z y\ndiv z 2\neql z 0\nadd z w";
+        let input = [7, 5];
+
+        let mut src = String::new();
+        let opts = Options { name: "coproc".to_owned(), const_fn: false, count_muls: true };
+        src.push_str(&generate(&coproc::lift(&coproc::parse(coproc_src)), &opts));
+        let opts = Options { name: "alu".to_owned(), const_fn: true, count_muls: false };
+        src.push_str(&generate(&alu::lift(&alu::parse(alu_src)), &opts));
+        src.push_str(&format!("fn main() {{\n    println!(\"{{:?}}\", coproc([0; 8], &[]));\n    \
+                               println!(\"{{:?}}\", alu([0; 4], &{:?}));\n}}\n", input));
+
+        let dir = std::env::temp_dir().join(format!("aoc-codegen-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (main, exe) = (dir.join("main.rs"), dir.join("main"));
+        std::fs::write(&main, &src).unwrap();
+        let rustc = std::env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+        let status = Command::new(rustc).arg("--edition=2018").arg(&main).arg("-o").arg(&exe).status().unwrap();
+        assert!(status.success(), "generated source doesn't compile:\n{}", src);
+        let out = Command::new(&exe).output().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let mut cm = coproc::Machine::new(&coproc::parse(coproc_src));
+        cm.run();
+        let program = alu::parse(alu_src);
+        let mut am = alu::Machine::new(&program);
+        am.inp = input.iter().rev().copied().collect();
+        am.run_until(program.len() as isize);
+        assert_eq!(String::from_utf8(out.stdout).unwrap(),
+                   format!("{:?}\n{:?}\n", (cm.regs, cm.muls), (am.regs, 0u64)));
+    }
+}
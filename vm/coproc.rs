@@ -0,0 +1,245 @@
+//! The 2017 day 23 "coprocessor" instruction set: parser and interpreter.
+
+use crate::debugger::{Debuggee, UndoRecord};
+use crate::decompile::{BinOp, Lifted, Op, Operand};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Reg {
+    A,B,C,D,E,F,G,H,
+}
+pub type Imm = i64;
+// Dst, Src
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Inst {
+    Set(Reg, Reg),
+    SetImm(Reg, Imm),
+    Sub(Reg, Reg),
+    SubImm(Reg, Imm),
+    Mul(Reg, Reg),
+    MulImm(Reg, Imm),
+    Jnz(Reg, Reg),
+    JnzImm(Reg, Imm),
+    JnzImmImm(Imm, Imm),
+    JnzImmReg(Imm, Reg),
+}
+
+fn parse_reg(input: &str) -> Reg {
+    let regc = input.chars().next().unwrap();
+    match regc {
+        'a' => Reg::A,
+        'b' => Reg::B,
+        'c' => Reg::C,
+        'd' => Reg::D,
+        'e' => Reg::E,
+        'f' => Reg::F,
+        'g' => Reg::G,
+        'h' => Reg::H,
+        _ => {
+            unreachable!("{}", regc);
+        }
+    }
+}
+
+enum RegOrImm {
+    Imm(Imm),
+    Reg(Reg),
+}
+
+fn parse_regimm(input: &str) -> RegOrImm {
+    input.parse::<Imm>()
+        .map(RegOrImm::Imm)
+        .unwrap_or_else(|_| RegOrImm::Reg(parse_reg(input)))
+}
+
+pub fn parse(input: &str) -> Vec<Inst> {
+    input.lines().map(|line| {
+        let mut words = line.split(' ');
+        let verb = words.next().unwrap();
+        //dbg!(line);
+        let dst = parse_regimm(words.next().unwrap());
+        let src = parse_regimm(words.next().unwrap());
+
+        match (verb, dst, src) {
+            ("set", RegOrImm::Reg(dst), RegOrImm::Reg(r)) => Inst::Set(dst, r),
+            ("set", RegOrImm::Reg(dst), RegOrImm::Imm(i)) => Inst::SetImm(dst, i),
+            ("sub", RegOrImm::Reg(dst), RegOrImm::Reg(r)) => Inst::Sub(dst, r),
+            ("sub", RegOrImm::Reg(dst), RegOrImm::Imm(i)) => Inst::SubImm(dst, i),
+            ("mul", RegOrImm::Reg(dst), RegOrImm::Reg(r)) => Inst::Mul(dst, r),
+            ("mul", RegOrImm::Reg(dst), RegOrImm::Imm(i)) => Inst::MulImm(dst, i),
+            ("jnz", RegOrImm::Reg(dst), RegOrImm::Reg(r)) => Inst::Jnz(dst, r),
+            ("jnz", RegOrImm::Reg(dst), RegOrImm::Imm(i)) => Inst::JnzImm(dst, i),
+            ("jnz", RegOrImm::Imm(dsti), RegOrImm::Reg(r)) => Inst::JnzImmReg(dsti, r),
+            ("jnz", RegOrImm::Imm(dsti), RegOrImm::Imm(i)) => Inst::JnzImmImm(dsti, i),
+            _ => {
+                unreachable!();
+            }
+        }
+    }).collect()
+}
+
+pub struct Machine {
+    pub regs: [i64; 8],
+    pub pc: isize,
+    pub program: Vec<Inst>,
+
+    pub muls: u64,
+    pub profile: Vec<u64>,
+    pub insn_lim: u64,
+}
+
+impl Machine {
+    pub fn new(program: &[Inst]) -> Self {
+        Self {
+            regs: [0; 8],
+            pc: 0,
+            program: program.to_vec(),
+            muls: 0,
+            profile: Vec::new(),
+            insn_lim: u64::MAX,
+        }
+    }
+
+    /// Offset added to `pc` if the instruction there is a jump that will be taken.
+    fn taken_jump(&self) -> Option<isize> {
+        let (cond, off) = match self.program[self.pc as usize] {
+            Inst::Jnz(dst, src) => (self.regs[dst as usize], self.regs[src as usize]),
+            Inst::JnzImm(dst, src) => (self.regs[dst as usize], src),
+            Inst::JnzImmImm(dst, src) => (dst, src),
+            Inst::JnzImmReg(dst, src) => (dst, self.regs[src as usize]),
+            _ => return None,
+        };
+        if cond != 0 { Some(off as isize) } else { None }
+    }
+
+    /// Execute the instruction at `pc`.
+    pub fn step(&mut self) {
+        match self.program[self.pc as usize] {
+            Inst::Set(dst, src) => {
+                self.regs[dst as usize] = self.regs[src as usize];
+            }
+            Inst::SetImm(dst, src) => {
+                self.regs[dst as usize] = src;
+            }
+            Inst::Sub(dst, src) => {
+                self.regs[dst as usize] -= self.regs[src as usize];
+            }
+            Inst::SubImm(dst, src) => {
+                self.regs[dst as usize] -= src;
+            }
+            Inst::Mul(dst, src) => {
+                self.regs[dst as usize] *= self.regs[src as usize];
+                self.muls += 1;
+            }
+            Inst::MulImm(dst, src) => {
+                self.regs[dst as usize] *= src;
+                self.muls += 1;
+            }
+            Inst::Jnz(dst, src) => {
+                if self.regs[dst as usize] != 0 {
+                    self.pc += (self.regs[src as usize] as isize) - 1;
+                    assert!(self.pc >= -1);
+                }
+            }
+            Inst::JnzImm(dst, src) => {
+                if self.regs[dst as usize] != 0 {
+                    self.pc += (src as isize) - 1;
+                    assert!(self.pc >= -1);
+                }
+            }
+            Inst::JnzImmImm(dst, src) => {
+                if dst != 0 {
+                    self.pc += (src as isize) - 1;
+                    assert!(self.pc >= -1);
+                }
+            }
+            Inst::JnzImmReg(dst, src) => {
+                if dst != 0 {
+                    self.pc += (self.regs[src as usize] as isize) - 1;
+                    assert!(self.pc >= -1);
+                }
+            }
+        };
+        self.pc += 1;
+    }
+
+    pub fn run(&mut self) {
+        let endpc = self.program.len() as isize;
+        while self.pc < endpc {
+            self.step();
+        }
+    }
+}
+
+impl Debuggee for Machine {
+    fn reg_names(&self) -> &[&'static str] {
+        &["a", "b", "c", "d", "e", "f", "g", "h"]
+    }
+
+    fn regs(&self) -> &[i64] {
+        &self.regs
+    }
+
+    fn pc(&self) -> isize {
+        self.pc
+    }
+
+    fn program_len(&self) -> usize {
+        self.program.len()
+    }
+
+    fn describe(&self, pc: isize) -> String {
+        format!("{:?}", self.program[pc as usize])
+    }
+
+    fn step_recorded(&mut self) -> Result<UndoRecord, String> {
+        let pc = self.pc;
+        let reg = match self.program[pc as usize] {
+            Inst::Set(dst, _) | Inst::SetImm(dst, _) |
+            Inst::Sub(dst, _) | Inst::SubImm(dst, _) |
+            Inst::Mul(dst, _) | Inst::MulImm(dst, _) => Some((dst as usize, self.regs[dst as usize])),
+            _ => None,
+        };
+        if let Some(off) = self.taken_jump() {
+            match pc.checked_add(off) {
+                Some(target) if target >= 0 => {}
+                _ => return Err(format!("jump by {} from pc {}", off, pc)),
+            }
+        }
+        self.step();
+        Ok(UndoRecord { pc, reg, input: None, })
+    }
+
+    fn undo(&mut self, rec: &UndoRecord) {
+        if let Inst::Mul(..) | Inst::MulImm(..) = self.program[rec.pc as usize] {
+            self.muls -= 1;
+        }
+        if let Some((r, v)) = rec.reg {
+            self.regs[r] = v;
+        }
+        self.pc = rec.pc;
+    }
+}
+
+/// Translate to the decompiler's IR.
+pub fn lift(program: &[Inst]) -> Lifted {
+    let r = |reg: Reg| reg as usize;
+    let ops = program.iter().map(|inst| match *inst {
+        Inst::Set(dst, src) => Op::Set(r(dst), Operand::Reg(r(src))),
+        Inst::SetImm(dst, src) => Op::Set(r(dst), Operand::Imm(src)),
+        Inst::Sub(dst, src) => Op::Bin(BinOp::Sub, r(dst), Operand::Reg(r(src))),
+        Inst::SubImm(dst, src) => Op::Bin(BinOp::Sub, r(dst), Operand::Imm(src)),
+        Inst::Mul(dst, src) => Op::Bin(BinOp::Mul, r(dst), Operand::Reg(r(src))),
+        Inst::MulImm(dst, src) => Op::Bin(BinOp::Mul, r(dst), Operand::Imm(src)),
+        Inst::Jnz(dst, src) => Op::Jnz(Operand::Reg(r(dst)), Operand::Reg(r(src))),
+        Inst::JnzImm(dst, src) => Op::Jnz(Operand::Reg(r(dst)), Operand::Imm(src)),
+        Inst::JnzImmImm(dst, src) => Op::Jnz(Operand::Imm(dst), Operand::Imm(src)),
+        Inst::JnzImmReg(dst, src) => Op::Jnz(Operand::Imm(dst), Operand::Reg(r(src))),
+    }).collect();
+
+    Lifted {
+        reg_names: vec!["a", "b", "c", "d", "e", "f", "g", "h"],
+        ops,
+        // Any register may be the answer.
+        live_out: (0..8).collect(),
+    }
+}
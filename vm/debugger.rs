@@ -0,0 +1,352 @@
+//! Time-travel debugger for the interpreted register machines.
+//!
+//! Every executed instruction pushes an [`UndoRecord`], so execution can be stepped or continued
+//! backwards as well as forwards.
+
+use anyhow::{anyhow, Result};
+use std::collections::VecDeque;
+use std::io::{BufRead, Write};
+
+/// Everything needed to reverse one instruction.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UndoRecord {
+    /// `pc` before the instruction.
+    pub pc: isize,
+    /// Register overwritten, and its old value.
+    pub reg: Option<(usize, i64)>,
+    /// Input consumed, if any.
+    pub input: Option<i64>,
+}
+
+/// A machine the debugger can drive.
+pub trait Debuggee {
+    fn reg_names(&self) -> &[&'static str];
+    fn regs(&self) -> &[i64];
+    fn pc(&self) -> isize;
+    fn program_len(&self) -> usize;
+    /// Human-readable instruction at `pc`.
+    fn describe(&self, pc: isize) -> String;
+    /// Execute one instruction, or report why it can't be (division by zero, missing input...)
+    /// without changing any state.
+    fn step_recorded(&mut self) -> std::result::Result<UndoRecord, String>;
+    /// Reverse the instruction described by `rec`.
+    fn undo(&mut self, rec: &UndoRecord);
+
+    fn halted(&self) -> bool {
+        self.pc() < 0 || self.pc() as usize >= self.program_len()
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CmpOp {
+    Eq, Ne, Lt, Le, Gt, Ge,
+}
+
+impl CmpOp {
+    fn parse(s: &str) -> Option<Self> {
+        Some(match s {
+            "==" => CmpOp::Eq,
+            "!=" => CmpOp::Ne,
+            "<" => CmpOp::Lt,
+            "<=" => CmpOp::Le,
+            ">" => CmpOp::Gt,
+            ">=" => CmpOp::Ge,
+            _ => return None,
+        })
+    }
+
+    fn sym(self) -> &'static str {
+        match self {
+            CmpOp::Eq => "==",
+            CmpOp::Ne => "!=",
+            CmpOp::Lt => "<",
+            CmpOp::Le => "<=",
+            CmpOp::Gt => ">",
+            CmpOp::Ge => ">=",
+        }
+    }
+
+    fn eval(self, a: i64, b: i64) -> bool {
+        match self {
+            CmpOp::Eq => a == b,
+            CmpOp::Ne => a != b,
+            CmpOp::Lt => a < b,
+            CmpOp::Le => a <= b,
+            CmpOp::Gt => a > b,
+            CmpOp::Ge => a >= b,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Breakpoint {
+    Pc(isize),
+    /// `reg op value`
+    Reg(usize, CmpOp, i64),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Stop {
+    /// Ran the requested number of steps.
+    Done,
+    Breakpoint(usize),
+    /// Watched register changed from `.1` to `.2`.
+    Watch(usize, i64, i64),
+    Halted,
+    Fault(String),
+    /// Nothing left to undo.
+    HistoryStart,
+}
+
+pub struct Debugger<M: Debuggee> {
+    pub machine: M,
+    history: VecDeque<UndoRecord>,
+    /// Oldest records are dropped beyond this many.
+    pub max_history: usize,
+    pub breakpoints: Vec<Breakpoint>,
+    pub watches: Vec<usize>,
+}
+
+impl<M: Debuggee> Debugger<M> {
+    pub fn new(machine: M) -> Self {
+        Self {
+            machine,
+            history: VecDeque::new(),
+            max_history: 10_000_000,
+            breakpoints: Vec::new(),
+            watches: Vec::new(),
+        }
+    }
+
+    pub fn history_len(&self) -> usize {
+        self.history.len()
+    }
+
+    /// Register conditions trigger when they become true, rather than on every step while they
+    /// stay true.
+    fn hit_breakpoint(&self, before: &[i64]) -> Option<usize> {
+        let regs = self.machine.regs();
+        self.breakpoints.iter().position(|bp| match *bp {
+            Breakpoint::Pc(pc) => self.machine.pc() == pc,
+            Breakpoint::Reg(r, op, val) => op.eval(regs[r], val) && !op.eval(before[r], val),
+        })
+    }
+
+    fn check(&self, before: &[i64]) -> Option<Stop> {
+        let regs = self.machine.regs();
+        for &w in self.watches.iter() {
+            if before[w] != regs[w] {
+                return Some(Stop::Watch(w, before[w], regs[w]));
+            }
+        }
+        self.hit_breakpoint(before).map(Stop::Breakpoint)
+    }
+
+    /// Execute up to `n` instructions forwards, stopping early at breakpoints and watches.
+    pub fn step(&mut self, n: usize) -> Stop {
+        for _ in 0..n {
+            if self.machine.halted() {
+                return Stop::Halted;
+            }
+            let before = self.machine.regs().to_vec();
+            match self.machine.step_recorded() {
+                Ok(rec) => {
+                    if self.history.len() == self.max_history {
+                        self.history.pop_front();
+                    }
+                    self.history.push_back(rec);
+                }
+                Err(e) => return Stop::Fault(e),
+            }
+            if let Some(stop) = self.check(&before) {
+                return stop;
+            }
+        }
+        if self.machine.halted() {
+            Stop::Halted
+        } else {
+            Stop::Done
+        }
+    }
+
+    /// Undo up to `n` instructions, stopping early at breakpoints and watches.
+    pub fn back(&mut self, n: usize) -> Stop {
+        for _ in 0..n {
+            let rec = match self.history.pop_back() {
+                Some(rec) => rec,
+                None => return Stop::HistoryStart,
+            };
+            let before = self.machine.regs().to_vec();
+            self.machine.undo(&rec);
+            if let Some(stop) = self.check(&before) {
+                return stop;
+            }
+        }
+        Stop::Done
+    }
+
+    pub fn cont(&mut self) -> Stop {
+        self.step(usize::MAX)
+    }
+
+    pub fn reverse_cont(&mut self) -> Stop {
+        self.back(usize::MAX)
+    }
+
+    fn reg_index(&self, name: &str) -> Result<usize> {
+        self.machine.reg_names().iter().position(|n| *n == name)
+            .ok_or_else(|| anyhow!("No register {:?}", name))
+    }
+
+    fn fmt_regs(&self) -> String {
+        self.machine.reg_names().iter().zip(self.machine.regs().iter())
+            .map(|(n, v)| format!("{}={}", n, v))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn fmt_stop(&self, stop: &Stop) -> String {
+        let names = self.machine.reg_names();
+        let reason = match stop {
+            Stop::Done => String::new(),
+            Stop::Breakpoint(i) => format!("breakpoint {}: ", i),
+            Stop::Watch(r, old, new) => format!("watch {}: {} -> {}: ", names[*r], old, new),
+            Stop::Halted => "halted: ".to_owned(),
+            Stop::Fault(e) => format!("fault ({}): ", e),
+            Stop::HistoryStart => "start of history: ".to_owned(),
+        };
+        let pc = self.machine.pc();
+        if self.machine.halted() {
+            format!("{}pc {}", reason, pc)
+        } else {
+            format!("{}pc {}: {}", reason, pc, self.machine.describe(pc))
+        }
+    }
+
+    /// Run one REPL command.  Returns false on `quit`.
+    pub fn command<W: Write>(&mut self, line: &str, out: &mut W) -> Result<bool> {
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        let count = |i: usize| -> Result<usize> {
+            Ok(words.get(i).map(|w| w.parse::<usize>()).transpose()?.unwrap_or(1))
+        };
+
+        let stop = match words.as_slice() {
+            [] => return Ok(true),
+            ["quit"] | ["q"] => return Ok(false),
+            ["step", ..] | ["s", ..] => self.step(count(1)?),
+            ["back", ..] | ["b", ..] => self.back(count(1)?),
+            ["continue"] | ["c"] => self.cont(),
+            ["rcontinue"] | ["rc"] => self.reverse_cont(),
+            ["regs"] | ["r"] => {
+                writeln!(out, "pc={} {}", self.machine.pc(), self.fmt_regs())?;
+                return Ok(true);
+            }
+            ["break", pc] => {
+                self.breakpoints.push(Breakpoint::Pc(pc.parse()?));
+                writeln!(out, "breakpoint {}: pc == {}", self.breakpoints.len() - 1, pc)?;
+                return Ok(true);
+            }
+            ["break", reg, op, val] => {
+                let r = self.reg_index(reg)?;
+                let op = CmpOp::parse(op).ok_or_else(|| anyhow!("Bad comparison {:?}", op))?;
+                self.breakpoints.push(Breakpoint::Reg(r, op, val.parse()?));
+                writeln!(out, "breakpoint {}: {} {} {}", self.breakpoints.len() - 1, reg, op.sym(), val)?;
+                return Ok(true);
+            }
+            ["delete", i] => {
+                let i = i.parse::<usize>()?;
+                if i >= self.breakpoints.len() {
+                    return Err(anyhow!("No breakpoint {}", i));
+                }
+                self.breakpoints.remove(i);
+                return Ok(true);
+            }
+            ["watch", reg] => {
+                let r = self.reg_index(reg)?;
+                self.watches.push(r);
+                writeln!(out, "watching {}", reg)?;
+                return Ok(true);
+            }
+            ["unwatch", reg] => {
+                let r = self.reg_index(reg)?;
+                self.watches.retain(|&w| w != r);
+                return Ok(true);
+            }
+            _ => {
+                writeln!(out, "commands: step [n], back [n], continue, rcontinue, regs, \
+                              break <pc>, break <reg> <op> <value>, delete <n>, watch <reg>, \
+                              unwatch <reg>, quit")?;
+                return Ok(true);
+            }
+        };
+        writeln!(out, "{}", self.fmt_stop(&stop))?;
+        Ok(true)
+    }
+
+    pub fn repl<R: BufRead, W: Write>(&mut self, input: R, mut out: W) -> Result<()> {
+        writeln!(out, "{}", self.fmt_stop(&Stop::Done))?;
+        for line in input.lines() {
+            let line = line?;
+            match self.command(&line, &mut out) {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(e) => writeln!(out, "error: {}", e)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coproc;
+
+    const PROGRAM: &str = "set b 3\nset c 0\nsub c -2\nsub b 1\njnz b -2\nmul c c";
+
+    #[test]
+    fn step_back_roundtrip() {
+        let program = coproc::parse(PROGRAM);
+        let mut dbg = Debugger::new(coproc::Machine::new(&program));
+
+        assert_eq!(dbg.cont(), Stop::Halted);
+        assert_eq!(dbg.machine.regs[2], 36);
+        assert_eq!(dbg.machine.muls, 1);
+
+        assert_eq!(dbg.reverse_cont(), Stop::HistoryStart);
+        assert_eq!(dbg.machine.regs, [0; 8]);
+        assert_eq!(dbg.machine.pc, 0);
+        assert_eq!(dbg.machine.muls, 0);
+
+        dbg.breakpoints.push(Breakpoint::Reg(1, CmpOp::Eq, 1));
+        assert_eq!(dbg.cont(), Stop::Breakpoint(0));
+        assert_eq!(dbg.machine.regs[2], 4);
+    }
+
+    #[test]
+    fn bad_jump() {
+        let program = coproc::parse("set a 1\njnz a -2");
+        let mut dbg = Debugger::new(coproc::Machine::new(&program));
+        assert_eq!(dbg.cont(), Stop::Fault("jump by -2 from pc 1".to_owned()));
+        assert_eq!(dbg.machine.pc, 1);
+    }
+
+    #[test]
+    fn repl_script() {
+        let program = coproc::parse(PROGRAM);
+        let mut dbg = Debugger::new(coproc::Machine::new(&program));
+        let script = "watch c\nc\nc\nback 2\nregs\nbreak 5\nunwatch c\nc\nq\n";
+        let mut out = Vec::new();
+        dbg.repl(script.as_bytes(), &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "\
+pc 0: SetImm(B, 3)
+watching c
+watch c: 0 -> 2: pc 3: SubImm(B, 1)
+watch c: 2 -> 4: pc 3: SubImm(B, 1)
+watch c: 4 -> 2: pc 2: SubImm(C, -2)
+pc=2 a=0 b=2 c=2 d=0 e=0 f=0 g=0 h=0
+breakpoint 0: pc == 5
+breakpoint 0: pc 5: Mul(C, C)
+");
+    }
+}
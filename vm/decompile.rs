@@ -0,0 +1,877 @@
+//! Decompiler for the register machine puzzles (2017 day 23, 2021 day 24).
+//!
+//! Programs are lifted into a small common IR, split into a control-flow graph of basic blocks,
+//! and printed either as C-like pseudo-code or as a Graphviz digraph.  Within each basic block,
+//! register values are tracked as expression trees; this folds constants (`mul x 0` is just
+//! `x = 0`) and lets stores that are overwritten before being read disappear.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum BinOp {
+    Add, Sub, Mul, Div, Mod, Eql, Ne,
+}
+
+impl BinOp {
+    fn eval(self, a: i64, b: i64) -> i64 {
+        match self {
+            BinOp::Add => a.wrapping_add(b),
+            BinOp::Sub => a.wrapping_sub(b),
+            BinOp::Mul => a.wrapping_mul(b),
+            BinOp::Div => a / b,
+            BinOp::Mod => a % b,
+            BinOp::Eql => (a == b) as i64,
+            BinOp::Ne => (a != b) as i64,
+        }
+    }
+
+    fn prec(self) -> u8 {
+        match self {
+            BinOp::Mul | BinOp::Div | BinOp::Mod => 3,
+            BinOp::Add | BinOp::Sub => 2,
+            BinOp::Eql | BinOp::Ne => 1,
+        }
+    }
+
+    fn sym(self) -> &'static str {
+        match self {
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Mul => "*",
+            BinOp::Div => "/",
+            BinOp::Mod => "%",
+            BinOp::Eql => "==",
+            BinOp::Ne => "!=",
+        }
+    }
+
+    fn is_cmp(self) -> bool {
+        matches!(self, BinOp::Eql | BinOp::Ne)
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Operand {
+    Reg(usize),
+    Imm(i64),
+}
+
+/// One lifted instruction.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Op {
+    /// `dst = input()`
+    Input(usize),
+    /// `dst = src`
+    Set(usize, Operand),
+    /// `dst = dst op src`
+    Bin(BinOp, usize, Operand),
+    /// Jump by `offset` instructions if `cond` is nonzero.
+    Jnz(Operand, Operand),
+}
+
+/// A program in the decompiler's IR.
+#[derive(Clone, Debug)]
+pub struct Lifted {
+    pub reg_names: Vec<&'static str>,
+    pub ops: Vec<Op>,
+    /// Registers observable after the program halts.
+    pub live_out: Vec<usize>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Expr {
+    Imm(i64),
+    Reg(usize),
+    Temp(usize),
+    Bin(BinOp, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    /// Build `a op b`, folding constants and algebraic identities.
+    pub fn bin(op: BinOp, a: Expr, b: Expr) -> Expr {
+        use BinOp::*;
+        use Expr::Imm;
+
+        match (op, &a, &b) {
+            (Div, _, Imm(0)) | (Mod, _, Imm(0)) => {}
+            (_, Imm(x), Imm(y)) => return Imm(op.eval(*x, *y)),
+            (Add, _, Imm(0)) | (Sub, _, Imm(0)) | (Mul, _, Imm(1)) | (Div, _, Imm(1)) => return a,
+            (Add, Imm(0), _) | (Mul, Imm(1), _) => return b,
+            (Mul, _, Imm(0)) | (Mul, Imm(0), _) | (Mod, _, Imm(1)) => return Imm(0),
+            (Sub, _, Imm(k)) => return Expr::bin(Add, a, Imm(k.wrapping_neg())),
+            (Add, Imm(_), _) => return Expr::bin(Add, b, a),
+            (Sub, x, y) | (Eql, x, y) | (Ne, x, y) if x == y => {
+                return Imm(op.eval(0, 0));
+            }
+            (Add, Expr::Bin(Add, x, k1), Imm(k2)) => {
+                if let Imm(k1) = **k1 {
+                    return Expr::bin(Add, (**x).clone(), Imm(k1.wrapping_add(*k2)));
+                }
+            }
+            (Eql, Expr::Bin(inner, x, y), Imm(0)) if inner.is_cmp() => {
+                let flipped = if *inner == Eql { Ne } else { Eql };
+                return Expr::bin(flipped, (**x).clone(), (**y).clone());
+            }
+            (Ne, Expr::Bin(inner, _, _), Imm(0)) if inner.is_cmp() => return a,
+            (Eql, Expr::Bin(Sub, x, y), Imm(0)) | (Ne, Expr::Bin(Sub, x, y), Imm(0)) => {
+                return Expr::bin(op, (**x).clone(), (**y).clone());
+            }
+            (Eql, Expr::Bin(Add, x, k), Imm(c)) | (Ne, Expr::Bin(Add, x, k), Imm(c)) => {
+                if let Imm(k) = **k {
+                    return Expr::bin(op, (**x).clone(), Imm(c.wrapping_sub(k)));
+                }
+            }
+            _ => {}
+        }
+        Expr::Bin(op, Box::new(a), Box::new(b))
+    }
+
+    /// The condition `self != 0`, as a comparison.
+    fn truthy(self) -> Expr {
+        match self {
+            Expr::Bin(op, _, _) if op.is_cmp() => self,
+            _ => Expr::bin(BinOp::Ne, self, Expr::Imm(0)),
+        }
+    }
+
+    /// The condition `self == 0`, as a comparison.
+    fn falsy(self) -> Expr {
+        Expr::bin(BinOp::Eql, self.truthy(), Expr::Imm(0))
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Expr::Bin(_, a, b) => 1 + a.size() + b.size(),
+            _ => 1,
+        }
+    }
+
+    fn uses_reg(&self, r: usize) -> bool {
+        match self {
+            Expr::Reg(x) => *x == r,
+            Expr::Bin(_, a, b) => a.uses_reg(r) || b.uses_reg(r),
+            _ => false,
+        }
+    }
+
+    fn uses_temp(&self, t: usize) -> bool {
+        match self {
+            Expr::Temp(x) => *x == t,
+            Expr::Bin(_, a, b) => a.uses_temp(t) || b.uses_temp(t),
+            _ => false,
+        }
+    }
+
+    fn replace(&mut self, from: &Expr, to: &Expr) {
+        if self == from {
+            *self = to.clone();
+        } else if let Expr::Bin(_, a, b) = self {
+            a.replace(from, to);
+            b.replace(from, to);
+        }
+    }
+
+    fn count_subexprs(&self, counts: &mut HashMap<Expr, usize>) {
+        if let Expr::Bin(_, a, b) = self {
+            *counts.entry(self.clone()).or_insert(0) += 1;
+            a.count_subexprs(counts);
+            b.count_subexprs(counts);
+        }
+    }
+
+    pub fn fmt(&self, names: &[&str]) -> String {
+        self.fmt_prec(names, 0)
+    }
+
+    fn fmt_prec(&self, names: &[&str], min_prec: u8) -> String {
+        match self {
+            Expr::Imm(i) => format!("{}", i),
+            Expr::Reg(r) => names[*r].to_owned(),
+            Expr::Temp(t) => format!("t{}", t),
+            Expr::Bin(op, a, b) => {
+                let p = op.prec();
+                let lhs = a.fmt_prec(names, if op.is_cmp() { p + 1 } else { p });
+                let s = match (op, &**b) {
+                    (BinOp::Add, Expr::Imm(k)) if *k < 0 => format!("{} - {}", lhs, -k),
+                    _ => format!("{} {} {}", lhs, op.sym(), b.fmt_prec(names, p + 1)),
+                };
+                if p < min_prec {
+                    format!("({})", s)
+                } else {
+                    s
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BasicBlock {
+    /// Instruction range `start..end`.
+    pub start: usize,
+    pub end: usize,
+    pub succs: Vec<usize>,
+    /// Control can leave the program from this block.
+    pub exits: bool,
+    /// Ends in a computed jump, with unknown successors.
+    pub dynamic: bool,
+}
+
+/// A natural loop: `header` dominates every block in `body`, and each of `latches` jumps back
+/// to it.
+#[derive(Clone, Debug)]
+pub struct Loop {
+    pub header: usize,
+    pub latches: Vec<usize>,
+    pub body: BTreeSet<usize>,
+}
+
+/// Block terminator, with the condition already rewritten in terms of post-block register names.
+#[derive(Clone, Debug)]
+enum Term {
+    Fallthrough,
+    /// `if (cond) goto pc`; out-of-range `pc` halts.
+    Branch(Expr, isize),
+    Goto(isize),
+    /// `if (cond) goto from + offset`.
+    Dynamic(Expr, usize, Expr),
+}
+
+#[derive(Clone, Debug)]
+struct BlockCode {
+    stmts: Vec<String>,
+    term: Term,
+}
+
+pub struct Decompiler {
+    prog: Lifted,
+    pub blocks: Vec<BasicBlock>,
+    pub loops: Vec<Loop>,
+    /// Registers live on entry to each instruction, as bitmasks.
+    live_in: Vec<u64>,
+    code: Vec<BlockCode>,
+}
+
+impl Decompiler {
+    pub fn new(prog: &Lifted) -> Self {
+        assert!(prog.reg_names.len() <= 64);
+        let mut res = Self {
+            prog: prog.clone(),
+            blocks: Vec::new(),
+            loops: Vec::new(),
+            live_in: Vec::new(),
+            code: Vec::new(),
+        };
+        res.build_cfg();
+        res.find_loops();
+        res.liveness();
+
+        let mut next_temp = 0;
+        res.code = (0..res.blocks.len()).map(|b| res.gen_block(b, &mut next_temp)).collect();
+        res
+    }
+
+    fn static_target(&self, pc: usize) -> Option<isize> {
+        match self.prog.ops[pc] {
+            Op::Jnz(_, Operand::Imm(off)) => Some(pc as isize + off as isize),
+            _ => None,
+        }
+    }
+
+    fn in_range(&self, pc: isize) -> bool {
+        pc >= 0 && (pc as usize) < self.prog.ops.len()
+    }
+
+    fn block_at(&self, pc: isize) -> Option<usize> {
+        self.blocks.iter().position(|b| b.start as isize == pc)
+    }
+
+    fn build_cfg(&mut self) {
+        let n = self.prog.ops.len();
+        let mut leaders = BTreeSet::new();
+        leaders.insert(0);
+        for pc in 0..n {
+            if let Op::Jnz(..) = self.prog.ops[pc] {
+                leaders.insert(pc + 1);
+                if let Some(t) = self.static_target(pc) {
+                    if self.in_range(t) {
+                        leaders.insert(t as usize);
+                    }
+                }
+            }
+        }
+        leaders.retain(|&pc| pc < n);
+
+        let starts = leaders.into_iter().collect::<Vec<_>>();
+        self.blocks = starts.iter().enumerate().map(|(i, &start)| {
+            let end = starts.get(i + 1).copied().unwrap_or(n);
+            BasicBlock { start, end, succs: Vec::new(), exits: false, dynamic: false, }
+        }).collect();
+
+        for b in 0..self.blocks.len() {
+            let last = self.blocks[b].end - 1;
+            let next = b + 1;
+            let (fallthrough, target, dynamic) = match self.prog.ops[last] {
+                Op::Jnz(Operand::Imm(0), _) => (true, None, false),
+                Op::Jnz(cond, off) => {
+                    let always = matches!(cond, Operand::Imm(_));
+                    match off {
+                        Operand::Imm(_) => (!always, self.static_target(last), false),
+                        Operand::Reg(_) => (!always, None, true),
+                    }
+                }
+                _ => (true, None, false),
+            };
+
+            let mut succs = Vec::new();
+            let mut exits = false;
+            if fallthrough {
+                if next < self.blocks.len() {
+                    succs.push(next);
+                } else {
+                    exits = true;
+                }
+            }
+            if let Some(t) = target {
+                match self.block_at(t) {
+                    Some(tb) if !succs.contains(&tb) => succs.push(tb),
+                    Some(_) => {}
+                    None => exits = true,
+                }
+            }
+
+            let block = &mut self.blocks[b];
+            block.succs = succs;
+            block.exits = exits;
+            block.dynamic = dynamic;
+        }
+    }
+
+    /// Find back edges by DFS from the entry block and collect their natural loops.
+    fn find_loops(&mut self) {
+        let nblocks = self.blocks.len();
+        let mut on_stack = vec![false; nblocks];
+        let mut visited = vec![false; nblocks];
+        let mut back_edges = Vec::new();
+
+        // Iterative DFS: (block, next successor index).
+        let mut stack = vec![(0usize, 0usize)];
+        visited[0] = true;
+        on_stack[0] = true;
+        while let Some(&mut (b, ref mut i)) = stack.last_mut() {
+            if let Some(&s) = self.blocks[b].succs.get(*i) {
+                *i += 1;
+                if on_stack[s] {
+                    back_edges.push((b, s));
+                } else if !visited[s] {
+                    visited[s] = true;
+                    on_stack[s] = true;
+                    stack.push((s, 0));
+                }
+            } else {
+                on_stack[b] = false;
+                stack.pop();
+            }
+        }
+
+        let mut preds = vec![Vec::new(); nblocks];
+        for (b, block) in self.blocks.iter().enumerate() {
+            for &s in block.succs.iter() {
+                preds[s].push(b);
+            }
+        }
+
+        for (latch, header) in back_edges {
+            let mut body = BTreeSet::new();
+            body.insert(header);
+            let mut work = vec![latch];
+            while let Some(b) = work.pop() {
+                if body.insert(b) {
+                    work.extend(preds[b].iter().copied());
+                }
+            }
+
+            match self.loops.iter_mut().find(|l| l.header == header) {
+                Some(l) => {
+                    l.latches.push(latch);
+                    l.body.extend(body);
+                }
+                None => self.loops.push(Loop { header, latches: vec![latch], body, }),
+            }
+        }
+        self.loops.sort_by_key(|l| l.header);
+    }
+
+    fn liveness(&mut self) {
+        let nregs = self.prog.reg_names.len();
+        let all = if nregs == 64 { u64::MAX } else { (1u64 << nregs) - 1 };
+        let exit_live = self.prog.live_out.iter().fold(0u64, |acc, r| acc | (1 << r));
+        let bit = |o: Operand| match o {
+            Operand::Reg(r) => 1u64 << r,
+            Operand::Imm(_) => 0,
+        };
+
+        self.live_in = vec![0; self.prog.ops.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for b in (0..self.blocks.len()).rev() {
+                let block = &self.blocks[b];
+                let mut live = block.succs.iter().fold(0, |acc, &s| acc | self.live_in[self.blocks[s].start]);
+                if block.exits {
+                    live |= exit_live;
+                }
+                if block.dynamic {
+                    live = all;
+                }
+
+                for pc in (block.start..block.end).rev() {
+                    live = match self.prog.ops[pc] {
+                        Op::Input(d) => live & !(1 << d),
+                        Op::Set(d, s) => (live & !(1 << d)) | bit(s),
+                        // `mul x 0` is how the ALU spells `x = 0`.
+                        Op::Bin(BinOp::Mul, d, Operand::Imm(0)) => live & !(1 << d),
+                        Op::Bin(_, d, s) => live | (1 << d) | bit(s),
+                        Op::Jnz(c, o) => live | bit(c) | bit(o),
+                    };
+                    if self.live_in[pc] != live {
+                        self.live_in[pc] = live;
+                        changed = true;
+                    }
+                }
+            }
+        }
+    }
+
+    fn live_after(&self, pc: usize) -> u64 {
+        let b = self.blocks.iter().position(|b| pc >= b.start && pc < b.end).unwrap();
+        let block = &self.blocks[b];
+        if pc + 1 < block.end {
+            return self.live_in[pc + 1];
+        }
+
+        let nregs = self.prog.reg_names.len();
+        if block.dynamic {
+            return if nregs == 64 { u64::MAX } else { (1u64 << nregs) - 1 };
+        }
+        let mut live = block.succs.iter().fold(0, |acc, &s| acc | self.live_in[self.blocks[s].start]);
+        if block.exits {
+            live |= self.prog.live_out.iter().fold(0u64, |acc, r| acc | (1 << r));
+        }
+        live
+    }
+
+    fn fmt_assign(&self, dst: &str, e: &Expr) -> String {
+        let names = &self.prog.reg_names;
+        if let Expr::Bin(op, a, b) = e {
+            if **a == Expr::Reg(names.iter().position(|n| *n == dst).unwrap_or(usize::MAX)) {
+                match (op, &**b) {
+                    (BinOp::Add, Expr::Imm(k)) if *k < 0 => return format!("{} -= {};", dst, -k),
+                    (BinOp::Add, _) | (BinOp::Sub, _) | (BinOp::Mul, _) | (BinOp::Div, _) | (BinOp::Mod, _) => {
+                        return format!("{} {}= {};", dst, op.sym(), b.fmt(names));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        format!("{} = {};", dst, e.fmt(names))
+    }
+
+    /// Emit assignments for the live registers modified since the last flush.  Afterwards every
+    /// register again holds its own (named) value.
+    fn flush(&self, state: &mut [Option<Expr>], live: u64, stmts: &mut Vec<String>, next_temp: &mut usize) {
+        let names = &self.prog.reg_names;
+        let mut pending = state.iter_mut().enumerate()
+            .filter_map(|(r, e)| e.take().map(|e| (r, e)))
+            .filter(|(r, e)| (live & (1 << r)) != 0 && *e != Expr::Reg(*r))
+            .collect::<Vec<_>>();
+
+        // Common subexpressions become temporaries, evaluated before any register is clobbered.
+        // Temporaries may themselves share subexpressions with each other.
+        let mut temps: Vec<(usize, Expr)> = Vec::new();
+        loop {
+            let mut counts = HashMap::new();
+            for (_, e) in pending.iter().chain(temps.iter()) {
+                e.count_subexprs(&mut counts);
+            }
+            let common = counts.into_iter()
+                .filter(|(_, n)| *n >= 2)
+                .map(|(e, _)| e)
+                .max_by_key(|e| (e.size(), e.fmt(names)));
+            let common = match common {
+                Some(e) => e,
+                None => break,
+            };
+            let temp = Expr::Temp(*next_temp);
+            for (_, e) in pending.iter_mut().chain(temps.iter_mut()) {
+                e.replace(&common, &temp);
+            }
+            temps.push((*next_temp, common));
+            *next_temp += 1;
+        }
+        while !temps.is_empty() {
+            let i = (0..temps.len()).find(|&i| {
+                temps.iter().all(|(t, _)| !temps[i].1.uses_temp(*t))
+            }).unwrap();
+            let (t, e) = temps.remove(i);
+            stmts.push(format!("{} = {};", Expr::Temp(t).fmt(names), e.fmt(names)));
+        }
+
+        // Order the assignments so that nothing reads a register after it has been overwritten.
+        while !pending.is_empty() {
+            let ready = (0..pending.len()).find(|&i| {
+                let r = pending[i].0;
+                pending.iter().enumerate().all(|(j, (_, e))| j == i || !e.uses_reg(r))
+            });
+            match ready {
+                Some(i) => {
+                    let (r, e) = pending.remove(i);
+                    stmts.push(self.fmt_assign(names[r], &e));
+                }
+                None => {
+                    // Cycle; save one old value in a temporary.
+                    let r = pending[0].0;
+                    let temp = Expr::Temp(*next_temp);
+                    *next_temp += 1;
+                    stmts.push(format!("{} = {};", temp.fmt(names), names[r]));
+                    for (_, e) in pending.iter_mut().skip(1) {
+                        e.replace(&Expr::Reg(r), &temp);
+                    }
+                    let (r, e) = pending.remove(0);
+                    stmts.push(self.fmt_assign(names[r], &e));
+                }
+            }
+        }
+    }
+
+    fn gen_block(&self, b: usize, next_temp: &mut usize) -> BlockCode {
+        let names = &self.prog.reg_names;
+        let block = &self.blocks[b];
+        let mut state: Vec<Option<Expr>> = vec![None; names.len()];
+        let mut stmts = Vec::new();
+        let mut term = Term::Fallthrough;
+
+        let cur = |state: &[Option<Expr>], o: Operand| match o {
+            Operand::Reg(r) => state[r].clone().unwrap_or(Expr::Reg(r)),
+            Operand::Imm(i) => Expr::Imm(i),
+        };
+
+        for pc in block.start..block.end {
+            match self.prog.ops[pc] {
+                Op::Input(d) => {
+                    let live = self.live_in[pc] & !(1 << d);
+                    self.flush(&mut state, live, &mut stmts, next_temp);
+                    stmts.push(format!("{} = input();", names[d]));
+                }
+                Op::Set(d, s) => {
+                    state[d] = Some(cur(&state, s));
+                }
+                Op::Bin(op, d, s) => {
+                    let e = Expr::bin(op, cur(&state, Operand::Reg(d)), cur(&state, s));
+                    state[d] = Some(e);
+                }
+                Op::Jnz(c, o) => {
+                    // Operands are read after the flush below.  A register that the flush assigns
+                    // (or an expression it computes) can simply be named; anything else computed
+                    // from clobbered registers is captured in a temporary first.
+                    let live = self.live_after(pc);
+                    let clobbered = |state: &[Option<Expr>], r: usize| {
+                        (live & (1 << r)) != 0 && matches!(&state[r], Some(e) if *e != Expr::Reg(r))
+                    };
+                    let mut read = |state: &[Option<Expr>], o: Operand| match o {
+                        Operand::Reg(r) if clobbered(state, r) => Expr::Reg(r),
+                        _ => {
+                            // Placeholders stand for the post-flush register values until we know
+                            // the old values aren't needed as well.
+                            let placeholder = |r: usize| Expr::Temp(usize::MAX - r);
+                            let orig = cur(state, o);
+                            let mut e = orig.clone();
+                            for r in (0..names.len()).filter(|&r| clobbered(state, r)) {
+                                if let Some(def @ Expr::Bin(..)) = &state[r] {
+                                    e.replace(def, &placeholder(r));
+                                }
+                            }
+                            if (0..names.len()).any(|r| clobbered(state, r) && e.uses_reg(r)) {
+                                let temp = Expr::Temp(*next_temp);
+                                *next_temp += 1;
+                                stmts.push(format!("{} = {};", temp.fmt(names), orig.fmt(names)));
+                                temp
+                            } else {
+                                for r in 0..names.len() {
+                                    e.replace(&placeholder(r), &Expr::Reg(r));
+                                }
+                                e
+                            }
+                        }
+                    };
+                    let cond = read(&state, c).truthy();
+                    let offset = read(&state, o);
+                    self.flush(&mut state, live, &mut stmts, next_temp);
+
+                    term = match (cond, offset) {
+                        (Expr::Imm(0), _) => Term::Fallthrough,
+                        (Expr::Imm(_), Expr::Imm(off)) => Term::Goto(pc as isize + off as isize),
+                        (cond, Expr::Imm(off)) => Term::Branch(cond, pc as isize + off as isize),
+                        (cond, offset) => Term::Dynamic(cond, pc, offset),
+                    };
+                }
+            }
+        }
+        if !matches!(self.prog.ops[block.end - 1], Op::Jnz(..)) {
+            let live = self.live_after(block.end - 1);
+            self.flush(&mut state, live, &mut stmts, next_temp);
+        }
+
+        BlockCode { stmts, term, }
+    }
+
+    fn label(&self, b: usize) -> String {
+        format!("L{}", self.blocks[b].start)
+    }
+
+    /// The loop headed by block `b`, if its body is the contiguous blocks `b..=latch` and it can
+    /// be printed as a `do { } while`.  Returns the latch.
+    fn simple_loop(&self, b: usize) -> Option<usize> {
+        let l = self.loops.iter().find(|l| l.header == b)?;
+        let latch = *l.body.iter().next_back().unwrap();
+        let contiguous = l.body.iter().copied().eq(b..=latch);
+        let back = match &self.code[latch].term {
+            Term::Branch(_, t) | Term::Goto(t) => *t == self.blocks[b].start as isize,
+            _ => false,
+        };
+        if contiguous && back && l.latches.contains(&latch) {
+            Some(latch)
+        } else {
+            None
+        }
+    }
+
+    /// Structured pseudo-code for the whole program.
+    pub fn pseudocode(&self) -> String {
+        let mut res = String::new();
+        for l in self.loops.iter() {
+            let body = l.body.iter().map(|&b| self.label(b)).collect::<Vec<_>>();
+            let latches = l.latches.iter().map(|&b| self.label(b)).collect::<Vec<_>>();
+            writeln!(res, "// loop {}: blocks {}; back edges from {}",
+                     self.label(l.header), body.join(", "), latches.join(", ")).unwrap();
+        }
+
+        // The first pass discovers which labels are still needed by gotos.
+        let mut emitter = Emitter { d: self, labels: BTreeSet::new(), gotos: BTreeSet::new(), out: String::new(), };
+        emitter.emit_range(0, self.blocks.len(), 1, None);
+        emitter.labels = std::mem::take(&mut emitter.gotos);
+        emitter.out.clear();
+        emitter.emit_range(0, self.blocks.len(), 1, None);
+
+        writeln!(res, "fn program() {{").unwrap();
+        res.push_str(&emitter.out);
+        writeln!(res, "}}").unwrap();
+        res
+    }
+
+    fn fmt_target(&self, t: isize) -> String {
+        match self.block_at(t) {
+            Some(tb) if self.in_range(t) => format!("goto {};", self.label(tb)),
+            _ => "return;".to_owned(),
+        }
+    }
+
+    /// Graphviz rendering of the CFG.  Back edges are dashed.
+    pub fn dot(&self) -> String {
+        let names = &self.prog.reg_names;
+        let mut res = String::new();
+        writeln!(res, "digraph cfg {{").unwrap();
+        writeln!(res, "    node [shape=box, fontname=\"monospace\"];").unwrap();
+        writeln!(res, "    exit [shape=oval];").unwrap();
+
+        for (b, code) in self.code.iter().enumerate() {
+            let mut lines = vec![format!("{}:", self.label(b))];
+            lines.extend(code.stmts.iter().map(|s| format!("  {}", s)));
+            match &code.term {
+                Term::Fallthrough => {}
+                Term::Branch(c, t) => lines.push(format!("  if ({}) {}", c.fmt(names), self.fmt_target(*t))),
+                Term::Goto(t) => lines.push(format!("  {}", self.fmt_target(*t))),
+                Term::Dynamic(c, pc, o) => {
+                    lines.push(format!("  if ({}) goto {} + {};", c.fmt(names), pc, o.fmt(names)))
+                }
+            }
+            let label = lines.iter()
+                .map(|l| format!("{}\\l", l.replace('\\', "\\\\").replace('"', "\\\"")))
+                .collect::<String>();
+            let header = self.loops.iter().any(|l| l.header == b);
+            writeln!(res, "    b{} [label=\"{}\"{}];", b, label, if header { ", penwidth=2" } else { "" }).unwrap();
+        }
+
+        for (b, block) in self.blocks.iter().enumerate() {
+            for &s in block.succs.iter() {
+                let back = self.loops.iter().any(|l| l.header == s && l.latches.contains(&b));
+                writeln!(res, "    b{} -> b{}{};", b, s, if back { " [style=dashed]" } else { "" }).unwrap();
+            }
+            if block.exits {
+                writeln!(res, "    b{} -> exit;", b).unwrap();
+            }
+            if block.dynamic {
+                writeln!(res, "    b{} -> exit [style=dotted, label=\"computed\"];", b).unwrap();
+            }
+        }
+        writeln!(res, "}}").unwrap();
+        res
+    }
+}
+
+/// Structured printer.  Loops become `do { } while`, forward branches become `if`, and anything
+/// else falls back to `goto`.
+struct Emitter<'a> {
+    d: &'a Decompiler,
+    /// Blocks that need a label.
+    labels: BTreeSet<usize>,
+    /// Blocks targeted by an emitted `goto`.
+    gotos: BTreeSet<usize>,
+    out: String,
+}
+
+impl<'a> Emitter<'a> {
+    fn line(&mut self, depth: usize, s: &str) {
+        writeln!(self.out, "{:width$}{}", "", s, width = depth * 4).unwrap();
+    }
+
+    fn goto(&mut self, t: isize) -> String {
+        if let Some(tb) = self.d.block_at(t) {
+            self.gotos.insert(tb);
+        }
+        self.d.fmt_target(t)
+    }
+
+    /// Emit blocks `start..end`.  `in_loop` is `(header, latch)` of a loop whose body this is:
+    /// the header isn't treated as a new loop and the latch's back edge is left to the caller.
+    fn emit_range(&mut self, start: usize, end: usize, depth: usize, in_loop: Option<(usize, usize)>) {
+        let d = self.d;
+        let names = &d.prog.reg_names;
+        let mut b = start;
+        while b < end {
+            if in_loop.map(|(h, _)| h) != Some(b) {
+                if let Some(latch) = d.simple_loop(b).filter(|&l| l < end) {
+                    self.line(depth, "do {");
+                    self.emit_range(b, latch + 1, depth + 1, Some((b, latch)));
+                    let cond = match &d.code[latch].term {
+                        Term::Branch(c, _) => c.fmt(names),
+                        _ => "1".to_owned(),
+                    };
+                    self.line(depth, &format!("}} while ({});", cond));
+                    b = latch + 1;
+                    continue;
+                }
+            }
+
+            if self.labels.contains(&b) {
+                let label = format!("{}:", d.label(b));
+                self.line(depth.saturating_sub(1), &label);
+            }
+            for s in d.code[b].stmts.iter() {
+                self.line(depth, s);
+            }
+
+            if in_loop.map(|(_, l)| l) == Some(b) {
+                b += 1;
+                continue;
+            }
+            match &d.code[b].term {
+                Term::Fallthrough => {}
+                Term::Branch(c, t) => {
+                    let tb = d.block_at(*t).filter(|_| d.in_range(*t));
+                    match tb {
+                        Some(tb) if tb > b + 1 && tb < end => {
+                            // Skip-ahead: `if (!c) { ... }`
+                            self.line(depth, &format!("if ({}) {{", c.clone().falsy().fmt(names)));
+                            self.emit_range(b + 1, tb, depth + 1, None);
+                            self.line(depth, "}");
+                            b = tb;
+                            continue;
+                        }
+                        _ => {
+                            let target = self.goto(*t);
+                            self.line(depth, &format!("if ({}) {}", c.fmt(names), target));
+                        }
+                    }
+                }
+                Term::Goto(t) => {
+                    let target = self.goto(*t);
+                    self.line(depth, &target);
+                }
+                Term::Dynamic(c, pc, o) => {
+                    self.line(depth, &format!("if ({}) goto {} + {};", c.fmt(names), pc, o.fmt(names)));
+                }
+            }
+            b += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fold_and_dead_stores() {
+        // x = 0; x += z; x %= 26; x += 12; x = (x == w); x = (x == 0); y = 0; y += w; y *= x;
+        // z += y; halt.  Only z is observable.
+        let (w, x, y, z) = (0, 1, 2, 3);
+        let ops = vec![
+            Op::Input(w),
+            Op::Bin(BinOp::Mul, x, Operand::Imm(0)),
+            Op::Bin(BinOp::Add, x, Operand::Reg(z)),
+            Op::Bin(BinOp::Mod, x, Operand::Imm(26)),
+            Op::Bin(BinOp::Add, x, Operand::Imm(12)),
+            Op::Bin(BinOp::Eql, x, Operand::Reg(w)),
+            Op::Bin(BinOp::Eql, x, Operand::Imm(0)),
+            Op::Bin(BinOp::Mul, y, Operand::Imm(0)),
+            Op::Bin(BinOp::Add, y, Operand::Reg(w)),
+            Op::Bin(BinOp::Mul, y, Operand::Reg(x)),
+            Op::Bin(BinOp::Add, z, Operand::Reg(y)),
+        ];
+        let prog = Lifted { reg_names: vec!["w", "x", "y", "z"], ops, live_out: vec![z], };
+        let d = Decompiler::new(&prog);
+        assert_eq!(d.pseudocode(), "fn program() {\n    w = input();\n    z += w * (z % 26 + 12 != w);\n}\n");
+    }
+
+    #[test]
+    fn loops_and_ifs() {
+        // L0: b = 10
+        // L1: f = 1
+        //     g = b; g -= 5; jnz g 2
+        //     f = 0
+        // L6: b -= 1; jnz b -6
+        let (b, f, g) = (0, 1, 2);
+        let ops = vec![
+            Op::Set(b, Operand::Imm(10)),
+            Op::Set(f, Operand::Imm(1)),
+            Op::Set(g, Operand::Reg(b)),
+            Op::Bin(BinOp::Sub, g, Operand::Imm(5)),
+            Op::Jnz(Operand::Reg(g), Operand::Imm(2)),
+            Op::Set(f, Operand::Imm(0)),
+            Op::Bin(BinOp::Sub, b, Operand::Imm(1)),
+            Op::Jnz(Operand::Reg(b), Operand::Imm(-6)),
+        ];
+        let prog = Lifted { reg_names: vec!["b", "f", "g"], ops, live_out: vec![b, f], };
+        let d = Decompiler::new(&prog);
+
+        assert_eq!(d.blocks.len(), 4);
+        assert_eq!(d.loops.len(), 1);
+        assert_eq!(d.loops[0].header, 1);
+        assert_eq!(d.pseudocode(), "\
+// loop L1: blocks L1, L5, L6; back edges from L6
+fn program() {
+    b = 10;
+    do {
+        f = 1;
+        if (b == 5) {
+            f = 0;
+        }
+        b -= 1;
+    } while (b != 0);
+}
+");
+        assert!(d.dot().contains("b3 -> b1 [style=dashed];"));
+    }
+}
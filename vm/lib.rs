@@ -0,0 +1,8 @@
+//! The puzzle virtual machines (2021 day 24 ALU, 2017 day 23 coprocessor) and the tools shared
+//! between them.  Re-exported by `aoc`, whose build script also uses `codegen` on cached inputs.
+
+pub mod alu;
+pub mod codegen;
+pub mod coproc;
+pub mod debugger;
+pub mod decompile;
@@ -0,0 +1,325 @@
+//! Interchangeable algorithms for the number of points left on by a sequence of on/off cuboid
+//! steps (2021 day 22).
+
+use bitvec::prelude::*;
+use std::collections::HashMap;
+
+use crate::cuboid::{Cuboid, CuboidSet};
+
+/// Turn every point of the cuboid on or off.
+pub type Step<const N: usize> = (bool, Cuboid<N>);
+
+pub trait VolumeEngine<const N: usize> {
+    fn name(&self) -> &'static str;
+
+    /// Points on after applying `steps` in order, or `None` if the answer (or, for some engines,
+    /// working storage) doesn't fit.
+    fn volume(&self, steps: &[Step<N>]) -> Option<i128>;
+}
+
+/// Clip every step to `region`, dropping those entirely outside.
+pub fn restrict<const N: usize>(steps: &[Step<N>], region: &Cuboid<N>) -> Vec<Step<N>> {
+    steps.iter().filter_map(|(on, c)| c.intersect(region).map(|c| (*on, c))).collect()
+}
+
+/// Maintain the lit region as disjoint boxes, splitting them around each step.
+pub struct BoxSplitting;
+
+impl<const N: usize> VolumeEngine<N> for BoxSplitting {
+    fn name(&self) -> &'static str {
+        "box splitting"
+    }
+
+    fn volume(&self, steps: &[Step<N>]) -> Option<i128> {
+        let mut set = CuboidSet::new();
+        for (on, c) in steps.iter() {
+            if *on {
+                set.insert(*c);
+            } else {
+                set.remove(c);
+            }
+        }
+        set.volume()
+    }
+}
+
+/// Signed cuboids: each step cancels its overlap with everything so far, then adds itself if on.
+/// Identical cuboids share one weight.
+pub struct InclusionExclusion;
+
+impl<const N: usize> VolumeEngine<N> for InclusionExclusion {
+    fn name(&self) -> &'static str {
+        "inclusion-exclusion"
+    }
+
+    fn volume(&self, steps: &[Step<N>]) -> Option<i128> {
+        let mut weights: HashMap<Cuboid<N>, i128> = HashMap::new();
+        for (on, c) in steps.iter() {
+            let mut updates: HashMap<Cuboid<N>, i128> = HashMap::new();
+            for (existing, w) in weights.iter() {
+                if let Some(overlap) = existing.intersect(c) {
+                    *updates.entry(overlap).or_default() -= w;
+                }
+            }
+            if *on {
+                *updates.entry(*c).or_default() += 1;
+            }
+            for (k, w) in updates.into_iter() {
+                let e = weights.entry(k).or_default();
+                *e += w;
+                if *e == 0 {
+                    weights.remove(&k);
+                }
+            }
+        }
+        weights.iter().try_fold(0i128, |acc, (c, w)| acc.checked_add(c.volume()?.checked_mul(*w)?))
+    }
+}
+
+/// Sorted distinct cell boundaries along `axis`; cell `i` is `b[i]..b[i + 1]`.
+fn boundaries<const N: usize>(steps: &[&Step<N>], axis: usize) -> Vec<i64> {
+    let mut b = steps.iter()
+        .flat_map(|(_, c)| vec![c.lo[axis], c.hi[axis] + 1])
+        .collect::<Vec<_>>();
+    b.sort_unstable();
+    b.dedup();
+    b
+}
+
+/// Compress each axis to the step boundaries and paint the steps onto a bitset of cells.
+pub struct CoordinateCompression {
+    /// Refuse grids with more cells than this.
+    pub max_cells: usize,
+}
+
+impl Default for CoordinateCompression {
+    fn default() -> Self {
+        Self { max_cells: 1 << 32 }
+    }
+}
+
+impl<const N: usize> VolumeEngine<N> for CoordinateCompression {
+    fn name(&self) -> &'static str {
+        "coordinate compression"
+    }
+
+    fn volume(&self, steps: &[Step<N>]) -> Option<i128> {
+        if N == 0 || steps.is_empty() {
+            return Some(0);
+        }
+        let refs = steps.iter().collect::<Vec<_>>();
+        let bounds = (0..N).map(|axis| boundaries(&refs, axis)).collect::<Vec<_>>();
+        let dims = bounds.iter().map(|b| b.len() - 1).collect::<Vec<_>>();
+        let cells = dims.iter().try_fold(1usize, |acc, &d| acc.checked_mul(d))?;
+        if cells > self.max_cells {
+            return None;
+        }
+
+        let mut grid = bitvec![Lsb0, usize; 0; cells];
+        let row_len = dims[N - 1];
+        for (on, c) in steps.iter() {
+            // Cell ranges along each axis.
+            let ranges = (0..N).map(|axis| {
+                let b = &bounds[axis];
+                let lo = b.binary_search(&c.lo[axis]).unwrap();
+                let hi = b.binary_search(&(c.hi[axis] + 1)).unwrap();
+                (lo, hi)
+            })
+            .collect::<Vec<_>>();
+            // Fill one contiguous run of the last axis per combination of the others.
+            let mut idx = ranges[..N - 1].iter().map(|r| r.0).collect::<Vec<_>>();
+            loop {
+                let row = idx.iter().zip(dims.iter()).fold(0, |acc, (&i, &d)| acc * d + i);
+                grid[row * row_len + ranges[N - 1].0..row * row_len + ranges[N - 1].1].set_all(*on);
+                // Odometer increment over the leading axes.
+                let mut axis = N - 1;
+                let done = loop {
+                    if axis == 0 {
+                        break true;
+                    }
+                    axis -= 1;
+                    idx[axis] += 1;
+                    if idx[axis] < ranges[axis].1 {
+                        break false;
+                    }
+                    idx[axis] = ranges[axis].0;
+                };
+                if done {
+                    break;
+                }
+            }
+        }
+
+        let width = |axis: usize, i: usize| (bounds[axis][i + 1] - bounds[axis][i]) as i128;
+        let mut total = 0i128;
+        for (row, bits) in grid.chunks(row_len).enumerate() {
+            let mut row_area = 1i128;
+            let mut r = row;
+            for axis in (0..N - 1).rev() {
+                row_area = row_area.checked_mul(width(axis, r % dims[axis]))?;
+                r /= dims[axis];
+            }
+            let len = bits.iter_ones().try_fold(0i128, |acc, i| acc.checked_add(width(N - 1, i)))?;
+            total = total.checked_add(row_area.checked_mul(len)?)?;
+        }
+        Some(total)
+    }
+}
+
+/// Segment tree over compressed coordinates answering "cover this range; how much of it was new?"
+struct CoverTree {
+    bounds: Vec<i64>,
+    full: Vec<bool>,
+    covered: Vec<i128>,
+}
+
+impl CoverTree {
+    fn new(bounds: Vec<i64>) -> Self {
+        let n = 4 * bounds.len().max(1);
+        Self { bounds, full: vec![false; n], covered: vec![0; n] }
+    }
+
+    /// Cover cells `l..r`, within node `node` spanning cells `nl..nr`.
+    fn cover(&mut self, node: usize, nl: usize, nr: usize, l: usize, r: usize) -> i128 {
+        if r <= nl || nr <= l || self.full[node] {
+            return 0;
+        }
+        if l <= nl && nr <= r {
+            let added = (self.bounds[nr] - self.bounds[nl]) as i128 - self.covered[node];
+            self.full[node] = true;
+            self.covered[node] += added;
+            return added;
+        }
+        let mid = (nl + nr) / 2;
+        let added = self.cover(2 * node, nl, mid, l, r) + self.cover(2 * node + 1, mid, nr, l, r);
+        self.covered[node] = self.covered[2 * node] + self.covered[2 * node + 1];
+        added
+    }
+}
+
+/// Sweep the first axis, recursing into each slab on the remaining ones.  The last axis is
+/// solved with a segment tree: visiting steps newest first, each point belongs to the first step
+/// that covers it.
+pub struct SweepLine;
+
+impl SweepLine {
+    fn sweep<const N: usize>(steps: &[&Step<N>], axis: usize) -> Option<i128> {
+        if !steps.iter().any(|(on, _)| *on) {
+            return Some(0);
+        }
+        let bounds = boundaries(steps, axis);
+        if axis == N - 1 {
+            let cells = bounds.len() - 1;
+            let mut tree = CoverTree::new(bounds);
+            let mut total = 0i128;
+            for (on, c) in steps.iter().rev() {
+                let l = tree.bounds.binary_search(&c.lo[axis]).unwrap();
+                let r = tree.bounds.binary_search(&(c.hi[axis] + 1)).unwrap();
+                let added = tree.cover(1, 0, cells, l, r);
+                if *on {
+                    total += added;
+                }
+            }
+            return Some(total);
+        }
+
+        let mut total = 0i128;
+        // Neighbouring slabs often see the same steps.
+        let mut prev: Option<(Vec<usize>, i128)> = None;
+        for w in bounds.windows(2) {
+            let active = (0..steps.len())
+                .filter(|&i| steps[i].1.lo[axis] <= w[0] && w[0] <= steps[i].1.hi[axis])
+                .collect::<Vec<_>>();
+            let area = match &prev {
+                Some((p, area)) if *p == active => *area,
+                _ => {
+                    let sub = active.iter().map(|&i| steps[i]).collect::<Vec<_>>();
+                    Self::sweep(&sub, axis + 1)?
+                }
+            };
+            total = total.checked_add(area.checked_mul((w[1] - w[0]) as i128)?)?;
+            prev = Some((active, area));
+        }
+        Some(total)
+    }
+}
+
+impl<const N: usize> VolumeEngine<N> for SweepLine {
+    fn name(&self) -> &'static str {
+        "sweep line"
+    }
+
+    fn volume(&self, steps: &[Step<N>]) -> Option<i128> {
+        if N == 0 {
+            return Some(0);
+        }
+        Self::sweep(&steps.iter().collect::<Vec<_>>(), 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::XorShift;
+
+    fn engines<const N: usize>() -> Vec<Box<dyn VolumeEngine<N>>> {
+        vec![
+            Box::new(BoxSplitting),
+            Box::new(InclusionExclusion),
+            Box::new(CoordinateCompression::default()),
+            Box::new(SweepLine),
+        ]
+    }
+
+    #[test]
+    fn engines_agree() {
+        let mut rng = XorShift::new(99);
+        let mut rand = |n: i64| rng.below(n as u64) as i64;
+        let steps = (0..40).map(|_| {
+            let lo = [rand(30) - 15, rand(30) - 15, rand(30) - 15];
+            let hi = [lo[0] + rand(12), lo[1] + rand(12), lo[2] + rand(12)];
+            (rand(3) != 0, Cuboid::new(lo, hi).unwrap())
+        })
+        .collect::<Vec<_>>();
+
+        let mut brute = 0;
+        for x in -15..30 {
+            for y in -15..30 {
+                for z in -15..30 {
+                    let last = steps.iter().rev().find(|(_, c)| c.contains(&[x, y, z]));
+                    brute += matches!(last, Some((true, _))) as i128;
+                }
+            }
+        }
+        for e in engines::<3>().iter() {
+            assert_eq!(e.volume(&steps), Some(brute), "{}", e.name());
+        }
+
+        let region = Cuboid::new([-5; 3], [5; 3]).unwrap();
+        let clipped = restrict(&steps, &region);
+        let mut set = CuboidSet::new();
+        for (on, c) in steps.iter() {
+            if *on { set.insert(*c) } else { set.remove(c) }
+        }
+        set.restrict(&region);
+        for e in engines::<3>().iter() {
+            assert_eq!(e.volume(&clipped), set.volume(), "{}", e.name());
+        }
+    }
+
+    #[test]
+    fn other_dimensions() {
+        let steps = vec![
+            (true, Cuboid::new([0, 0], [3, 3]).unwrap()),
+            (false, Cuboid::new([1, 1], [2, 5]).unwrap()),
+            (true, Cuboid::new([2, 2], [2, 2]).unwrap()),
+        ];
+        for e in engines::<2>().iter() {
+            assert_eq!(e.volume(&steps), Some(11), "{}", e.name());
+        }
+        let steps = vec![(true, Cuboid::new([0; 4], [9; 4]).unwrap()), (false, Cuboid::new([5; 4], [20; 4]).unwrap())];
+        for e in engines::<4>().iter() {
+            assert_eq!(e.volume(&steps), Some(10000 - 625), "{}", e.name());
+        }
+    }
+}